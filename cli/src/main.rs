@@ -0,0 +1,773 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Command line access to the vault, for scripts and remote sessions.
+//!
+//! Passphrases, passwords and `otpauth://` URIs are prompted without echo on
+//! the terminal, or read line by line from the file descriptor given with
+//! `--passphrase-fd`, so that they never show in the arguments.
+extern crate repassync_core;
+
+extern crate clap;
+extern crate rpassword;
+extern crate secstr;
+extern crate uuid;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate env_logger;
+
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::os::unix::io::FromRawFd;
+use std::process;
+use std::str;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use serde::Serialize;
+
+use repassync_core::model::{Vault, Entry};
+use repassync_core::io::error::VaultError;
+use repassync_core::io::file::{EncryptedVaultFile, vault_path, save_vault};
+use repassync_core::io::kdf::Kdf;
+use repassync_core::io::seal::VaultKey;
+use repassync_core::io::atomic::write_atomically;
+use repassync_core::io::device::device_id;
+use repassync_core::settings::Settings;
+use repassync_core::sync::{reconcile, synchronize, Reconciled, SyncError, SyncOutcome, SyncState};
+use repassync_core::sync::folder::merge_conflict_copies;
+use repassync_core::sync::git::{self, GitRepository, Pulled};
+use repassync_core::util::{PasswordGenerator, Query, Otp, OtpKind, check_password_quality};
+
+type CliResult<T> = Result<T, String>;
+
+fn main() {
+
+    env_logger::init().expect("Failed to initialize logging");
+
+    let matches = cli().get_matches();
+
+    if let Err(e) = run(&matches) {
+        eprintln!("repassync-cli: {}", e);
+        process::exit(1);
+    }
+
+}
+
+fn cli<'a, 'b>() -> App<'a, 'b> {
+    let entry_arg = Arg::with_name("entry")
+        .required(true)
+        .help("Identifier or name of the entry");
+    let generator_args = vec![
+        Arg::with_name("length").long("length").takes_value(true).default_value("20")
+            .help("Length of the generated password"),
+        Arg::with_name("no-lower").long("no-lower").help("Do not use lower case letters"),
+        Arg::with_name("no-upper").long("no-upper").help("Do not use upper case letters"),
+        Arg::with_name("no-numbers").long("no-numbers").help("Do not use numbers"),
+        Arg::with_name("no-special").long("no-special").help("Do not use special characters")
+    ];
+    let field_args = vec![
+        Arg::with_name("user").long("user").takes_value(true).help("User name, empty to remove it"),
+        Arg::with_name("uri").long("uri").takes_value(true).help("URI, empty to remove it"),
+        Arg::with_name("comment").long("comment").takes_value(true).help("Comment, empty to remove it"),
+        Arg::with_name("tag").long("tag").takes_value(true).multiple(true).number_of_values(1)
+            .help("Tag, may be repeated"),
+        Arg::with_name("otp").long("otp")
+            .help("Prompt for the otpauth:// URI of the one-time password generator, empty to remove it"),
+        Arg::with_name("generate").long("generate").help("Generate the password instead of prompting for it")
+    ];
+
+    App::new("repassync-cli")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Manage the repassync vault from the command line")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(Arg::with_name("json").long("json").global(true)
+             .help("Print the output as JSON"))
+        .arg(Arg::with_name("passphrase-fd").long("passphrase-fd").takes_value(true).global(true)
+             .help("Read passphrases and passwords from this file descriptor, one per line"))
+        .subcommand(SubCommand::with_name("init")
+                    .about("Create a new vault"))
+        .subcommand(SubCommand::with_name("unlock-check")
+                    .about("Check that the passphrase unlocks the vault"))
+        .subcommand(SubCommand::with_name("ls")
+                    .about("List the entries")
+                    .arg(Arg::with_name("query").multiple(true)
+                         .help("Only list entries matching the search, e.g. `tag:work`")))
+        .subcommand(SubCommand::with_name("show")
+                    .about("Show an entry")
+                    .arg(entry_arg.clone())
+                    .arg(Arg::with_name("password").long("password").help("Also show the password"))
+                    .arg(Arg::with_name("otp").long("otp").help("Also show the current one-time code")))
+        .subcommand(SubCommand::with_name("add")
+                    .about("Add an entry")
+                    .arg(Arg::with_name("name").required(true).help("Name of the entry"))
+                    .args(&field_args)
+                    .args(&generator_args))
+        .subcommand(SubCommand::with_name("edit")
+                    .about("Modify an entry")
+                    .arg(entry_arg.clone())
+                    .arg(Arg::with_name("name").long("name").takes_value(true).help("New name"))
+                    .arg(Arg::with_name("clear-tags").long("clear-tags").help("Remove the existing tags"))
+                    .arg(Arg::with_name("password").long("password").help("Prompt for a new password"))
+                    .args(&field_args)
+                    .args(&generator_args))
+        .subcommand(SubCommand::with_name("rm")
+                    .about("Move an entry to the trash")
+                    .arg(entry_arg)
+                    .arg(Arg::with_name("purge").long("purge").help("Delete the entry permanently")))
+        .subcommand(SubCommand::with_name("generate")
+                    .about("Generate a password")
+                    .args(&generator_args))
+        .subcommand(SubCommand::with_name("passwd")
+                    .about("Change the vault passphrase"))
+        .subcommand(SubCommand::with_name("sync")
+                    .about("Synchronize the vault with the configured server"))
+        .subcommand(SubCommand::with_name("history")
+                    .about("List the saved versions of the vault"))
+        .subcommand(SubCommand::with_name("restore")
+                    .about("Restore the vault, or one of its entries, as saved in a previous version")
+                    .arg(Arg::with_name("version").required(true).help("Identifier of the version, as listed by `history`"))
+                    .arg(Arg::with_name("entry").long("entry").takes_value(true)
+                         .help("Identifier or name of the entry to restore, instead of the whole vault")))
+}
+
+fn run(matches: &ArgMatches) -> CliResult<()> {
+    let json = matches.is_present("json");
+    let mut secrets = match matches.value_of("passphrase-fd") {
+        Some(fd) => {
+            let fd = fd.parse().map_err(|_| format!("invalid file descriptor {}", fd))?;
+            Secrets::from_fd(fd)
+        },
+        None => {
+            Secrets::prompt()
+        }
+    };
+
+    match matches.subcommand() {
+        ("init", Some(_)) => init(&mut secrets),
+        ("unlock-check", Some(_)) => {
+            open(&mut secrets)?;
+            print(json, &Status { unlocked: true }, "Vault unlocked")
+        },
+        ("ls", Some(args)) => ls(&mut secrets, json, args),
+        ("show", Some(args)) => show(&mut secrets, json, args),
+        ("add", Some(args)) => add(&mut secrets, json, args),
+        ("edit", Some(args)) => edit(&mut secrets, args),
+        ("rm", Some(args)) => rm(&mut secrets, args),
+        ("generate", Some(args)) => {
+            let password = generate(args)?;
+            print(json, &Generated { password: password.clone() }, password)
+        },
+        ("passwd", Some(_)) => passwd(&mut secrets),
+        ("sync", Some(_)) => sync(&mut secrets, json),
+        ("history", Some(_)) => history(json),
+        ("restore", Some(args)) => restore(&mut secrets, args),
+        _ => Err("unknown command".to_owned())
+    }
+}
+
+/// Where passphrases and passwords are read from.
+struct Secrets {
+    reader: Option<BufReader<File>>
+}
+
+impl Secrets {
+
+    fn prompt() -> Secrets {
+        Secrets { reader: None }
+    }
+
+    fn from_fd(fd: i32) -> Secrets {
+        let file = unsafe { File::from_raw_fd(fd) };
+        Secrets { reader: Some(BufReader::new(file)) }
+    }
+
+    fn read(&mut self, prompt: &str) -> CliResult<SecStr> {
+        let mut secret = match self.reader {
+            Some(ref mut reader) => {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) => return Err("no more secrets to read".to_owned()),
+                    Ok(_) => line,
+                    Err(e) => return Err(format!("unable to read secret: {}", e))
+                }
+            },
+            None => {
+                rpassword::prompt_password_stderr(prompt).map_err(|e| format!("unable to read secret: {}", e))?
+            }
+        };
+        while secret.ends_with('\n') || secret.ends_with('\r') {
+            secret.pop();
+        }
+        Ok(SecStr::new(secret.into_bytes()))
+    }
+
+    /// Reads a new secret, asking for confirmation when prompting.
+    fn read_new(&mut self, prompt: &str) -> CliResult<SecStr> {
+        let secret = self.read(prompt)?;
+        if self.reader.is_none() {
+            let confirm = self.read("Confirm: ")?;
+            if confirm != secret {
+                return Err("passphrases do not match".to_owned());
+            }
+        }
+        Ok(secret)
+    }
+
+}
+
+/// Opens the vault as it is on disk, for the commands that only read it.
+fn open(secrets: &mut Secrets) -> CliResult<(Vault, SecStr, Kdf)> {
+    let path = vault_path().map_err(to_string)?;
+    let file = EncryptedVaultFile::from_file(&path).map_err(|e| match e {
+        VaultError::NotFound => "no vault found, create one with `repassync-cli init`".to_owned(),
+        e => format!("unable to open vault file {}: {}", path.display(), e)
+    })?;
+    let passphrase = secrets.read("Passphrase: ")?;
+    let vault = file.decrypt(passphrase.clone())
+        .and_then(|decrypted| decrypted.to_vault())
+        .map_err(|e| match e {
+            VaultError::WrongPassphrase => "unable to unlock the vault, is the passphrase correct?".to_owned(),
+            e => format!("unable to unlock the vault: {}", e)
+        })?;
+    Ok((vault, passphrase, file.kdf().upgraded()))
+}
+
+/// Opens the vault to modify it, with the versions saved on other devices merged in.
+fn unlock(secrets: &mut Secrets) -> CliResult<(Vault, SecStr, Kdf)> {
+    let (vault, passphrase, kdf) = open(secrets)?;
+    let vault = bring_up_to_date(vault, &passphrase, &kdf)?;
+    Ok((vault, passphrase, kdf))
+}
+
+/// Merges the versions of the vault saved on other devices, from the git
+/// remote of its history and from the conflict copies of its folder.
+fn bring_up_to_date(mut vault: Vault, passphrase: &SecStr, kdf: &Kdf) -> CliResult<Vault> {
+    let path = vault_path().map_err(to_string)?;
+    let device = device()?;
+    if let Some(pulled) = pull_history(passphrase, kdf, device) {
+        vault = pulled;
+    }
+    // left by a file synchronization tool sharing the vault folder
+    let merged = merge_conflict_copies(&path, passphrase, kdf, device)
+        .map_err(|e| format!("unable to merge conflict copies: {}", e))?;
+    if let Some(merged) = merged {
+        merged.write(&path).map_err(|e| format!("unable to save vault: {}", e))?;
+        SyncState::mark_dirty().map_err(|e| format!("unable to save synchronization state: {}", e))?;
+        eprintln!("Merged {} conflict copies into the vault", merged.merged.len());
+        if merged.conflicts > 0 {
+            eprintln!("{} entries changed on both sides are left to settle in the application", merged.conflicts);
+        }
+        vault = merged.vault.to_vault().map_err(|e| format!("unable to unlock the vault: {}", e))?;
+    }
+    vault.set_device(device);
+    Ok(vault)
+}
+
+fn device() -> CliResult<Uuid> {
+    device_id().map_err(|e| format!("unable to identify this device: {}", e))
+}
+
+fn save(vault: &Vault, passphrase: SecStr, kdf: &Kdf) -> CliResult<()> {
+    save_vault(vault, passphrase.clone(), kdf).map_err(|e| format!("unable to save vault: {}", e))?;
+    SyncState::mark_dirty().map_err(|e| format!("unable to save synchronization state: {}", e))?;
+    // the vault is saved, failing to record it in its history is only reported
+    let repository = match Settings::load().git_repository() {
+        Some(repository) => repository.and_then(|repository| {
+            repository.commit("Save vault")?;
+            Ok(repository)
+        }),
+        None => return Ok(())
+    };
+    let repository = match repository {
+        Ok(repository) => repository,
+        Err(e) => {
+            eprintln!("repassync-cli: unable to record the vault history: {}", e);
+            return Ok(());
+        }
+    };
+    match git::synchronize(&repository, Some(&passphrase), kdf, device()?) {
+        Ok(Pulled::UpToDate) => (),
+        Ok(_) => eprintln!("The vault history changed on another device, it is merged the next time the vault is opened"),
+        Err(e) => eprintln!("repassync-cli: unable to push the vault history, it is pushed the next time: {}", e)
+    }
+    Ok(())
+}
+
+/// Brings the versions saved on other devices from the git remote of the
+/// vault history, returns the vault if it changed.
+///
+/// The vault stays usable while the remote cannot be reached.
+fn pull_history(passphrase: &SecStr, kdf: &Kdf, device: Uuid) -> Option<Vault> {
+    let repository = match Settings::load().git_repository() {
+        Some(Ok(repository)) => repository,
+        Some(Err(e)) => {
+            eprintln!("repassync-cli: unable to open the vault history: {}", e);
+            return None;
+        },
+        None => return None
+    };
+    match git::synchronize(&repository, Some(passphrase), kdf, device) {
+        Ok(Pulled::Pulled { commit, content, merged }) => {
+            if let Err(e) = repository.advance(&commit, &content) {
+                eprintln!("repassync-cli: unable to update the vault from its history: {}", e);
+                return None;
+            }
+            // the new version is pushed back if it is a merge
+            if let Err(e) = git::synchronize(&repository, Some(passphrase), kdf, device) {
+                eprintln!("repassync-cli: unable to push the vault history: {}", e);
+            }
+            let vault = match merged {
+                Some((merged, conflicts)) => {
+                    eprintln!("Merged the versions of the vault saved on other devices");
+                    if conflicts > 0 {
+                        eprintln!("{} entries changed on both sides are left to settle in the application", conflicts);
+                    }
+                    merged.to_vault()
+                },
+                None => {
+                    EncryptedVaultFile::from_bytes(&content)
+                        .and_then(|file| file.decrypt(passphrase.clone()))
+                        .and_then(|decrypted| decrypted.to_vault())
+                }
+            };
+            match vault {
+                Ok(vault) => Some(vault),
+                Err(e) => {
+                    eprintln!("repassync-cli: unable to open the version of the vault from its history: {}", e);
+                    None
+                }
+            }
+        },
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("repassync-cli: unable to synchronize the vault history, it is synchronized the next time: {}", e);
+            None
+        }
+    }
+}
+
+fn git_repository() -> CliResult<GitRepository> {
+    match Settings::load().git_repository() {
+        Some(repository) => repository.map_err(|e| format!("unable to open the vault history: {}", e)),
+        None => Err("the vault history is not enabled, set `git_history` in settings.toml".to_owned())
+    }
+}
+
+fn init(secrets: &mut Secrets) -> CliResult<()> {
+    let path = vault_path().map_err(to_string)?;
+    if path.exists() {
+        return Err(format!("a vault already exists in {}", path.display()));
+    }
+    let passphrase = secrets.read_new("New passphrase: ")?;
+    warn_quality(&passphrase);
+    let key = VaultKey::generate().map_err(to_string)?;
+    let mut vault = Vault::new("".to_owned(), key);
+    vault.set_device(device()?);
+    let kdf = Kdf::calibrate(Settings::load().unlock_time()).map_err(to_string)?;
+    save(&vault, passphrase, &kdf)
+}
+
+fn passwd(secrets: &mut Secrets) -> CliResult<()> {
+    let (vault, _, kdf) = unlock(secrets)?;
+    let passphrase = secrets.read_new("New passphrase: ")?;
+    warn_quality(&passphrase);
+    save(&vault, passphrase, &kdf)
+}
+
+fn sync(secrets: &mut Secrets, json: bool) -> CliResult<()> {
+    let client = match Settings::load().sync_client() {
+        Some(client) => client.map_err(to_string)?,
+        None => return Err("synchronization is not configured, set `sync_url` in settings.toml".to_owned())
+    };
+    let path = vault_path().map_err(to_string)?;
+    let local = match fs::read(&path) {
+        Ok(content) => Some(content),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("unable to read vault file: {}", e))
+    };
+    let mut state = SyncState::load();
+    let outcome = synchronize(&client, &mut state, local.as_ref().map(Vec::as_slice))
+        .map_err(|e| match e {
+            SyncError::Http(e) => format!("unable to reach the server, the local vault is kept until the next synchronization: {}", e),
+            e => format!("unable to synchronize: {}", e)
+        })?;
+    let (outcome, text, conflicts) = match outcome {
+        SyncOutcome::UpToDate => ("up-to-date", "Vault already up to date".to_owned(), 0),
+        SyncOutcome::Pushed => {
+            if let Some(ref local) = local {
+                SyncState::save_base(local).map_err(|e| format!("unable to save synchronization base: {}", e))?;
+            }
+            ("pushed", "Vault uploaded to the server".to_owned(), 0)
+        },
+        SyncOutcome::Pulled(content) => {
+            write_atomically(&path, &content).map_err(|e| format!("unable to write vault file: {}", e))?;
+            SyncState::save_base(&content).map_err(|e| format!("unable to save synchronization base: {}", e))?;
+            ("pulled", "Vault downloaded from the server".to_owned(), 0)
+        },
+        SyncOutcome::Conflict(remote, etag) => {
+            // the vault was changed both locally and on the server
+            let local = local.unwrap_or_default();
+            let (_, passphrase, kdf) = open(secrets)?;
+            let base = SyncState::load_base();
+            let reconciled = reconcile(&client, &mut state, &passphrase, &kdf, &local, &remote, &etag, base.as_ref().map(Vec::as_slice))
+                .map_err(|e| format!("unable to merge with the server version: {}", e))?;
+            match reconciled {
+                Reconciled::Merged { content, conflicts, .. } => {
+                    write_atomically(&path, &content).map_err(|e| format!("unable to write vault file: {}", e))?;
+                    SyncState::save_base(&content).map_err(|e| format!("unable to save synchronization base: {}", e))?;
+                    let text = match conflicts {
+                        0 => "Vault merged with the server version".to_owned(),
+                        n => format!("Vault merged with the server version, {} entries changed on both sides are left to settle in the application", n)
+                    };
+                    ("merged", text, conflicts)
+                },
+                Reconciled::Retry => {
+                    return Err("the server version changed while merging, try again".to_owned());
+                }
+            }
+        }
+    };
+    state.save().map_err(|e| format!("unable to save synchronization state: {}", e))?;
+    print(json, &Synced { outcome, conflicts }, text)
+}
+
+fn history(json: bool) -> CliResult<()> {
+    let commits = git_repository()?.history().map_err(|e| format!("unable to read the vault history: {}", e))?;
+    let versions: Vec<Version> = commits.into_iter()
+        .map(|commit| Version { id: commit.id, date: commit.date.to_rfc3339(), message: commit.message })
+        .collect();
+    let text = versions.iter()
+        .map(|version| format!("{}\t{}\t{}", version.id, version.date, version.message))
+        .collect::<Vec<_>>()
+        .join("\n");
+    print(json, &versions, text)
+}
+
+fn restore(secrets: &mut Secrets, args: &ArgMatches) -> CliResult<()> {
+    let repository = git_repository()?;
+    let (mut vault, passphrase, kdf) = unlock(secrets)?;
+    let version = args.value_of("version").unwrap();
+    let content = repository.content_at(version).map_err(|e| format!("unable to read version {}: {}", version, e))?;
+    let file = EncryptedVaultFile::from_bytes(&content).map_err(|e| format!("unable to read version {}: {}", version, e))?;
+    let decrypted = match file.decrypt(passphrase.clone()) {
+        // saved before the passphrase was changed
+        Err(VaultError::WrongPassphrase) => file.decrypt(secrets.read("Passphrase of this version: ")?),
+        decrypted => decrypted
+    };
+    let past = decrypted.and_then(|decrypted| decrypted.to_vault()).map_err(|e| match e {
+        VaultError::WrongPassphrase => "unable to open this version, is the passphrase correct?".to_owned(),
+        e => format!("unable to open version {}: {}", version, e)
+    })?;
+    match args.value_of("entry") {
+        Some(key) => {
+            let entry = find_entry(&past, key)?;
+            vault.restore_past_entry(&past, entry.id()).map_err(to_string)?;
+        },
+        None => vault.restore_past_version(&past).map_err(to_string)?
+    }
+    save(&vault, passphrase, &kdf)
+}
+
+fn ls(secrets: &mut Secrets, json: bool, args: &ArgMatches) -> CliResult<()> {
+    let (vault, _, _) = open(secrets)?;
+    let query = args.values_of("query")
+        .map(|terms| terms.collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    let query = Query::parse(&query);
+    let mut entries: Vec<&Entry> = vault.into_iter().filter(|entry| query.matches(entry)).collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    let summaries: Vec<EntryOutput> = entries.into_iter().map(EntryOutput::from_entry).collect();
+    let text = summaries.iter()
+        .map(|entry| format!("{}\t{}\t{}", entry.id, entry.name, entry.user.as_ref().map(|u| u.as_str()).unwrap_or("")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    print(json, &summaries, text)
+}
+
+fn show(secrets: &mut Secrets, json: bool, args: &ArgMatches) -> CliResult<()> {
+    let (vault, passphrase, kdf) = open(secrets)?;
+    let entry = find_entry(&vault, args.value_of("entry").unwrap())?;
+    let mut output = EntryOutput::from_entry(&entry);
+    if args.is_present("password") {
+        let password = vault.reveal_password(&entry).map_err(to_string)?;
+        output.password = Some(utf8(password)?);
+    }
+    if args.is_present("otp") {
+        let otp = entry_otp(&vault, &entry)?;
+        output.otp_code = Some(match otp.kind {
+            OtpKind::Totp { .. } => otp.current().map_err(to_string)?.0,
+            OtpKind::Hotp { .. } => {
+                // a counter based code is used once shown, the counter is saved like in the application
+                let mut vault = bring_up_to_date(vault, &passphrase, &kdf)?;
+                let entry = vault.get_entry(entry.id()).cloned()
+                    .ok_or_else(|| format!("entry {} was deleted on another device", entry.name))?;
+                let mut otp = entry_otp(&vault, &entry)?;
+                let code = otp.current().map_err(to_string)?.0;
+                otp.increment();
+                vault.set_otp(entry.id(), Some(otp.to_uri())).map_err(to_string)?;
+                save(&vault, passphrase, &kdf)?;
+                code
+            }
+        });
+    }
+    let text = output.to_text();
+    print(json, &output, text)
+}
+
+fn entry_otp(vault: &Vault, entry: &Entry) -> CliResult<Otp> {
+    let uri = vault.reveal_otp(entry).map_err(to_string)?
+        .ok_or_else(|| format!("entry {} has no one-time password", entry.name))?;
+    Otp::parse(&utf8(uri)?).map_err(to_string)
+}
+
+fn add(secrets: &mut Secrets, json: bool, args: &ArgMatches) -> CliResult<()> {
+    let (mut vault, passphrase, kdf) = unlock(secrets)?;
+    let name = args.value_of("name").unwrap().to_owned();
+    let password =
+        if args.is_present("generate") {
+            SecStr::new(generate(args)?.into_bytes())
+        } else {
+            secrets.read_new(&format!("Password for {}: ", name))?
+        };
+    let id = vault.add_entry(name, password).map_err(to_string)?.id().clone();
+    update_fields(&mut vault, &id, args, secrets)?;
+    save(&vault, passphrase, &kdf)?;
+    print(json, &Added { id: id.to_string() }, id)
+}
+
+fn edit(secrets: &mut Secrets, args: &ArgMatches) -> CliResult<()> {
+    let (mut vault, passphrase, kdf) = unlock(secrets)?;
+    let entry = find_entry(&vault, args.value_of("entry").unwrap())?;
+    let id = entry.id().clone();
+    if let Some(name) = args.value_of("name") {
+        vault.update_entry(&id, |entry| entry.name = name.to_owned());
+    }
+    if args.is_present("clear-tags") {
+        vault.update_entry(&id, |entry| entry.tags.clear());
+    }
+    if args.is_present("generate") {
+        vault.set_password(&id, SecStr::new(generate(args)?.into_bytes())).map_err(to_string)?;
+    } else if args.is_present("password") {
+        let password = secrets.read_new(&format!("New password for {}: ", entry.name))?;
+        vault.set_password(&id, password).map_err(to_string)?;
+    }
+    update_fields(&mut vault, &id, args, secrets)?;
+    save(&vault, passphrase, &kdf)
+}
+
+fn rm(secrets: &mut Secrets, args: &ArgMatches) -> CliResult<()> {
+    let (mut vault, passphrase, kdf) = unlock(secrets)?;
+    let entry = find_entry(&vault, args.value_of("entry").unwrap())?;
+    vault.trash_entry(entry.id());
+    if args.is_present("purge") {
+        vault.purge_entry(entry.id());
+    }
+    save(&vault, passphrase, &kdf)
+}
+
+/// Applies the `--user`, `--uri`, `--comment`, `--tag` and `--otp` options to the entry.
+fn update_fields(vault: &mut Vault, id: &Uuid, args: &ArgMatches, secrets: &mut Secrets) -> CliResult<()> {
+    let optional = |name: &str| {
+        args.value_of(name).map(|value| {
+            let value = value.trim();
+            if value.is_empty() { None } else { Some(value.to_owned()) }
+        })
+    };
+    let user = optional("user");
+    let uri = optional("uri");
+    let comment = optional("comment");
+    let tags: Vec<String> = args.values_of("tag").map(|tags| tags.map(|t| t.to_owned()).collect()).unwrap_or_default();
+    if user.is_some() || uri.is_some() || comment.is_some() || !tags.is_empty() {
+        vault.update_entry(id, move |entry| {
+            if let Some(user) = user {
+                entry.user = user;
+            }
+            if let Some(uri) = uri {
+                entry.uri = uri;
+            }
+            if let Some(comment) = comment {
+                entry.comment = comment;
+            }
+            entry.tags.extend(tags);
+        });
+    }
+    if args.is_present("otp") {
+        let otp = utf8(secrets.read("otpauth:// URI: ")?)?;
+        let otp = otp.trim();
+        let otp = if otp.is_empty() {
+            None
+        } else {
+            Otp::parse(otp).map_err(to_string)?;
+            Some(SecStr::new(otp.as_bytes().to_vec()))
+        };
+        vault.set_otp(id, otp).map_err(to_string)?;
+    }
+    Ok(())
+}
+
+/// Finds an entry by identifier, or by name if it is unique.
+fn find_entry(vault: &Vault, key: &str) -> CliResult<Entry> {
+    if let Ok(id) = Uuid::parse_str(key) {
+        if let Some(entry) = vault.get_entry(&id) {
+            return Ok(entry.clone());
+        }
+    }
+    let found: Vec<&Entry> = vault.into_iter().filter(|entry| entry.name == key).collect();
+    match found.len() {
+        0 => Err(format!("no entry {}", key)),
+        1 => Ok(found[0].clone()),
+        _ => {
+            let ids: Vec<String> = found.iter().map(|entry| entry.id().to_string()).collect();
+            Err(format!("several entries are named {}, use one of {}", key, ids.join(", ")))
+        }
+    }
+}
+
+fn generate(args: &ArgMatches) -> CliResult<String> {
+    let length = args.value_of("length").unwrap_or("20");
+    let length = length.parse().map_err(|_| format!("invalid length {}", length))?;
+    let use_lower = !args.is_present("no-lower");
+    let use_upper = !args.is_present("no-upper");
+    let use_numbers = !args.is_present("no-numbers");
+    let use_special = !args.is_present("no-special");
+    if !(use_lower || use_upper || use_numbers || use_special) {
+        return Err("no characters left to generate a password".to_owned());
+    }
+    Ok(PasswordGenerator::new(length, use_lower, use_upper, use_numbers, use_special).generate())
+}
+
+fn warn_quality(passphrase: &SecStr) {
+    if let Ok(passphrase) = str::from_utf8(passphrase.unsecure()) {
+        eprintln!("Passphrase strength: {:?}", check_password_quality(passphrase));
+    }
+}
+
+fn utf8(secret: SecStr) -> CliResult<String> {
+    String::from_utf8(secret.unsecure().to_vec()).map_err(to_string)
+}
+
+fn to_string<E: Display>(e: E) -> String {
+    e.to_string()
+}
+
+fn print<T: Serialize, S: Display>(json: bool, value: &T, text: S) -> CliResult<()> {
+    if json {
+        let json = serde_json::to_string_pretty(value).map_err(to_string)?;
+        println!("{}", json);
+    } else {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Status {
+    unlocked: bool
+}
+
+#[derive(Serialize)]
+struct Synced {
+    outcome: &'static str,
+    conflicts: usize
+}
+
+#[derive(Serialize)]
+struct Version {
+    id: String,
+    date: String,
+    message: String
+}
+
+#[derive(Serialize)]
+struct Generated {
+    password: String
+}
+
+#[derive(Serialize)]
+struct Added {
+    id: String
+}
+
+#[derive(Serialize)]
+struct EntryOutput {
+    id: String,
+    name: String,
+    user: Option<String>,
+    uri: Option<String>,
+    comment: Option<String>,
+    tags: Vec<String>,
+    otp: bool,
+    created: String,
+    last_modified: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    otp_code: Option<String>
+}
+
+impl EntryOutput {
+
+    fn from_entry(entry: &Entry) -> EntryOutput {
+        let mut tags: Vec<String> = entry.tags.iter().cloned().collect();
+        tags.sort();
+        EntryOutput {
+            id: entry.id().to_string(),
+            name: entry.name.clone(),
+            user: entry.user.clone(),
+            uri: entry.uri.clone(),
+            comment: entry.comment.clone(),
+            tags: tags,
+            otp: entry.has_otp(),
+            created: entry.created().to_rfc3339(),
+            last_modified: entry.last_modified().to_rfc3339(),
+            password: None,
+            otp_code: None
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("id: {}", self.id),
+            format!("name: {}", self.name)
+        ];
+        if let Some(ref user) = self.user {
+            lines.push(format!("user: {}", user));
+        }
+        if let Some(ref uri) = self.uri {
+            lines.push(format!("uri: {}", uri));
+        }
+        if !self.tags.is_empty() {
+            lines.push(format!("tags: {}", self.tags.join(", ")));
+        }
+        if let Some(ref comment) = self.comment {
+            lines.push(format!("comment: {}", comment));
+        }
+        lines.push(format!("created: {}", self.created));
+        lines.push(format!("last modified: {}", self.last_modified));
+        if let Some(ref password) = self.password {
+            lines.push(format!("password: {}", password));
+        }
+        if let Some(ref code) = self.otp_code {
+            lines.push(format!("one-time code: {}", code));
+        }
+        lines.join("\n")
+    }
+
+}
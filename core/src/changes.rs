@@ -0,0 +1,224 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! The log of the changes made to a vault, and by which device.
+//!
+//! Each change is stamped with the device that made it and a Lamport clock,
+//! one more than the greatest clock known to that device. Permanently deleted
+//! entries leave a tombstone, so that a copy that still has the entry does not
+//! bring it back when merged.
+//!
+//! Every time a device writes the vault, it acknowledges the changes it knows
+//! of. Changes and tombstones acknowledged by every device that ever wrote
+//! the vault are dropped, they can no longer be missing from any copy. The
+//! last change of each device to an existing entry is kept though: a copy
+//! written by a device this one never heard of may still miss it, and it
+//! tells which side changed the entry when merging without a common version.
+use std::collections::{HashMap, HashSet};
+
+use chrono::prelude::*;
+use chrono::serde::ts_seconds;
+
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OperationKind {
+    Added,
+    Modified,
+    Trashed,
+    Restored,
+    /// Permanently deleted.
+    Purged
+}
+
+/// A change made to an entry.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Operation {
+    pub entry: Uuid,
+    pub kind: OperationKind,
+    pub device: Uuid,
+    pub clock: u64,
+    #[serde(with="ts_seconds")]
+    pub date: DateTime<Utc>
+}
+
+/// What remains of a permanently deleted entry.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Tombstone {
+    #[serde(with="ts_seconds")]
+    pub deleted: DateTime<Utc>,
+    pub device: Uuid,
+    pub clock: u64
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ChangeLog {
+    /// changes not yet acknowledged by every device, by increasing clock
+    #[serde(default)]
+    operations: Vec<Operation>,
+    /// permanently deleted entries, by identifier
+    #[serde(default)]
+    tombstones: HashMap<Uuid, Tombstone>,
+    /// for each device, the clock of its last change known to this copy
+    #[serde(default)]
+    seen: HashMap<Uuid, u64>,
+    /// for each device, the changes it knew of the last time it wrote the vault
+    #[serde(default)]
+    acknowledged: HashMap<Uuid, HashMap<Uuid, u64>>,
+    /// the device using this copy, changes are recorded on its behalf
+    #[serde(skip)]
+    device: Option<Uuid>
+}
+
+impl ChangeLog {
+
+    pub fn set_device(&mut self, device: Uuid) {
+        self.device = Some(device);
+    }
+
+    fn device(&self) -> Uuid {
+        self.device.unwrap_or_else(Uuid::nil)
+    }
+
+    /// The greatest clock of the known changes.
+    pub fn clock(&self) -> u64 {
+        self.seen.values().cloned().max().unwrap_or(0)
+    }
+
+    /// The changes not yet acknowledged by every device, oldest first.
+    pub fn operations(&self) -> ::std::slice::Iter<Operation> {
+        self.operations.iter()
+    }
+
+    pub fn tombstone(&self, id: &Uuid) -> Option<&Tombstone> {
+        self.tombstones.get(id)
+    }
+
+    /// Whether the entry was changed by a change `other` does not know of.
+    pub fn changed_since(&self, other: &ChangeLog, id: &Uuid) -> bool {
+        self.operations.iter().any(|op| {
+            &op.entry == id && other.seen.get(&op.device).map(|&clock| clock < op.clock).unwrap_or(true)
+        })
+    }
+
+    /// Records a change made by this device to the entry.
+    pub fn record(&mut self, id: &Uuid, kind: OperationKind) {
+        let device = self.device();
+        let clock = self.clock() + 1;
+        let date = Utc::now();
+        self.seen.insert(device, clock);
+        match kind {
+            OperationKind::Purged => {
+                self.tombstones.insert(id.clone(), Tombstone { deleted: date, device: device, clock: clock });
+            },
+            _ => {
+                // the entry is back
+                self.tombstones.remove(id);
+            }
+        }
+        self.operations.push(Operation { entry: id.clone(), kind: kind, device: device, clock: clock, date: date });
+        self.acknowledge();
+    }
+
+    /// Drops the tombstone of an entry that was brought back by a merge.
+    pub fn forget_tombstone(&mut self, id: &Uuid) {
+        self.tombstones.remove(id);
+    }
+
+    /// Records that this device knows of every change of the log, and
+    /// drops the ones every device now knows of.
+    pub fn acknowledge(&mut self) {
+        let device = self.device();
+        self.acknowledged.insert(device, self.seen.clone());
+        self.compact();
+    }
+
+    fn compact(&mut self) {
+        let acknowledged = &self.acknowledged;
+        let known_everywhere = |device: &Uuid, clock: u64| {
+            acknowledged.values().all(|ack| ack.get(device).map(|&c| c >= clock).unwrap_or(false))
+        };
+        self.tombstones.retain(|_, tombstone| !known_everywhere(&tombstone.device, tombstone.clock));
+
+        let mut last = HashMap::new();
+        for op in &self.operations {
+            let clock = last.entry((op.entry, op.device)).or_insert(op.clock);
+            if *clock < op.clock {
+                *clock = op.clock;
+            }
+        }
+        // permanently deleted entries every device knows of
+        let mut gone = HashSet::new();
+        for op in &self.operations {
+            if op.kind == OperationKind::Purged && known_everywhere(&op.device, op.clock) {
+                gone.insert(op.entry);
+            } else {
+                gone.remove(&op.entry);
+            }
+        }
+        self.operations.retain(|op| {
+            !known_everywhere(&op.device, op.clock) || (last[&(op.entry, op.device)] == op.clock && !gone.contains(&op.entry))
+        });
+    }
+
+    /// Combines the changes known to two copies of the vault.
+    ///
+    /// The result is used by the device of `local`, and is not acknowledged
+    /// until it writes the vault.
+    pub fn merge(local: &ChangeLog, remote: &ChangeLog) -> ChangeLog {
+        let mut operations = local.operations.clone();
+        for op in &remote.operations {
+            if !operations.iter().any(|known| known.device == op.device && known.clock == op.clock) {
+                operations.push(op.clone());
+            }
+        }
+        operations.sort_by(|a, b| (a.clock, a.device).cmp(&(b.clock, b.device)));
+
+        let mut tombstones = local.tombstones.clone();
+        for (id, tombstone) in &remote.tombstones {
+            let later = tombstones.get(id).map(|known| known.clock < tombstone.clock).unwrap_or(true);
+            if later {
+                tombstones.insert(id.clone(), tombstone.clone());
+            }
+        }
+
+        let mut acknowledged = local.acknowledged.clone();
+        for (device, ack) in &remote.acknowledged {
+            let known = acknowledged.entry(device.clone()).or_insert_with(HashMap::new);
+            max_clocks(known, ack);
+        }
+        let mut seen = local.seen.clone();
+        max_clocks(&mut seen, &remote.seen);
+
+        let mut merged = ChangeLog {
+            operations: operations,
+            tombstones: tombstones,
+            seen: seen,
+            acknowledged: acknowledged,
+            device: local.device
+        };
+        // one side may have dropped what the other one still keeps
+        merged.compact();
+        merged
+    }
+
+}
+
+/// Keeps the greatest clock of each device.
+fn max_clocks(clocks: &mut HashMap<Uuid, u64>, other: &HashMap<Uuid, u64>) {
+    for (device, &clock) in other {
+        let known = clocks.entry(device.clone()).or_insert(0);
+        if *known < clock {
+            *known = clock;
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::path::{Path, PathBuf};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Write, Result, Error, ErrorKind};
+use std::os::unix::fs::OpenOptionsExt;
+
+use openssl::rand::rand_bytes;
+
+/// Atomically replaces the content of `file_path` with `content`.
+///
+/// The content is first written to a temporary file in the same directory,
+/// which is synced to disk and then renamed over the target. The directory
+/// itself is synced afterwards so that the rename is durable. If anything
+/// fails before the rename, the previous file is left untouched.
+pub fn write_atomically<P: AsRef<Path>>(file_path: P, content: &[u8]) -> Result<()> {
+    let file_path = file_path.as_ref();
+    let dir = match file_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from(".")
+    };
+    let tmp_path = temporary_path(&dir, file_path)?;
+
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, file_path));
+
+    match written {
+        Ok(()) => {
+            File::open(&dir)?.sync_all()
+        },
+        Err(e) => {
+            // best effort, the original error is the interesting one
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+fn temporary_path(dir: &Path, file_path: &Path) -> Result<PathBuf> {
+    let name = match file_path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return Err(Error::new(ErrorKind::InvalidInput, "not a file path"))
+    };
+    let mut suffix = [0; 8];
+    rand_bytes(&mut suffix).map_err(|e| Error::new(ErrorKind::Other, e))?;
+    let suffix: String = suffix.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(dir.join(format!(".{}.{}.tmp", name, suffix)))
+}
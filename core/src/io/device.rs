@@ -0,0 +1,40 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::fs::File;
+use std::io::{Read, Result, Error, ErrorKind};
+
+use xdg;
+
+use uuid::Uuid;
+
+use io::atomic::write_atomically;
+
+/// The identifier of this device, generated on first use and stored in `$XDG_DATA_HOME/repassync/device`.
+///
+/// It stamps the changes made to the vault, and is shared by every client of the same user on this machine.
+pub fn device_id() -> Result<Uuid> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("repassync")
+        .map_err(|e| Error::new(ErrorKind::Other, e))?;
+    if let Some(path) = xdg_dirs.find_data_file("device") {
+        let mut content = String::new();
+        File::open(&path)?.read_to_string(&mut content)?;
+        match Uuid::parse_str(content.trim()) {
+            Ok(id) => return Ok(id),
+            Err(e) => warn!("Invalid device identifier in {}, generating another one: {}", path.display(), e)
+        }
+    }
+    let id = Uuid::new_v4();
+    let path = xdg_dirs.place_data_file("device")?;
+    write_atomically(path, id.to_string().as_bytes())?;
+    Ok(id)
+}
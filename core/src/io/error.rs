@@ -0,0 +1,116 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+use serde_cbor;
+
+use openssl::error::ErrorStack;
+
+/// The failures of reading, writing, encrypting and decrypting vault files.
+#[derive(Debug)]
+pub enum VaultError {
+    /// The vault file does not exist.
+    NotFound,
+    /// The vault file or its directory cannot be accessed.
+    PermissionDenied,
+    /// Any other input/output error.
+    Io(io::Error),
+    /// The passphrase does not open the vault, or the file was modified.
+    WrongPassphrase,
+    /// The file is not a valid vault file.
+    Corrupted(String),
+    /// The vault could not be serialized to be written.
+    Serialization(serde_cbor::error::Error),
+    /// The file was written by a newer version of repassync.
+    UnsupportedVersion(u8),
+    /// The cryptographic library failed.
+    Crypto(ErrorStack)
+}
+
+pub type Result<T> = result::Result<T, VaultError>;
+
+impl fmt::Display for VaultError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VaultError::NotFound => write!(f, "vault file not found"),
+            VaultError::PermissionDenied => write!(f, "permission denied on the vault file"),
+            VaultError::Io(ref e) => write!(f, "{}", e),
+            VaultError::WrongPassphrase => write!(f, "wrong passphrase, or the vault file was modified"),
+            VaultError::Corrupted(ref msg) => write!(f, "corrupted vault file: {}", msg),
+            VaultError::Serialization(ref e) => write!(f, "unable to serialize the vault: {}", e),
+            VaultError::UnsupportedVersion(version) => write!(f, "unsupported vault version {}", version),
+            VaultError::Crypto(ref e) => write!(f, "{}", e)
+        }
+    }
+
+}
+
+impl error::Error for VaultError {
+
+    fn description(&self) -> &str {
+        match *self {
+            VaultError::NotFound => "vault file not found",
+            VaultError::PermissionDenied => "permission denied on the vault file",
+            VaultError::Io(ref e) => e.description(),
+            VaultError::WrongPassphrase => "wrong passphrase",
+            VaultError::Corrupted(_) => "corrupted vault file",
+            VaultError::Serialization(_) => "unable to serialize the vault",
+            VaultError::UnsupportedVersion(_) => "unsupported vault version",
+            VaultError::Crypto(ref e) => e.description()
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            VaultError::Io(ref e) => Some(e),
+            VaultError::Serialization(ref e) => Some(e),
+            VaultError::Crypto(ref e) => Some(e),
+            _ => None
+        }
+    }
+
+}
+
+impl From<io::Error> for VaultError {
+
+    fn from(e: io::Error) -> VaultError {
+        match e.kind() {
+            io::ErrorKind::NotFound => VaultError::NotFound,
+            io::ErrorKind::PermissionDenied => VaultError::PermissionDenied,
+            _ => VaultError::Io(e)
+        }
+    }
+
+}
+
+impl From<ErrorStack> for VaultError {
+
+    fn from(e: ErrorStack) -> VaultError {
+        VaultError::Crypto(e)
+    }
+
+}
+
+impl From<serde_cbor::error::Error> for VaultError {
+
+    /// Anything that cannot be deserialized is considered corrupted, failures
+    /// to serialize are reported with `VaultError::Serialization` instead.
+    fn from(e: serde_cbor::error::Error) -> VaultError {
+        VaultError::Corrupted(e.to_string())
+    }
+
+}
@@ -0,0 +1,632 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::collections::{HashMap, HashSet};
+
+use std::path::{Path, PathBuf};
+
+use std::fs::File;
+use std::io::Read;
+
+use chrono::prelude::*;
+use chrono::serde::ts_seconds;
+
+use secstr::SecStr;
+
+use serde::Serialize;
+use serde_bytes;
+use serde_cbor::de::from_slice;
+use serde_cbor::ser::to_vec;
+
+use openssl::symm::{decrypt, encrypt_aead, decrypt_aead, Cipher};
+use openssl::rand::rand_bytes;
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use uuid::Uuid;
+
+use model::{Vault, Entry, Conflict};
+
+use changes::ChangeLog;
+
+use settings::Settings;
+
+use io::atomic::write_atomically;
+use io::error::{Result, VaultError};
+use io::kdf::Kdf;
+use io::merge::{EntryState, Modified, merge_entry, merge_map};
+use io::seal::{VaultKey, Sealed};
+
+/// The version of the vault files written by this client.
+///
+///  - version 1: AES-256-CBC, without any authentication
+///  - version 2: AES-256-GCM, with the header authenticated as associated data
+///  - version 3: same as version 2, with the key derivation function stored in the header
+///
+/// Files of previous versions can still be read, and are written back with the latest one.
+const VERSION: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EncryptedVaultFile {
+    version: u8,
+    #[serde(with="serde_bytes")]
+    salt: Vec<u8>,
+    /// PBKDF2 iterations of versions 1 and 2, superseded by `kdf`
+    #[serde(default)]
+    iter: u32,
+    #[serde(default)]
+    kdf: Option<Kdf>,
+    #[serde(with="serde_bytes")]
+    iv: Vec<u8>,
+    #[serde(with="serde_bytes")]
+    encrypted: Vec<u8>,
+    #[serde(default, with="serde_bytes")]
+    tag: Vec<u8>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DecryptedVaultFile {
+    owner: String,
+    /// the vault key from which entry keys are derived, see `io::seal`
+    #[serde(default, with="serde_bytes")]
+    key: Vec<u8>,
+    /// entries by identifier, or by name in older files
+    entries: HashMap<String, EncryptedEntry>,
+    /// deleted entries by identifier
+    #[serde(default)]
+    trash: HashMap<String, TrashedEntry>,
+    /// versions not kept when merging concurrent changes, by entry identifier
+    #[serde(default)]
+    conflicts: HashMap<String, ConflictEntry>,
+    /// recent changes and tombstones of permanently deleted entries
+    #[serde(default)]
+    changes: ChangeLog
+}
+
+/// An entry of the vault.
+///
+/// The password is encrypted with the entry key, in `encrypted`, as well as
+/// the one-time password generator, in `otp`, and the user and comment, in
+/// `details`. The other fields are only protected by the vault encryption.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EncryptedEntry {
+    /// the display name, absent in older files where entries are keyed by name
+    #[serde(default)]
+    name: Option<String>,
+    tags: HashSet<String>,
+    #[serde(with="ts_seconds")]
+    created: DateTime<Utc>,
+    #[serde(with="ts_seconds")]
+    last_created: DateTime<Utc>,
+    /// only in files written before the details were sealed
+    #[serde(default)]
+    comment: Option<String>,
+    /// only in files written before the details were sealed
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    uri: Option<String>,
+    /// salt of the entry key derivation
+    #[serde(with="serde_bytes")]
+    salt: Vec<u8>,
+    /// always 0, entry keys are derived with HMAC from the vault key
+    iter: u32,
+    /// AES-256-GCM nonce, empty for unsealed entries of older files
+    #[serde(with="serde_bytes")]
+    iv: Vec<u8>,
+    /// encrypted password followed by the authentication tag
+    #[serde(with="serde_bytes")]
+    encrypted: Vec<u8>,
+    /// `otpauth://` URI of the one-time password generator, sealed like the password
+    #[serde(default)]
+    otp: Option<SealedSecret>,
+    /// `EntryDetails`, sealed so that they stay identical while unchanged
+    #[serde(default)]
+    details: Option<SealedSecret>
+}
+
+/// The fields of an entry that may be secret, sealed in `EncryptedEntry::details`.
+#[derive(Debug, Deserialize, Serialize)]
+struct EntryDetails {
+    comment: Option<String>,
+    user: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct SealedSecret {
+    #[serde(with="serde_bytes")]
+    salt: Vec<u8>,
+    #[serde(with="serde_bytes")]
+    iv: Vec<u8>,
+    #[serde(with="serde_bytes")]
+    encrypted: Vec<u8>
+}
+
+impl<'a> From<&'a Sealed> for SealedSecret {
+
+    fn from(sealed: &Sealed) -> SealedSecret {
+        SealedSecret {
+            salt: sealed.salt.clone(),
+            iv: sealed.iv.clone(),
+            encrypted: sealed.encrypted.clone()
+        }
+    }
+
+}
+
+impl<'a> From<&'a SealedSecret> for Sealed {
+
+    fn from(sealed: &SealedSecret) -> Sealed {
+        Sealed {
+            salt: sealed.salt.clone(),
+            iv: sealed.iv.clone(),
+            encrypted: sealed.encrypted.clone()
+        }
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TrashedEntry {
+    #[serde(with="ts_seconds")]
+    deleted: DateTime<Utc>,
+    entry: EncryptedEntry
+}
+
+/// The version of an entry that was not kept, both absent if it was deleted.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct ConflictEntry {
+    #[serde(default)]
+    entry: Option<EncryptedEntry>,
+    #[serde(default)]
+    trashed: Option<TrashedEntry>
+}
+
+impl ConflictEntry {
+
+    fn from_state(state: EntryState<EncryptedEntry>) -> ConflictEntry {
+        match state {
+            EntryState::Live(entry) => ConflictEntry { entry: Some(entry), trashed: None },
+            EntryState::Trashed(deleted, entry) => ConflictEntry { entry: None, trashed: Some(TrashedEntry { deleted, entry }) },
+            EntryState::Purged(_) | EntryState::Absent => ConflictEntry { entry: None, trashed: None }
+        }
+    }
+
+}
+
+/// Entries of older files, stored unsealed in `EncryptedEntry::encrypted`.
+#[derive(Debug, Deserialize, Serialize)]
+struct UnsealedEntry {
+    #[serde(with="serde_bytes")]
+    password: Vec<u8>,
+    comment: Option<String>,
+    user: Option<String>,
+    uri: Option<String>
+}
+
+/// Serializes a part of the vault, failures are not the file's fault.
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    to_vec(value).map_err(VaultError::Serialization)
+}
+
+/// Returns the path of the vault file, in the configured directory, creating it if needed.
+pub fn vault_path() -> Result<PathBuf> {
+    Ok(Settings::load().vault_path()?)
+}
+
+/// Encrypts the vault with the given passphrase and writes it to the vault file.
+pub fn save_vault(vault: &Vault, password: SecStr, kdf: &Kdf) -> Result<()> {
+    let path = vault_path()?;
+    DecryptedVaultFile::from_vault(vault)?
+        .encrypt(password, kdf)?
+        .to_file(path)
+}
+
+impl EncryptedVaultFile {
+
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<EncryptedVaultFile> {
+        let mut raw = vec![];
+        File::open(file_path)?.read_to_end(&mut raw)?;
+        EncryptedVaultFile::from_bytes(&raw)
+    }
+
+    /// Reads a vault file from its content, as stored on disk.
+    pub fn from_bytes(raw: &[u8]) -> Result<EncryptedVaultFile> {
+        Ok(from_slice(raw)?)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
+        Ok(write_atomically(file_path, &self.to_bytes()?)?)
+    }
+
+    /// The content of the file, as stored on disk.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        encode(self)
+    }
+
+    pub fn decrypt(&self, password: SecStr) -> Result<DecryptedVaultFile> {
+        if self.version < 1 || self.version > VERSION {
+            return Err(VaultError::UnsupportedVersion(self.version));
+        }
+        // derive the 256 bits key
+        let derived_key = self.kdf().derive_key(&password, &self.salt)?;
+        if self.version == 1 {
+            // without authentication, a wrong key shows as a padding or parsing error
+            let raw_decrypted =
+                decrypt(Cipher::aes_256_cbc(), derived_key.unsecure(), Some(&self.iv), &self.encrypted)
+                    .map_err(|_| VaultError::WrongPassphrase)?;
+            from_slice(&raw_decrypted).map_err(|_| VaultError::WrongPassphrase)
+        } else {
+            // the tag cannot tell a wrong key from a modified file
+            let aad = self.associated_data()?;
+            let raw_decrypted =
+                decrypt_aead(Cipher::aes_256_gcm(), derived_key.unsecure(), Some(&self.iv), &aad, &self.encrypted, &self.tag)
+                    .map_err(|_| VaultError::WrongPassphrase)?;
+            Ok(from_slice(&raw_decrypted)?)
+        }
+    }
+
+    /// The key derivation function used for this file.
+    pub fn kdf(&self) -> Kdf {
+        self.kdf.unwrap_or_else(|| Kdf::pbkdf2(self.iter))
+    }
+
+    /// The header fields authenticated along with the encrypted content.
+    fn associated_data(&self) -> ::std::io::Result<Vec<u8>> {
+        let mut aad = vec![];
+        aad.write_u8(self.version)?;
+        if self.version < 3 {
+            aad.write_u32::<BigEndian>(self.iter)?;
+        } else {
+            self.kdf().write_to(&mut aad)?;
+        }
+        aad.write_u32::<BigEndian>(self.salt.len() as u32)?;
+        aad.extend_from_slice(&self.salt);
+        aad.write_u32::<BigEndian>(self.iv.len() as u32)?;
+        aad.extend_from_slice(&self.iv);
+        Ok(aad)
+    }
+
+}
+
+impl Modified for EncryptedEntry {
+
+    fn last_modified(&self) -> DateTime<Utc> {
+        self.last_created
+    }
+
+}
+
+impl EncryptedEntry {
+
+    fn from_entry(entry: &Entry, key: &VaultKey) -> Result<Self> {
+        let sealed = entry.sealed_password();
+        let details =
+            if entry.comment.is_none() && entry.user.is_none() {
+                None
+            } else {
+                let details = EntryDetails { comment: entry.comment.clone(), user: entry.user.clone() };
+                Some(SealedSecret::from(&key.seal_deterministic(&SecStr::new(encode(&details)?))?))
+            };
+        Ok(EncryptedEntry {
+            name: Some(entry.name.clone()),
+            tags: entry.tags.clone(),
+            created: entry.created(),
+            last_created: entry.last_modified(),
+            comment: None,
+            user: None,
+            uri: entry.uri.clone(),
+            salt: sealed.salt.clone(),
+            iter: 0,
+            iv: sealed.iv.clone(),
+            encrypted: sealed.encrypted.clone(),
+            otp: entry.sealed_otp().map(SealedSecret::from),
+            details: details
+        })
+    }
+
+    fn to_entry(&self, id_or_name: &String, key: &VaultKey) -> Result<Entry> {
+        let (id, name) =
+            match self.name {
+                Some(ref name) => {
+                    let id = Uuid::parse_str(id_or_name)
+                        .map_err(|e| VaultError::Corrupted(format!("invalid entry identifier {}: {}", id_or_name, e)))?;
+                    (id, name.clone())
+                },
+                None => {
+                    (Uuid::new_v4(), id_or_name.clone())
+                }
+            };
+        let mut comment = self.comment.clone();
+        let mut user = self.user.clone();
+        let mut uri = self.uri.clone();
+        let sealed =
+            if self.iv.is_empty() {
+                // seal the entries of older files
+                let unsealed: UnsealedEntry = from_slice(&self.encrypted)?;
+                comment = unsealed.comment;
+                user = unsealed.user;
+                uri = unsealed.uri;
+                key.seal(&SecStr::new(unsealed.password))?
+            } else {
+                Sealed {
+                    salt: self.salt.clone(),
+                    iv: self.iv.clone(),
+                    encrypted: self.encrypted.clone()
+                }
+            };
+        if let Some(ref details) = self.details {
+            let details: EntryDetails = from_slice(key.open(&Sealed::from(details))?.unsecure())?;
+            comment = details.comment;
+            user = details.user;
+        }
+        let mut entry = Entry::restore(id, name, sealed, self.created, self.last_created);
+        entry.comment = comment;
+        entry.user = user;
+        entry.uri = uri;
+        entry.tags = self.tags.clone();
+        entry.set_sealed_otp(self.otp.as_ref().map(Sealed::from));
+        Ok(entry)
+    }
+
+    /// The same entry with its secrets sealed with another vault key.
+    fn resealed(&self, from: &VaultKey, to: &VaultKey) -> Result<EncryptedEntry> {
+        let mut entry = self.clone();
+        // unsealed entries of older files do not depend on the key
+        if !self.iv.is_empty() {
+            let password = from.open(&Sealed {
+                salt: self.salt.clone(),
+                iv: self.iv.clone(),
+                encrypted: self.encrypted.clone()
+            })?;
+            let sealed = to.seal(&password)?;
+            entry.salt = sealed.salt;
+            entry.iv = sealed.iv;
+            entry.encrypted = sealed.encrypted;
+        }
+        if let Some(ref otp) = self.otp {
+            let otp = from.open(&Sealed::from(otp))?;
+            entry.otp = Some(SealedSecret::from(&to.seal(&otp)?));
+        }
+        if let Some(ref details) = self.details {
+            let details = from.open(&Sealed::from(details))?;
+            entry.details = Some(SealedSecret::from(&to.seal_deterministic(&details)?));
+        }
+        Ok(entry)
+    }
+
+}
+
+impl DecryptedVaultFile {
+
+    pub fn new(owner: String) -> Self {
+        DecryptedVaultFile {
+            owner: owner,
+            key: vec![],
+            entries: HashMap::new(),
+            trash: HashMap::new(),
+            conflicts: HashMap::new(),
+            changes: ChangeLog::default()
+        }
+    }
+
+    pub fn from_vault(vault: &Vault) -> Result<Self> {
+        let key = vault.key();
+        let mut entries = HashMap::new();
+        for entry in vault {
+            entries.insert(entry.id().to_string(), EncryptedEntry::from_entry(entry, key)?);
+        }
+        let mut trash = HashMap::new();
+        for trashed in vault.trash() {
+            let trashed_entry = TrashedEntry {
+                deleted: trashed.deleted,
+                entry: EncryptedEntry::from_entry(&trashed.entry, key)?
+            };
+            trash.insert(trashed.entry.id().to_string(), trashed_entry);
+        }
+        let mut conflicts = HashMap::new();
+        for conflict in vault.conflicts() {
+            let other = match conflict.other {
+                Some(ref other) => Some(EncryptedEntry::from_entry(other, key)?),
+                None => None
+            };
+            let conflict_entry = match conflict.deleted {
+                Some(deleted) => ConflictEntry { entry: None, trashed: other.map(|entry| TrashedEntry { deleted, entry }) },
+                None => ConflictEntry { entry: other, trashed: None }
+            };
+            conflicts.insert(conflict.id.to_string(), conflict_entry);
+        }
+        Ok(DecryptedVaultFile {
+            owner: vault.owner().clone(),
+            key: vault.key().unsecure().to_vec(),
+            entries: entries,
+            trash: trash,
+            conflicts: conflicts,
+            changes: vault.changes().clone()
+        })
+    }
+
+    pub fn to_vault(&self) -> Result<Vault> {
+        let key =
+            if self.key.is_empty() {
+                VaultKey::generate()?
+            } else {
+                VaultKey::from_bytes(self.key.clone())
+            };
+        let mut vault = Vault::new(self.owner.clone(), key.clone());
+        vault.set_changes(self.changes.clone());
+        for (id_or_name, encrypted_entry) in &self.entries {
+            vault.insert_entry(encrypted_entry.to_entry(id_or_name, &key)?);
+        }
+        for (id, trashed_entry) in &self.trash {
+            vault.insert_trashed_entry(trashed_entry.entry.to_entry(id, &key)?, trashed_entry.deleted);
+        }
+        for (id, conflict_entry) in &self.conflicts {
+            let (other, deleted) = match (&conflict_entry.entry, &conflict_entry.trashed) {
+                (&Some(ref entry), _) => (Some(entry.to_entry(id, &key)?), None),
+                (&None, &Some(ref trashed)) => (Some(trashed.entry.to_entry(id, &key)?), Some(trashed.deleted)),
+                (&None, &None) => (None, None)
+            };
+            let id = Uuid::parse_str(id)
+                .map_err(|e| VaultError::Corrupted(format!("invalid entry identifier {}: {}", id, e)))?;
+            vault.insert_conflict(Conflict { id, other, deleted });
+        }
+        Ok(vault)
+    }
+
+    /// Merges the changes made to `local` and `remote` since `base`, their last common version.
+    ///
+    /// Entries changed on both sides keep their most recent version, the other
+    /// one is recorded as a conflict. When the change logs tell that only one
+    /// side changed an entry, that side is kept even without a base. Returns
+    /// the merged file, with the owner and key of `local`, and the number of
+    /// new conflicts.
+    pub fn merge(base: Option<&DecryptedVaultFile>, local: &DecryptedVaultFile, remote: &DecryptedVaultFile) -> Result<(DecryptedVaultFile, usize)> {
+        let empty = DecryptedVaultFile::new(local.owner.clone());
+        let resealed;
+        let remote =
+            if remote.key == local.key {
+                remote
+            } else {
+                // another vault altogether, only its entries are kept
+                resealed = remote.resealed(&local.key)?;
+                &resealed
+            };
+        let base = match base {
+            Some(base) if base.key == local.key => base,
+            _ => &empty
+        };
+
+        let mut merged = DecryptedVaultFile {
+            owner: local.owner.clone(),
+            key: local.key.clone(),
+            entries: HashMap::new(),
+            trash: HashMap::new(),
+            conflicts: merge_map(&base.conflicts, &local.conflicts, &remote.conflicts),
+            changes: ChangeLog::merge(&local.changes, &remote.changes)
+        };
+        let mut conflicts = 0;
+        let ids: HashSet<&String> = base.ids().chain(local.ids()).chain(remote.ids()).collect();
+        for id in ids {
+            let (local_state, remote_state) = (local.state(id), remote.state(id));
+            let changed = Uuid::parse_str(id).ok().map(|uuid| {
+                (local.changes.changed_since(&remote.changes, &uuid), remote.changes.changed_since(&local.changes, &uuid))
+            });
+            let merged_state = match changed {
+                // the log tells which side changed the entry since the other one saw it
+                Some((true, false)) => Ok(local_state),
+                Some((false, true)) => Ok(remote_state),
+                _ => merge_entry(&base.state(id), &local_state, &remote_state)
+            };
+            let state = match merged_state {
+                Ok(state) => state,
+                Err(conflicting) => {
+                    conflicts += 1;
+                    merged.conflicts.insert(id.clone(), ConflictEntry::from_state(conflicting.other));
+                    conflicting.merged
+                }
+            };
+            match state {
+                EntryState::Live(entry) => {
+                    merged.forget_tombstone(id);
+                    merged.entries.insert(id.clone(), entry);
+                },
+                EntryState::Trashed(deleted, entry) => {
+                    merged.forget_tombstone(id);
+                    merged.trash.insert(id.clone(), TrashedEntry { deleted, entry });
+                },
+                EntryState::Purged(_) | EntryState::Absent => ()
+            }
+        }
+        Ok((merged, conflicts))
+    }
+
+    fn ids(&self) -> ::std::iter::Chain<::std::collections::hash_map::Keys<String, EncryptedEntry>, ::std::collections::hash_map::Keys<String, TrashedEntry>> {
+        self.entries.keys().chain(self.trash.keys())
+    }
+
+    fn state(&self, id: &String) -> EntryState<EncryptedEntry> {
+        match (self.entries.get(id), self.trash.get(id)) {
+            (Some(entry), _) => EntryState::Live(entry.clone()),
+            (None, Some(trashed)) => EntryState::Trashed(trashed.deleted, trashed.entry.clone()),
+            (None, None) => {
+                // entries of older files are keyed by name, and never have a tombstone
+                let tombstone = Uuid::parse_str(id).ok().and_then(|id| self.changes.tombstone(&id).cloned());
+                match tombstone {
+                    Some(tombstone) => EntryState::Purged(tombstone.deleted),
+                    None => EntryState::Absent
+                }
+            }
+        }
+    }
+
+    fn forget_tombstone(&mut self, id: &String) {
+        if let Ok(id) = Uuid::parse_str(id) {
+            self.changes.forget_tombstone(&id);
+        }
+    }
+
+    /// Records that `device` knows of every change of this file, before writing it.
+    pub fn acknowledge(&mut self, device: Uuid) {
+        self.changes.set_device(device);
+        self.changes.acknowledge();
+    }
+
+    /// The same content with the secrets sealed with another vault key.
+    fn resealed(&self, key: &[u8]) -> Result<DecryptedVaultFile> {
+        let from = VaultKey::from_bytes(self.key.clone());
+        let to = VaultKey::from_bytes(key.to_vec());
+        let mut entries = HashMap::new();
+        for (id, entry) in &self.entries {
+            entries.insert(id.clone(), entry.resealed(&from, &to)?);
+        }
+        let mut trash = HashMap::new();
+        for (id, trashed) in &self.trash {
+            trash.insert(id.clone(), TrashedEntry { deleted: trashed.deleted, entry: trashed.entry.resealed(&from, &to)? });
+        }
+        Ok(DecryptedVaultFile {
+            owner: self.owner.clone(),
+            key: key.to_vec(),
+            entries: entries,
+            trash: trash,
+            // they do not concern this vault
+            conflicts: HashMap::new(),
+            changes: self.changes.clone()
+        })
+    }
+
+    pub fn encrypt(&self, password: SecStr, kdf: &Kdf) -> Result<EncryptedVaultFile> {
+        // serializes data
+        let raw_decrypted = encode(self)?;
+        // generate 128 bits salt and 96 bits nonce
+        let mut salt = [0; 16];
+        let mut iv = [0; 12];
+        rand_bytes(&mut salt)?;
+        rand_bytes(&mut iv)?;
+        // derive the 256 bits key
+        let derived_key = kdf.derive_key(&password, &salt)?;
+        let mut file = EncryptedVaultFile {
+            version: VERSION,
+            salt: salt.to_vec(),
+            iter: 0,
+            kdf: Some(*kdf),
+            iv: iv.to_vec(),
+            encrypted: vec![],
+            tag: vec![0; 16]
+        };
+        // encrypt with the derived key and nonce, authenticating the header
+        let aad = file.associated_data()?;
+        file.encrypted = encrypt_aead(Cipher::aes_256_gcm(), derived_key.unsecure(), Some(&file.iv), &aad, &raw_decrypted, &mut file.tag)?;
+        Ok(file)
+    }
+
+}
@@ -0,0 +1,200 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::time::{Duration, Instant};
+
+use secstr::{SecStr, SecVec};
+
+
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::hash::MessageDigest;
+
+use argon2::{self, Config, ThreadMode, Variant, Version};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use io::error::{Result, VaultError};
+
+/// The size in bytes of the derived keys.
+const KEY_LENGTH: usize = 32;
+
+/// The minimal memory in KiB recommended for Argon2.
+pub const MIN_MEM_COST: u32 = 64 * 1024;
+
+/// The minimal number of passes recommended for Argon2.
+pub const MIN_TIME_COST: u32 = 3;
+
+/// The maximal memory in KiB, for the calibration and the files read.
+const MAX_MEM_COST: u32 = 1024 * 1024;
+
+/// The maximal number of Argon2 passes, for the calibration and the files read.
+const MAX_TIME_COST: u32 = 64;
+
+/// The maximal degree of parallelism of the files read.
+const MAX_LANES: u32 = 16;
+
+/// The maximal number of PBKDF2 iterations of the files read.
+const MAX_ITERATIONS: u32 = 10_000_000;
+
+const LANES: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum KdfAlgorithm {
+    Pbkdf2Sha256,
+    Argon2id
+}
+
+/// The key derivation function and its costs, as stored in the vault header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Kdf {
+    pub algorithm: KdfAlgorithm,
+    /// The number of iterations for PBKDF2, of passes for Argon2.
+    pub time_cost: u32,
+    /// The memory in KiB used by Argon2.
+    pub mem_cost: u32,
+    /// The degree of parallelism of Argon2.
+    pub lanes: u32
+}
+
+impl Kdf {
+
+    pub fn pbkdf2(iter: u32) -> Kdf {
+        Kdf {
+            algorithm: KdfAlgorithm::Pbkdf2Sha256,
+            time_cost: iter,
+            mem_cost: 0,
+            lanes: 0
+        }
+    }
+
+    pub fn argon2id(mem_cost: u32, time_cost: u32, lanes: u32) -> Kdf {
+        Kdf {
+            algorithm: KdfAlgorithm::Argon2id,
+            time_cost: time_cost,
+            mem_cost: mem_cost,
+            lanes: lanes
+        }
+    }
+
+    /// Picks Argon2id parameters so that deriving a key takes about `target` on this machine.
+    ///
+    /// One pass with the minimal memory is measured, then the memory is scaled
+    /// up to reach the target with the minimal number of passes. Once the
+    /// memory reaches its maximum, the number of passes is increased instead.
+    /// The result is never below the recommended floor.
+    pub fn calibrate(target: Duration) -> Result<Kdf> {
+        let password = SecStr::new(b"repassync calibration".to_vec());
+        let salt = [0_u8; 16];
+
+        let start = Instant::now();
+        Kdf::argon2id(MIN_MEM_COST, 1, LANES).derive_key(&password, &salt)?;
+        let pass_millis = millis(start.elapsed()).max(1) as f64;
+        let target_millis = millis(target) as f64;
+
+        let scale = target_millis / (pass_millis * f64::from(MIN_TIME_COST));
+        let mem_cost = (f64::from(MIN_MEM_COST) * scale) as u32;
+        // round to the MiB
+        let mem_cost = (mem_cost / 1024 * 1024).max(MIN_MEM_COST).min(MAX_MEM_COST);
+
+        let mem_pass_millis = pass_millis * f64::from(mem_cost) / f64::from(MIN_MEM_COST);
+        let time_cost = ((target_millis / mem_pass_millis) as u32).max(MIN_TIME_COST).min(MAX_TIME_COST);
+
+        let kdf = Kdf::argon2id(mem_cost, time_cost, LANES);
+        info!("Calibrated key derivation for {} ms: {:?}", target_millis, kdf);
+        Ok(kdf)
+    }
+
+    /// Whether these parameters are weaker than the recommended ones.
+    pub fn is_below_floor(&self) -> bool {
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => true,
+            KdfAlgorithm::Argon2id => self.mem_cost < MIN_MEM_COST || self.time_cost < MIN_TIME_COST
+        }
+    }
+
+    /// The parameters to save with: files still using PBKDF2 are migrated to the default ones.
+    pub fn upgraded(self) -> Kdf {
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => Kdf::default(),
+            KdfAlgorithm::Argon2id => self
+        }
+    }
+
+    /// Rejects the costs that no calibration produces, as the parameters come
+    /// from the unauthenticated file header and are used before the tag is checked.
+    fn check_bounds(&self) -> Result<()> {
+        let within = match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => self.time_cost <= MAX_ITERATIONS,
+            KdfAlgorithm::Argon2id =>
+                self.mem_cost <= MAX_MEM_COST && self.time_cost <= MAX_TIME_COST && self.lanes <= MAX_LANES
+        };
+        if within {
+            Ok(())
+        } else {
+            Err(VaultError::Corrupted(format!("key derivation costs out of bounds: {:?}", self)))
+        }
+    }
+
+    /// Derives a 256 bits key from the password and salt.
+    pub fn derive_key(&self, password: &SecStr, salt: &[u8]) -> Result<SecVec<u8>> {
+        self.check_bounds()?;
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                let mut derived_key = SecVec::new(vec![0_u8; KEY_LENGTH]);
+                pbkdf2_hmac(password.unsecure(), salt, self.time_cost as usize, MessageDigest::sha256(), derived_key.unsecure_mut())?;
+                Ok(derived_key)
+            },
+            KdfAlgorithm::Argon2id => {
+                let config = Config {
+                    variant: Variant::Argon2id,
+                    version: Version::Version13,
+                    mem_cost: self.mem_cost,
+                    time_cost: self.time_cost,
+                    lanes: self.lanes,
+                    thread_mode: ThreadMode::from_threads(self.lanes),
+                    hash_length: KEY_LENGTH as u32,
+                    .. Config::default()
+                };
+                argon2::hash_raw(password.unsecure(), salt, &config)
+                    .map(SecVec::new)
+                    // the parameters come from the file header
+                    .map_err(|e| VaultError::Corrupted(format!("invalid key derivation parameters: {}", e)))
+            }
+        }
+    }
+
+    /// Appends the binary representation of the parameters, used to authenticate them.
+    pub fn write_to(&self, out: &mut Vec<u8>) -> ::std::io::Result<()> {
+        let algorithm = match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => 0,
+            KdfAlgorithm::Argon2id => 1
+        };
+        out.write_u8(algorithm)?;
+        out.write_u32::<BigEndian>(self.time_cost)?;
+        out.write_u32::<BigEndian>(self.mem_cost)?;
+        out.write_u32::<BigEndian>(self.lanes)
+    }
+
+}
+
+impl Default for Kdf {
+
+    /// Argon2id with the recommended minimal costs.
+    fn default() -> Kdf {
+        Kdf::argon2id(MIN_MEM_COST, MIN_TIME_COST, LANES)
+    }
+
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos()) / 1_000_000
+}
@@ -0,0 +1,127 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Three-way merge of two copies of the vault changed independently.
+//!
+//! Each entry is compared with its state in the last common version, the
+//! base. A change made on one side only is taken as is. When both sides
+//! changed the same entry differently, the most recent change is kept and
+//! the other one is recorded as a conflict for the user to settle, so that
+//! nothing is silently lost.
+//!
+//! Without a base, a permanently deleted entry is told apart from one never
+//! seen by its tombstone: a copy that was not changed since does not bring
+//! it back.
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use chrono::prelude::*;
+
+/// The state of an entry in one copy of the vault.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryState<E> {
+    Live(E),
+    Trashed(DateTime<Utc>, E),
+    /// Permanently deleted, at the date of its tombstone.
+    Purged(DateTime<Utc>),
+    /// Never added, or permanently deleted so long ago that every copy knows it.
+    Absent
+}
+
+/// An entry of which both sides kept a different version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflicting<E> {
+    pub merged: EntryState<E>,
+    /// The version that was not kept.
+    pub other: EntryState<E>
+}
+
+/// Something that records when it was last changed.
+pub trait Modified {
+    fn last_modified(&self) -> DateTime<Utc>;
+}
+
+impl<E: Modified> EntryState<E> {
+
+    /// When this state was reached, for absent entries it is unknown.
+    fn changed(&self) -> Option<DateTime<Utc>> {
+        match *self {
+            EntryState::Live(ref entry) => Some(entry.last_modified()),
+            EntryState::Trashed(deleted, ref entry) => Some(max(deleted, entry.last_modified())),
+            EntryState::Purged(deleted) => Some(deleted),
+            EntryState::Absent => None
+        }
+    }
+
+}
+
+/// Merges the states of one entry, returns the merged state or the conflict.
+pub fn merge_entry<E: Clone + PartialEq + Modified>(base: &EntryState<E>, local: &EntryState<E>, remote: &EntryState<E>) -> Result<EntryState<E>, Conflicting<E>> {
+    use self::EntryState::*;
+    if local == remote || remote == base {
+        return Ok(local.clone());
+    }
+    if local == base {
+        return Ok(remote.clone());
+    }
+    match (local, remote) {
+        // deleted on both sides, whatever the content
+        (&Trashed(l, _), &Trashed(r, _)) | (&Purged(l), &Purged(r)) => {
+            Ok(if l >= r { local.clone() } else { remote.clone() })
+        },
+        (&Purged(_), &Trashed(_, _)) | (&Purged(_), &Absent) | (&Trashed(_, _), &Absent) => Ok(local.clone()),
+        (&Trashed(_, _), &Purged(_)) | (&Absent, &Purged(_)) | (&Absent, &Trashed(_, _)) => Ok(remote.clone()),
+        // a copy that did not see the deletion, and was not changed since
+        (&Purged(deleted), &Live(ref entry)) if *base == Absent && entry.last_modified() <= deleted => {
+            Ok(local.clone())
+        },
+        (&Live(ref entry), &Purged(deleted)) if *base == Absent && entry.last_modified() <= deleted => {
+            Ok(remote.clone())
+        },
+        // a modification wins over a deletion, the deletion is to confirm
+        (&Live(_), _) if !is_live(remote) => {
+            Err(Conflicting { merged: local.clone(), other: remote.clone() })
+        },
+        (_, &Live(_)) if !is_live(local) => {
+            Err(Conflicting { merged: remote.clone(), other: local.clone() })
+        },
+        _ => {
+            if local.changed() >= remote.changed() {
+                Err(Conflicting { merged: local.clone(), other: remote.clone() })
+            } else {
+                Err(Conflicting { merged: remote.clone(), other: local.clone() })
+            }
+        }
+    }
+}
+
+fn is_live<E>(state: &EntryState<E>) -> bool {
+    match *state {
+        EntryState::Live(_) => true,
+        _ => false
+    }
+}
+
+/// Merges maps of values that have no notion of conflict, local changes win.
+pub fn merge_map<K: Clone + Eq + Hash, V: Clone + PartialEq>(base: &HashMap<K, V>, local: &HashMap<K, V>, remote: &HashMap<K, V>) -> HashMap<K, V> {
+    let keys: HashSet<&K> = base.keys().chain(local.keys()).chain(remote.keys()).collect();
+    let mut merged = HashMap::new();
+    for key in keys {
+        let (b, l, r) = (base.get(key), local.get(key), remote.get(key));
+        let value = if l == b { r } else { l };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
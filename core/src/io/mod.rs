@@ -10,4 +10,10 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
+pub mod error;
 pub mod file;
+pub mod kdf;
+pub mod merge;
+pub mod seal;
+pub mod atomic;
+pub mod device;
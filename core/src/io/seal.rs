@@ -0,0 +1,128 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Per entry encryption.
+//!
+//! Each vault has its own random 256 bits key, stored in the vault file and
+//! thus protected by the passphrase. Entry secrets are sealed with a key
+//! derived from it with HMAC-SHA256 and a random salt, and encrypted with
+//! AES-256-GCM. Deriving an entry key is cheap, so that unlocking the vault
+//! runs the passphrase key derivation only once, and secrets are only
+//! decrypted when actually needed.
+//!
+//! As the vault key does not depend on the passphrase, changing the passphrase
+//! does not require to encrypt the entries again.
+//!
+//! Data sealed again at each save, like the entry details, is sealed with a
+//! salt and nonce derived from its content, so that unchanged entries stay
+//! identical and merging can compare them. Each content then gets its own
+//! entry key, and a nonce is never reused with a key for different data; only
+//! the equality of two sealed contents is revealed.
+use secstr::{SecStr, SecVec};
+
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use openssl::symm::{encrypt_aead, decrypt_aead, Cipher};
+
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const IV_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+const CONTEXT: &'static [u8] = b"repassync entry";
+
+const SYNTHETIC_CONTEXT: &'static [u8] = b"repassync synthetic";
+
+/// The key from which entry keys are derived.
+#[derive(Debug, Clone)]
+pub struct VaultKey(SecVec<u8>);
+
+/// Some data sealed with a key derived from a vault key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sealed {
+    /// The salt from which the entry key is derived.
+    pub salt: Vec<u8>,
+    /// The AES-256-GCM nonce.
+    pub iv: Vec<u8>,
+    /// The encrypted data, followed by the authentication tag.
+    pub encrypted: Vec<u8>
+}
+
+impl VaultKey {
+
+    pub fn generate() -> Result<VaultKey, ErrorStack> {
+        let mut key = SecVec::new(vec![0_u8; KEY_LENGTH]);
+        rand_bytes(key.unsecure_mut())?;
+        Ok(VaultKey(key))
+    }
+
+    pub fn from_bytes(key: Vec<u8>) -> VaultKey {
+        VaultKey(SecVec::new(key))
+    }
+
+    pub fn unsecure(&self) -> &[u8] {
+        self.0.unsecure()
+    }
+
+    pub fn seal(&self, data: &SecStr) -> Result<Sealed, ErrorStack> {
+        let mut salt = [0; SALT_LENGTH];
+        let mut iv = [0; IV_LENGTH];
+        rand_bytes(&mut salt)?;
+        rand_bytes(&mut iv)?;
+        self.seal_with(&salt, &iv, data)
+    }
+
+    /// Seals the data with a salt and nonce derived from it, the same data
+    /// always gives the same result with this key.
+    pub fn seal_deterministic(&self, data: &SecStr) -> Result<Sealed, ErrorStack> {
+        let pkey = PKey::hmac(self.0.unsecure())?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+        signer.update(SYNTHETIC_CONTEXT)?;
+        signer.update(data.unsecure())?;
+        let synthetic = signer.sign_to_vec()?;
+        let (salt, rest) = synthetic.split_at(SALT_LENGTH);
+        self.seal_with(salt, &rest[..IV_LENGTH], data)
+    }
+
+    fn seal_with(&self, salt: &[u8], iv: &[u8], data: &SecStr) -> Result<Sealed, ErrorStack> {
+        let key = self.entry_key(salt)?;
+        let mut tag = [0; TAG_LENGTH];
+        let mut encrypted = encrypt_aead(Cipher::aes_256_gcm(), key.unsecure(), Some(iv), CONTEXT, data.unsecure(), &mut tag)?;
+        encrypted.extend_from_slice(&tag);
+        Ok(Sealed {
+            salt: salt.to_vec(),
+            iv: iv.to_vec(),
+            encrypted: encrypted
+        })
+    }
+
+    pub fn open(&self, sealed: &Sealed) -> Result<SecStr, ErrorStack> {
+        let key = self.entry_key(&sealed.salt)?;
+        // a truncated content fails the tag check
+        let split = sealed.encrypted.len().saturating_sub(TAG_LENGTH);
+        let (encrypted, tag) = sealed.encrypted.split_at(split);
+        decrypt_aead(Cipher::aes_256_gcm(), key.unsecure(), Some(&sealed.iv), CONTEXT, encrypted, tag)
+            .map(SecStr::new)
+    }
+
+    fn entry_key(&self, salt: &[u8]) -> Result<SecVec<u8>, ErrorStack> {
+        let pkey = PKey::hmac(self.0.unsecure())?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+        signer.update(CONTEXT)?;
+        signer.update(salt)?;
+        signer.sign_to_vec().map(SecVec::new)
+    }
+
+}
@@ -0,0 +1,43 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! The core of repassync: the vault model, file format and cryptography,
+//! password generation and strength checks, and the synchronization with a server.
+//!
+//! It does not depend on GTK, and is shared by the graphical client and `repassync-cli`.
+extern crate chrono;
+extern crate rand;
+extern crate char_iter;
+extern crate xdg;
+extern crate secstr;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_bytes;
+extern crate serde_cbor;
+extern crate openssl;
+extern crate byteorder;
+extern crate argon2;
+extern crate toml;
+extern crate uuid;
+extern crate pwquality;
+extern crate reqwest;
+extern crate url;
+#[macro_use]
+extern crate log;
+
+pub mod model;
+pub mod changes;
+pub mod util;
+pub mod io;
+pub mod settings;
+pub mod sync;
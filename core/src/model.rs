@@ -0,0 +1,465 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use chrono::prelude::*;
+use chrono::Duration;
+use std::collections::{HashSet, HashMap};
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use openssl::error::ErrorStack;
+
+use io::seal::{VaultKey, Sealed};
+
+use changes::{ChangeLog, OperationKind};
+
+#[derive(Debug, Clone)]
+pub struct Vault {
+    owner: String,
+    key: VaultKey,
+    entries: HashMap<Uuid, Entry>,
+    trash: HashMap<Uuid, TrashedEntry>,
+    conflicts: HashMap<Uuid, Conflict>,
+    changes: ChangeLog
+}
+
+impl Vault {
+
+    pub fn new(owner: String, key: VaultKey) -> Vault {
+        Vault {
+            owner: owner,
+            key: key,
+            entries: HashMap::new(),
+            trash: HashMap::new(),
+            conflicts: HashMap::new(),
+            changes: ChangeLog::default()
+        }
+    }
+
+    /// The device changing the vault, on behalf of which changes are recorded.
+    pub fn set_device(&mut self, device: Uuid) {
+        self.changes.set_device(device);
+    }
+
+    pub fn changes(&self) -> &ChangeLog {
+        &self.changes
+    }
+
+    /// Replaces the log of changes, when loading the vault.
+    pub fn set_changes(&mut self, changes: ChangeLog) {
+        self.changes = changes;
+    }
+
+    pub fn add_entry(&mut self, name: String, password: SecStr) -> Result<Entry, ErrorStack> {
+        let entry = Entry::new(name, self.key.seal(&password)?);
+        self.entries.insert(entry.id, entry.clone());
+        self.changes.record(&entry.id, OperationKind::Added);
+        Ok(entry)
+    }
+
+    /// Decrypts the password of the given entry.
+    pub fn reveal_password(&self, entry: &Entry) -> Result<SecStr, ErrorStack> {
+        self.key.open(&entry.password)
+    }
+
+    pub fn insert_entry(&mut self, entry: Entry) {
+        self.entries.insert(entry.id, entry);
+    }
+
+    pub fn get_entry(&self, id: &Uuid) -> Option<&Entry> {
+        self.entries.get(id)
+    }
+
+    /// Modifies the entry and updates its modification date, returns `false` if it does not exist.
+    pub fn update_entry<F: FnOnce(&mut Entry)>(&mut self, id: &Uuid, update: F) -> bool {
+        match self.entries.get_mut(id) {
+            Some(entry) => {
+                update(entry);
+                entry.last_modified = Utc::now();
+                self.changes.record(id, OperationKind::Modified);
+                true
+            },
+            None => {
+                false
+            }
+        }
+    }
+
+    /// Seals the new password of the entry, returns `false` if it does not exist.
+    pub fn set_password(&mut self, id: &Uuid, password: SecStr) -> Result<bool, ErrorStack> {
+        let sealed = self.key.seal(&password)?;
+        Ok(self.update_entry(id, move |entry| entry.password = sealed))
+    }
+
+    /// Seals the new password of the entry if it differs from the current one,
+    /// returns whether it was replaced.
+    ///
+    /// Nothing is replaced when the current password cannot be decrypted, as
+    /// the new one was then not typed over it.
+    pub fn change_password(&mut self, id: &Uuid, password: SecStr) -> Result<bool, ErrorStack> {
+        let current = match self.entries.get(id) {
+            Some(entry) => self.reveal_password(entry)?,
+            None => return Ok(false)
+        };
+        if current == password {
+            Ok(false)
+        } else {
+            self.set_password(id, password)
+        }
+    }
+
+    /// Decrypts the `otpauth://` URI of the given entry, if it has one.
+    pub fn reveal_otp(&self, entry: &Entry) -> Result<Option<SecStr>, ErrorStack> {
+        match entry.otp {
+            Some(ref otp) => self.key.open(otp).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Seals the new `otpauth://` URI of the entry, returns `false` if it does not exist.
+    pub fn set_otp(&mut self, id: &Uuid, otp: Option<SecStr>) -> Result<bool, ErrorStack> {
+        let sealed = match otp {
+            Some(otp) => Some(self.key.seal(&otp)?),
+            None => None
+        };
+        Ok(self.update_entry(id, move |entry| entry.otp = sealed))
+    }
+
+    /// Moves the entry to the trash, returns `false` if it does not exist.
+    pub fn trash_entry(&mut self, id: &Uuid) -> bool {
+        match self.entries.remove(id) {
+            Some(entry) => {
+                self.insert_trashed_entry(entry, Utc::now());
+                self.changes.record(id, OperationKind::Trashed);
+                true
+            },
+            None => {
+                false
+            }
+        }
+    }
+
+    pub fn insert_trashed_entry(&mut self, entry: Entry, deleted: DateTime<Utc>) {
+        self.trash.insert(entry.id, TrashedEntry { entry: entry, deleted: deleted });
+    }
+
+    /// Moves the entry back from the trash, returns `false` if it is not in the trash.
+    pub fn restore_entry(&mut self, id: &Uuid) -> bool {
+        match self.trash.remove(id) {
+            Some(trashed) => {
+                self.insert_entry(trashed.entry);
+                self.changes.record(id, OperationKind::Restored);
+                true
+            },
+            None => {
+                false
+            }
+        }
+    }
+
+    /// Permanently deletes the entry from the trash, returns `false` if it is not in the trash.
+    pub fn purge_entry(&mut self, id: &Uuid) -> bool {
+        match self.trash.remove(id) {
+            Some(_) => {
+                self.changes.record(id, OperationKind::Purged);
+                true
+            },
+            None => {
+                false
+            }
+        }
+    }
+
+    /// Permanently deletes the entries trashed for longer than `age`, returns how many were deleted.
+    pub fn purge_trash(&mut self, age: Duration) -> usize {
+        let limit = Utc::now() - age;
+        let expired: Vec<Uuid> = self.trash.values()
+            .filter(|trashed| trashed.deleted <= limit)
+            .map(|trashed| trashed.entry.id)
+            .collect();
+        for id in &expired {
+            self.purge_entry(id);
+        }
+        expired.len()
+    }
+
+    pub fn empty_trash(&mut self) {
+        let ids: Vec<Uuid> = self.trash.keys().cloned().collect();
+        for id in &ids {
+            self.purge_entry(id);
+        }
+    }
+
+    pub fn trash(&self) -> ::std::collections::hash_map::Values<Uuid, TrashedEntry> {
+        self.trash.values()
+    }
+
+    pub fn get_trashed_entry(&self, id: &Uuid) -> Option<&TrashedEntry> {
+        self.trash.get(id)
+    }
+
+    pub fn insert_conflict(&mut self, conflict: Conflict) {
+        self.conflicts.insert(conflict.id, conflict);
+    }
+
+    /// The entries changed concurrently on two devices, waiting for the user to choose a version.
+    pub fn conflicts(&self) -> ::std::collections::hash_map::Values<Uuid, Conflict> {
+        self.conflicts.values()
+    }
+
+    /// Settles the conflict on an entry, returns `false` if there is none.
+    pub fn resolve_conflict(&mut self, id: &Uuid, resolution: Resolution) -> bool {
+        let conflict = match self.conflicts.remove(id) {
+            Some(conflict) => conflict,
+            None => return false
+        };
+        match resolution {
+            Resolution::Current => (),
+            Resolution::Other => {
+                let live = self.entries.remove(id).is_some();
+                let trashed = self.trash.remove(id).is_some();
+                match conflict.restore(self) {
+                    Some(kind) => self.changes.record(id, kind),
+                    None if live || trashed => self.changes.record(id, OperationKind::Purged),
+                    None => ()
+                }
+            },
+            Resolution::Both => {
+                let mut conflict = conflict;
+                let mut kind = None;
+                if self.entries.contains_key(id) || self.trash.contains_key(id) {
+                    if let Some(ref mut other) = conflict.other {
+                        other.id = Uuid::new_v4();
+                        kind = Some(OperationKind::Added);
+                    }
+                }
+                let copy = conflict.other.as_ref().map(|other| other.id);
+                if let (Some(copy), Some(restored)) = (copy, conflict.restore(self)) {
+                    self.changes.record(&copy, kind.unwrap_or(restored));
+                }
+            }
+        }
+        true
+    }
+
+    /// Brings the entry back as it is in `past`, a previous version of the vault,
+    /// returns `false` if it has no such entry.
+    ///
+    /// The entry keeps its identifier: it replaces the current version, or
+    /// comes back from the trash.
+    pub fn restore_past_entry(&mut self, past: &Vault, id: &Uuid) -> Result<bool, ErrorStack> {
+        let original = match past.entries.get(id) {
+            Some(entry) => entry,
+            None => return Ok(false)
+        };
+        let mut entry = original.clone();
+        if self.key.unsecure() != past.key.unsecure() {
+            // a vault created again since
+            entry.password = self.key.seal(&past.reveal_password(original)?)?;
+            entry.otp = match past.reveal_otp(original)? {
+                Some(otp) => Some(self.key.seal(&otp)?),
+                None => None
+            };
+        }
+        entry.last_modified = Utc::now();
+        let kind =
+            if self.entries.contains_key(id) {
+                OperationKind::Modified
+            } else if self.trash.remove(id).is_some() {
+                OperationKind::Restored
+            } else {
+                OperationKind::Added
+            };
+        self.entries.insert(*id, entry);
+        self.changes.record(id, kind);
+        Ok(true)
+    }
+
+    /// Brings every entry back to its state in `past`, a previous version of
+    /// the vault. Entries added since are moved to the trash.
+    pub fn restore_past_version(&mut self, past: &Vault) -> Result<(), ErrorStack> {
+        for (id, entry) in &past.entries {
+            let unchanged = self.entries.get(id).map(|current| current.last_modified == entry.last_modified).unwrap_or(false);
+            if !unchanged {
+                self.restore_past_entry(past, id)?;
+            }
+        }
+        let added: Vec<Uuid> = self.entries.keys().filter(|id| !past.entries.contains_key(id)).cloned().collect();
+        for id in &added {
+            self.trash_entry(id);
+        }
+        Ok(())
+    }
+
+    /// Whether some entry already has this name.
+    ///
+    /// Several entries may have the same name, this is only meant to warn the user.
+    pub fn has_entry(&self, name: &String) -> bool {
+        self.entries.values().any(|entry| &entry.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn owner(&self) -> &String {
+        &self.owner
+    }
+
+    pub fn key(&self) -> &VaultKey {
+        &self.key
+    }
+
+}
+
+impl<'a> IntoIterator for &'a Vault {
+
+    type Item = &'a Entry;
+
+    type IntoIter = ::std::collections::hash_map::Values<'a, Uuid, Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.values()
+    }
+
+}
+
+/// The version of an entry that was not kept when merging concurrent changes.
+///
+/// The vault holds the other version, or nothing if it was deleted.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub id: Uuid,
+    /// The entry as changed on the other side, `None` if it was deleted there.
+    pub other: Option<Entry>,
+    /// When the other side moved the entry to the trash, if it did.
+    pub deleted: Option<DateTime<Utc>>
+}
+
+impl Conflict {
+
+    /// Puts the other version in the vault, returns the change it makes if any.
+    fn restore(self, vault: &mut Vault) -> Option<OperationKind> {
+        match (self.other, self.deleted) {
+            (Some(entry), Some(deleted)) => {
+                vault.insert_trashed_entry(entry, deleted);
+                Some(OperationKind::Trashed)
+            },
+            (Some(entry), None) => {
+                vault.insert_entry(entry);
+                Some(OperationKind::Modified)
+            },
+            (None, _) => None
+        }
+    }
+
+}
+
+/// How to settle a conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the version in the vault.
+    Current,
+    /// Use the other version instead.
+    Other,
+    /// Keep the version in the vault, and the other one as a copy.
+    Both
+}
+
+/// An entry in the trash, that can still be restored.
+#[derive(Debug, Clone)]
+pub struct TrashedEntry {
+    pub entry: Entry,
+    pub deleted: DateTime<Utc>
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    id: Uuid,
+    pub name: String,
+    /// sealed with the vault key, only decrypted on demand
+    password: Sealed,
+    /// the sealed `otpauth://` URI of the one-time password generator
+    otp: Option<Sealed>,
+    pub comment: Option<String>,
+    pub user: Option<String>,
+    pub uri: Option<String>,
+    pub tags: HashSet<String>,
+    created: DateTime<Utc>,
+    last_modified: DateTime<Utc>
+}
+
+impl Entry {
+
+    fn new(name: String, password: Sealed) -> Entry {
+        let created = Utc::now();
+        return Entry {
+            id: Uuid::new_v4(),
+            name: name,
+            password: password,
+            otp: None,
+            comment: None,
+            user: None,
+            uri: None,
+            created: created,
+            last_modified: created,
+            tags: HashSet::new()
+        }
+    }
+
+    /// Rebuilds an entry that was previously stored, keeping its original dates.
+    pub fn restore(id: Uuid, name: String, password: Sealed, created: DateTime<Utc>, last_modified: DateTime<Utc>) -> Entry {
+        Entry {
+            id: id,
+            name: name,
+            password: password,
+            otp: None,
+            comment: None,
+            user: None,
+            uri: None,
+            created: created,
+            last_modified: last_modified,
+            tags: HashSet::new()
+        }
+    }
+
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
+    pub fn sealed_password(&self) -> &Sealed {
+        &self.password
+    }
+
+    pub fn sealed_otp(&self) -> Option<&Sealed> {
+        self.otp.as_ref()
+    }
+
+    pub fn set_sealed_otp(&mut self, otp: Option<Sealed>) {
+        self.otp = otp;
+    }
+
+    pub fn has_otp(&self) -> bool {
+        self.otp.is_some()
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn last_modified(&self) -> DateTime<Utc> {
+        self.last_modified
+    }
+
+}
@@ -0,0 +1,155 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::fs::{self, File};
+use std::io::{self, Read, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use xdg;
+
+use toml;
+
+use io::atomic::write_atomically;
+
+use sync::{self, SyncClient};
+use sync::git::GitRepository;
+
+/// The user preferences, stored in `$XDG_CONFIG_HOME/repassync/settings.toml`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The time unlocking the vault should take, in milliseconds.
+    pub unlock_time: u64,
+    /// The number of days deleted entries are kept in the trash.
+    pub trash_days: u32,
+    /// The number of seconds after which a copied password is cleared from the clipboard.
+    pub clipboard_timeout: u32,
+    /// The number of minutes of inactivity after which the vault is locked, `0` to never lock.
+    pub lock_timeout: u32,
+    /// The URL of the vault on a repassync server, or with a `webdavs` scheme
+    /// of the vault file on a WebDAV server, empty to not synchronize.
+    pub sync_url: String,
+    /// The token used to authenticate to the server, or the WebDAV password, if it needs one.
+    ///
+    /// It is needed to synchronize before the vault is unlocked, so it is
+    /// stored unencrypted, protected only by the permissions of the file.
+    pub sync_token: String,
+    /// The directory of the vault file, e.g. a folder shared by a file
+    /// synchronization tool, empty for the data directory.
+    pub vault_dir: String,
+    /// Whether every save of the vault is committed to a git repository in its directory.
+    pub git_history: bool,
+    /// The git remote the history is pushed to and pulled from, empty for none.
+    pub git_remote: String
+}
+
+impl Default for Settings {
+
+    fn default() -> Settings {
+        Settings {
+            unlock_time: 500,
+            trash_days: 30,
+            clipboard_timeout: 30,
+            lock_timeout: 5,
+            sync_url: String::new(),
+            sync_token: String::new(),
+            vault_dir: String::new(),
+            git_history: false,
+            git_remote: String::new()
+        }
+    }
+
+}
+
+impl Settings {
+
+    /// Loads the settings, falling back to the default ones if they cannot be read.
+    pub fn load() -> Settings {
+        let xdg_dirs = match xdg::BaseDirectories::with_prefix("repassync") {
+            Ok(xdg_dirs) => xdg_dirs,
+            Err(e) => {
+                warn!("Unable to find settings directory: {}", e);
+                return Settings::default();
+            }
+        };
+        match xdg_dirs.find_config_file("settings.toml") {
+            Some(path) => {
+                let mut content = String::new();
+                match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+                    Ok(_) => {
+                        toml::from_str(&content).unwrap_or_else(|e| {
+                            warn!("Invalid settings file: {}", e);
+                            Settings::default()
+                        })
+                    },
+                    Err(e) => {
+                        warn!("Unable to read settings file: {}", e);
+                        Settings::default()
+                    }
+                }
+            },
+            None => {
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("repassync")
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let path = xdg_dirs.place_config_file("settings.toml")?;
+        let content = toml::to_string(self).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        write_atomically(path, content.as_bytes())
+    }
+
+    pub fn unlock_time(&self) -> Duration {
+        Duration::from_millis(self.unlock_time)
+    }
+
+    /// The path of the vault file, creating its directory if needed.
+    pub fn vault_path(&self) -> io::Result<PathBuf> {
+        if self.vault_dir.is_empty() {
+            let xdg_dirs = xdg::BaseDirectories::with_prefix("repassync")
+                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+            xdg_dirs.place_data_file("repassync.vault")
+        } else {
+            fs::create_dir_all(&self.vault_dir)?;
+            Ok(Path::new(&self.vault_dir).join("repassync.vault"))
+        }
+    }
+
+    /// The client for the configured server, if synchronization is enabled.
+    pub fn sync_client(&self) -> Option<sync::Result<SyncClient>> {
+        if self.sync_url.is_empty() {
+            None
+        } else {
+            let token = if self.sync_token.is_empty() { None } else { Some(self.sync_token.clone()) };
+            Some(SyncClient::new(&self.sync_url, token))
+        }
+    }
+
+    /// The git repository of the vault history, with the configured remote,
+    /// if the history is enabled.
+    pub fn git_repository(&self) -> Option<sync::Result<GitRepository>> {
+        if !self.git_history {
+            return None;
+        }
+        let open = || -> sync::Result<GitRepository> {
+            let repository = GitRepository::open(&self.vault_path()?)?;
+            repository.set_remote(&self.git_remote)?;
+            Ok(repository)
+        };
+        Some(open())
+    }
+
+}
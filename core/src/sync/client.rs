@@ -0,0 +1,229 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::io::Read;
+use std::time::Duration;
+
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use reqwest::header::{Authorization, Basic, Bearer, ETag, EntityTag, IfMatch, IfNoneMatch};
+
+use url::percent_encoding::percent_decode;
+
+use sync::{Result, SyncError};
+
+/// The time after which a request to the server is abandoned.
+const TIMEOUT: u64 = 30;
+
+/// The result of downloading the vault.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fetched {
+    /// The server version is the one with the given revision.
+    NotModified,
+    /// The server has no vault yet.
+    Missing,
+    /// The server version and its revision.
+    Vault(Vec<u8>, String)
+}
+
+/// The result of uploading the vault.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pushed {
+    /// The new revision of the vault.
+    Stored(String),
+    /// The server version is not the expected one anymore.
+    Conflict
+}
+
+/// The protocol spoken by the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    /// A repassync server, authenticated with a bearer token.
+    Repassync,
+    /// A WebDAV server such as Nextcloud, authenticated with a user name and password.
+    WebDav
+}
+
+/// A client for the vault resource of a repassync or WebDAV server.
+///
+/// The vault is downloaded with `GET` and uploaded with `PUT` at the configured
+/// URL, with `If-Match`/`If-None-Match` conditions on its revision.
+///
+/// URLs with a `webdav` or `webdavs` scheme designate a file on a WebDAV
+/// server, reached over HTTP or HTTPS, e.g.
+/// `webdavs://alice@cloud.example.org/remote.php/dav/files/alice/repassync.vault`.
+/// The user name is taken from the URL, and the token is the password.
+pub struct SyncClient {
+    client: Client,
+    url: Url,
+    protocol: Protocol,
+    user: Option<String>,
+    token: Option<String>
+}
+
+impl SyncClient {
+
+    /// A client for the vault at `url`, authenticating with `token` if any.
+    pub fn new(url: &str, token: Option<String>) -> Result<SyncClient> {
+        let url = Url::parse(url).map_err(|e| SyncError::InvalidUrl(e.to_string()))?;
+        let (protocol, scheme) = match url.scheme() {
+            "http" => (Protocol::Repassync, "http"),
+            "https" => (Protocol::Repassync, "https"),
+            "webdav" => (Protocol::WebDav, "http"),
+            "webdavs" => (Protocol::WebDav, "https"),
+            scheme => return Err(SyncError::InvalidUrl(format!("unsupported scheme {}", scheme)))
+        };
+        let user = if url.username().is_empty() {
+            None
+        } else {
+            Some(percent_decode(url.username().as_bytes()).decode_utf8_lossy().into_owned())
+        };
+        // the scheme of a URL cannot be changed to a special one such as http
+        let mut url = Url::parse(&format!("{}{}", scheme, &url.as_str()[url.scheme().len()..]))
+            .map_err(|e| SyncError::InvalidUrl(e.to_string()))?;
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        let client = Client::builder()
+            .timeout(Duration::from_secs(TIMEOUT))
+            .build()?;
+        Ok(SyncClient {
+            client: client,
+            url: url,
+            protocol: protocol,
+            user: user,
+            token: token
+        })
+    }
+
+    fn authorize(&self, request: &mut RequestBuilder) {
+        match self.protocol {
+            Protocol::Repassync => {
+                if let Some(ref token) = self.token {
+                    request.header(Authorization(Bearer { token: token.clone() }));
+                }
+            },
+            Protocol::WebDav => {
+                if self.user.is_some() || self.token.is_some() {
+                    request.header(Authorization(Basic {
+                        username: self.user.clone().unwrap_or_default(),
+                        password: self.token.clone()
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Downloads the vault, unless its revision is still `etag`.
+    pub fn fetch(&self, etag: Option<&str>) -> Result<Fetched> {
+        let mut request = self.client.get(self.url.clone());
+        self.authorize(&mut request);
+        if let Some(etag) = etag {
+            request.header(IfNoneMatch::Items(vec![EntityTag::strong(etag.to_owned())]));
+        }
+        let mut response = request.send()?;
+        match response.status() {
+            StatusCode::NotModified => {
+                Ok(Fetched::NotModified)
+            },
+            StatusCode::NotFound => {
+                Ok(Fetched::Missing)
+            },
+            StatusCode::Ok => {
+                let etag = revision(&response)?;
+                let mut content = vec![];
+                response.read_to_end(&mut content)?;
+                Ok(Fetched::Vault(content, etag))
+            },
+            status => {
+                Err(unexpected(status))
+            }
+        }
+    }
+
+    /// Uploads the vault if the server version still has revision `etag`,
+    /// or if there is none on the server when `etag` is `None`.
+    pub fn push(&self, content: &[u8], etag: Option<&str>) -> Result<Pushed> {
+        let mut response = self.put(content, etag)?;
+        if self.protocol == Protocol::WebDav && response.status() == StatusCode::Conflict {
+            // the folder of the vault does not exist yet
+            self.create_folder()?;
+            response = self.put(content, etag)?;
+        }
+        match response.status() {
+            StatusCode::Ok | StatusCode::Created | StatusCode::NoContent => {
+                match revision(&response) {
+                    Err(SyncError::MissingRevision) if self.protocol == Protocol::WebDav => {
+                        Ok(Pushed::Stored(self.stored_revision(content)?))
+                    },
+                    etag => Ok(Pushed::Stored(etag?))
+                }
+            },
+            StatusCode::PreconditionFailed => {
+                Ok(Pushed::Conflict)
+            },
+            status => {
+                Err(unexpected(status))
+            }
+        }
+    }
+
+    fn put(&self, content: &[u8], etag: Option<&str>) -> Result<Response> {
+        let mut request = self.client.put(self.url.clone());
+        self.authorize(&mut request);
+        match etag {
+            Some(etag) => {
+                request.header(IfMatch::Items(vec![EntityTag::strong(etag.to_owned())]));
+            },
+            None => {
+                request.header(IfNoneMatch::Any);
+            }
+        }
+        Ok(request.body(content.to_vec()).send()?)
+    }
+
+    /// Creates the WebDAV collection containing the vault.
+    fn create_folder(&self) -> Result<()> {
+        let folder = self.url.join("./").map_err(|e| SyncError::InvalidUrl(e.to_string()))?;
+        let mut request = self.client.request(Method::Extension("MKCOL".to_owned()), folder);
+        self.authorize(&mut request);
+        let response = request.send()?;
+        match response.status() {
+            // created, or created meanwhile
+            StatusCode::Created | StatusCode::MethodNotAllowed => Ok(()),
+            status => Err(unexpected(status))
+        }
+    }
+
+    /// The revision of the vault just uploaded, for WebDAV servers that do
+    /// not send it in response.
+    fn stored_revision(&self, content: &[u8]) -> Result<String> {
+        match self.fetch(None)? {
+            Fetched::Vault(ref stored, ref etag) if stored.as_slice() == content => Ok(etag.clone()),
+            // changed again meanwhile, the revision of the upload is lost
+            _ => Err(SyncError::MissingRevision)
+        }
+    }
+
+}
+
+fn revision(response: &Response) -> Result<String> {
+    match response.headers().get::<ETag>() {
+        Some(&ETag(ref tag)) => Ok(tag.tag().to_owned()),
+        None => Err(SyncError::MissingRevision)
+    }
+}
+
+fn unexpected(status: StatusCode) -> SyncError {
+    match status {
+        StatusCode::Unauthorized | StatusCode::Forbidden => SyncError::Unauthorized,
+        status => SyncError::Status(u16::from(status))
+    }
+}
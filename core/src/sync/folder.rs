@@ -0,0 +1,147 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Synchronization through a folder shared by a file synchronization tool,
+//! such as Syncthing, Nextcloud or Dropbox.
+//!
+//! The tool copies the vault file between machines on its own. When the file
+//! changed on two machines at once, it keeps one version and leaves the other
+//! one next to it under another name. These conflict copies are merged back
+//! into the vault, and removed.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use io::atomic::write_atomically;
+use io::error::{Result, VaultError};
+use io::file::{DecryptedVaultFile, EncryptedVaultFile};
+use io::kdf::Kdf;
+
+/// Whether `path` is a conflict copy of the vault file at `vault_path`.
+///
+/// Copies are in the same directory, with a name made of the name of the
+/// vault file, some text mentioning the conflict, and the same extension:
+/// `repassync.sync-conflict-20180102-030405-ABCDEFG.vault` (Syncthing),
+/// `repassync (conflicted copy 2018-01-02 030405).vault` (Nextcloud),
+/// `repassync (Alice's conflicted copy 2018-01-02).vault` (Dropbox) or
+/// `repassync_conflict-20180102-030405.vault` (ownCloud).
+pub fn is_conflict_copy(vault_path: &Path, path: &Path) -> bool {
+    if path == vault_path || path.parent() != vault_path.parent() {
+        return false;
+    }
+    let stem = vault_path.file_stem().and_then(|stem| stem.to_str());
+    let extension = vault_path.extension().and_then(|extension| extension.to_str());
+    let name = path.file_name().and_then(|name| name.to_str());
+    match (stem, extension, name) {
+        (Some(stem), Some(extension), Some(name)) => {
+            let suffix = format!(".{}", extension);
+            if name.len() <= stem.len() + suffix.len() || !name.starts_with(stem) || !name.ends_with(&suffix) {
+                return false;
+            }
+            name[stem.len()..name.len() - suffix.len()].to_lowercase().contains("conflict")
+        },
+        _ => false
+    }
+}
+
+/// The conflict copies of the vault file, sorted by name.
+pub fn conflict_copies(vault_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = match vault_path.parent() {
+        Some(dir) => dir,
+        None => return Ok(vec![])
+    };
+    let mut copies = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_conflict_copy(vault_path, &path) {
+            copies.push(path);
+        }
+    }
+    copies.sort();
+    Ok(copies)
+}
+
+/// The vault file merged with its conflict copies.
+#[derive(Debug)]
+pub struct Merged {
+    pub vault: DecryptedVaultFile,
+    /// The content written to the vault file.
+    pub content: Vec<u8>,
+    /// The number of entries changed on both sides, left for the user to settle.
+    pub conflicts: usize,
+    /// The copies merged into the vault, removed once it is written.
+    pub merged: Vec<PathBuf>,
+    /// The copies that could not be opened with the passphrase, left as they are.
+    pub skipped: Vec<PathBuf>
+}
+
+impl Merged {
+
+    /// Writes the merged vault in place of the vault file, and removes the merged copies.
+    pub fn write(&self, vault_path: &Path) -> Result<()> {
+        write_atomically(vault_path, &self.content)?;
+        for copy in &self.merged {
+            if let Err(e) = fs::remove_file(copy) {
+                warn!("Unable to remove conflict copy {}: {}", copy.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+}
+
+/// Merges the conflict copies into the vault file, on behalf of `device`.
+///
+/// The result is encrypted again with `kdf`, but nothing is written until
+/// `Merged::write` is called. Returns `None` if there is no copy that can be
+/// merged.
+pub fn merge_conflict_copies(vault_path: &Path, passphrase: &SecStr, kdf: &Kdf, device: Uuid) -> Result<Option<Merged>> {
+    let copies = conflict_copies(vault_path)?;
+    if copies.is_empty() {
+        return Ok(None);
+    }
+    let open = |path: &Path| -> Result<DecryptedVaultFile> {
+        EncryptedVaultFile::from_bytes(&fs::read(path)?)?.decrypt(passphrase.clone())
+    };
+    let mut vault = open(vault_path)?;
+    let mut conflicts = 0;
+    let mut merged = vec![];
+    let mut skipped = vec![];
+    for copy in copies {
+        match open(&copy) {
+            Ok(other) => {
+                // the common version is unknown, the change logs tell who changed what
+                let (next, new_conflicts) = DecryptedVaultFile::merge(None, &vault, &other)?;
+                vault = next;
+                conflicts += new_conflicts;
+                merged.push(copy);
+            },
+            Err(VaultError::WrongPassphrase) => {
+                warn!("Conflict copy {} has another passphrase, leaving it", copy.display());
+                skipped.push(copy);
+            },
+            Err(e) => {
+                warn!("Unable to open conflict copy {}: {}", copy.display(), e);
+                skipped.push(copy);
+            }
+        }
+    }
+    if merged.is_empty() {
+        return Ok(None);
+    }
+    vault.acknowledge(device);
+    let content = vault.encrypt(passphrase.clone(), kdf)?.to_bytes()?;
+    Ok(Some(Merged { vault, content, conflicts, merged, skipped }))
+}
@@ -0,0 +1,334 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! History of the vault in a git repository, and its synchronization with a git remote.
+//!
+//! The folder of the vault file is the repository, every save is committed.
+//! The remote, if any, is `origin`. The vault file is encrypted, so it is
+//! never merged as text: when both sides changed it, the versions are merged
+//! like any other copies of the vault, and the result is committed on top of
+//! both.
+//!
+//! The `git` command is used, so that the configuration of the user, such as
+//! SSH keys and credential helpers, applies to the remote.
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::prelude::*;
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use io::atomic::write_atomically;
+use io::file::{DecryptedVaultFile, EncryptedVaultFile};
+use io::kdf::Kdf;
+
+use sync::{Result, SyncError};
+
+/// The name of the remote the vault is synchronized with.
+const REMOTE: &str = "origin";
+
+/// A commit of the vault file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub id: String,
+    pub date: DateTime<Utc>,
+    pub message: String
+}
+
+/// The repository holding the history of the vault file.
+pub struct GitRepository {
+    dir: PathBuf,
+    /// the name of the vault file in the repository
+    file: String
+}
+
+impl GitRepository {
+
+    /// Opens the repository of the folder of the vault file, creating it if needed.
+    pub fn open(vault_path: &Path) -> Result<GitRepository> {
+        let dir = match vault_path.parent() {
+            Some(dir) => dir.to_path_buf(),
+            None => return Err(SyncError::Git(format!("{} is not in a folder", vault_path.display())))
+        };
+        let file = match vault_path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_owned(),
+            None => return Err(SyncError::Git(format!("invalid vault file name {}", vault_path.display())))
+        };
+        let repository = GitRepository { dir: dir, file: file };
+        if !repository.dir.join(".git").exists() {
+            repository.git(&["init", "-q"])?;
+            // the encrypted vault is not text, no use showing diffs
+            let info = repository.dir.join(".git").join("info");
+            fs::create_dir_all(&info)?;
+            write_atomically(info.join("attributes"), format!("{} binary\n", repository.file).as_bytes())?;
+        }
+        Ok(repository)
+    }
+
+    /// Runs git in the repository, returns what it printed.
+    fn git(&self, args: &[&str]) -> Result<Vec<u8>> {
+        self.git_with_input(args, None)
+    }
+
+    fn git_with_input(&self, args: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>> {
+        let mut child = Command::new("git")
+            .args(args)
+            .current_dir(&self.dir)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(input) = input {
+            if let Some(ref mut stdin) = child.stdin {
+                stdin.write_all(input)?;
+            }
+            child.stdin = None;
+        }
+        let output = child.wait_with_output()?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(failed(args, &output.stderr))
+        }
+    }
+
+    /// Runs git, returns its first line of output, or `None` if it failed.
+    fn query(&self, args: &[&str]) -> Result<Option<String>> {
+        match self.git(args) {
+            Ok(output) => Ok(String::from_utf8_lossy(&output).lines().next().map(str::to_owned)),
+            Err(SyncError::Git(_)) => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Runs a git command that creates commits, with a default identity if the user has none.
+    fn git_commit(&self, args: &[&str]) -> Result<Vec<u8>> {
+        let mut all = vec![];
+        if self.query(&["config", "user.email"])?.is_none() {
+            all.extend_from_slice(&["-c", "user.name=Repassync", "-c", "user.email=repassync@localhost"]);
+        }
+        all.extend_from_slice(args);
+        self.git(&all)
+    }
+
+    /// Commits the vault file, returns `false` if it did not change.
+    pub fn commit(&self, message: &str) -> Result<bool> {
+        if !self.dir.join(&self.file).exists() {
+            return Ok(false);
+        }
+        self.git(&["add", "--", &self.file])?;
+        let changed = self.git(&["diff", "--cached", "--quiet", "--", &self.file]).is_err();
+        if changed {
+            self.git_commit(&["commit", "-q", "-m", message, "--", &self.file])?;
+        }
+        Ok(changed)
+    }
+
+    /// The commits of the vault file, the most recent first.
+    pub fn history(&self) -> Result<Vec<Commit>> {
+        if self.head()?.is_none() {
+            return Ok(vec![]);
+        }
+        let output = self.git(&["log", "--format=%H%x09%ct%x09%s", "--", &self.file])?;
+        let mut commits = vec![];
+        for line in String::from_utf8_lossy(&output).lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (id, date, message) = (fields.next(), fields.next(), fields.next());
+            let date = date.and_then(|date| date.parse::<i64>().ok()).map(|date| Utc.timestamp(date, 0));
+            if let (Some(id), Some(date)) = (id, date) {
+                commits.push(Commit { id: id.to_owned(), date: date, message: message.unwrap_or_default().to_owned() });
+            }
+        }
+        Ok(commits)
+    }
+
+    /// The vault file as of the given commit.
+    pub fn content_at(&self, commit: &str) -> Result<Vec<u8>> {
+        self.git(&["show", &format!("{}:./{}", commit, self.file)])
+    }
+
+    /// Uses `url` as the remote, none if it is empty.
+    pub fn set_remote(&self, url: &str) -> Result<()> {
+        let current = self.query(&["remote", "get-url", REMOTE])?;
+        match current {
+            Some(ref current) if current == url => (),
+            Some(_) if url.is_empty() => { self.git(&["remote", "remove", REMOTE])?; },
+            Some(_) => { self.git(&["remote", "set-url", "--", REMOTE, url])?; },
+            None if url.is_empty() => (),
+            None => { self.git(&["remote", "add", "--", REMOTE, url])?; }
+        }
+        Ok(())
+    }
+
+    fn has_remote(&self) -> Result<bool> {
+        Ok(self.query(&["remote", "get-url", REMOTE])?.is_some())
+    }
+
+    /// The current commit, `None` before the first one.
+    fn head(&self) -> Result<Option<String>> {
+        self.query(&["rev-parse", "--verify", "-q", "HEAD"])
+    }
+
+    fn branch(&self) -> Result<String> {
+        Ok(self.query(&["symbolic-ref", "--short", "HEAD"])?.unwrap_or_else(|| "master".to_owned()))
+    }
+
+    /// The last fetched commit of the branch on the remote, if it has one.
+    fn remote_head(&self) -> Result<Option<String>> {
+        let branch = format!("refs/remotes/{}/{}", REMOTE, self.branch()?);
+        self.query(&["rev-parse", "--verify", "-q", &branch])
+    }
+
+    fn push(&self) -> Result<()> {
+        let branch = format!("HEAD:refs/heads/{}", self.branch()?);
+        self.git(&["push", "-q", REMOTE, &branch])?;
+        Ok(())
+    }
+
+    /// Commits `content` as the vault file on top of both `local` and `remote`,
+    /// without touching the working copy. Returns the new commit.
+    fn merge_commit(&self, content: &[u8], local: &str, remote: &str, message: &str) -> Result<String> {
+        let blob = self.git_with_input(&["hash-object", "-w", "--stdin"], Some(content))?;
+        let blob = String::from_utf8_lossy(&blob).trim().to_owned();
+        // a separate index, the one of the working copy is left alone
+        let index = self.dir.join(".git").join("repassync-merge-index");
+        let tree = {
+            let with_index = |args: &[&str]| -> Result<Vec<u8>> {
+                let output = Command::new("git")
+                    .args(args)
+                    .current_dir(&self.dir)
+                    .env("GIT_INDEX_FILE", &index)
+                    .output()?;
+                if output.status.success() {
+                    Ok(output.stdout)
+                } else {
+                    Err(failed(args, &output.stderr))
+                }
+            };
+            with_index(&["read-tree", local])?;
+            with_index(&["update-index", "--add", "--cacheinfo", &format!("100644,{},{}", blob, self.file)])?;
+            with_index(&["write-tree"])
+        };
+        let _ = fs::remove_file(&index);
+        let tree = String::from_utf8_lossy(&tree?).trim().to_owned();
+        let commit = self.git_commit(&["commit-tree", &tree, "-p", local, "-p", remote, "-m", message])?;
+        Ok(String::from_utf8_lossy(&commit).trim().to_owned())
+    }
+
+    /// Moves the current branch to `commit`, whose vault file is `content`, and writes it.
+    ///
+    /// Nothing must have been saved since `synchronize` returned the commit.
+    pub fn advance(&self, commit: &str, content: &[u8]) -> Result<()> {
+        write_atomically(self.dir.join(&self.file), content)?;
+        self.git(&["update-ref", "-m", "repassync: synchronize", "HEAD", commit])?;
+        // the index follows, the working copy is already up to date
+        self.git(&["reset", "-q"])?;
+        Ok(())
+    }
+
+}
+
+/// The error of a git command, named after its subcommand.
+fn failed(args: &[&str], stderr: &[u8]) -> SyncError {
+    let mut args = args.iter();
+    let mut command = None;
+    while let Some(arg) = args.next() {
+        if *arg == "-c" {
+            args.next();
+        } else {
+            command = Some(arg);
+            break;
+        }
+    }
+    let message = String::from_utf8_lossy(stderr).trim().to_owned();
+    SyncError::Git(format!("git {} failed: {}", command.unwrap_or(&"command"), message))
+}
+
+/// The result of synchronizing the repository with its remote.
+#[derive(Debug)]
+pub enum Pulled {
+    /// The remote had nothing new, local commits were pushed.
+    UpToDate,
+    /// The remote had new commits, or both sides were merged.
+    ///
+    /// The vault file is `content` at `commit`, to check out with
+    /// `GitRepository::advance`. Pushing waits for the next synchronization.
+    Pulled {
+        commit: String,
+        content: Vec<u8>,
+        /// The merged vault and its number of conflicts, if both sides changed.
+        merged: Option<(DecryptedVaultFile, usize)>
+    },
+    /// Both sides changed, and the passphrase is needed to merge them.
+    Diverged
+}
+
+/// Commits the vault file, pulls the commits of the remote, merging the vault
+/// on behalf of `device` if both sides changed, and pushes local commits.
+///
+/// The merged vault is encrypted with `kdf`. The three versions are decrypted
+/// with `passphrase`; the common one may use a previous passphrase, it is then
+/// merged without.
+pub fn synchronize(repository: &GitRepository, passphrase: Option<&SecStr>, kdf: &Kdf, device: Uuid) -> Result<Pulled> {
+    repository.commit("Save vault")?;
+    if !repository.has_remote()? {
+        return Ok(Pulled::UpToDate);
+    }
+    repository.git(&["fetch", "-q", REMOTE])?;
+    let remote = match repository.remote_head()? {
+        Some(remote) => remote,
+        None => {
+            // an empty remote
+            if repository.head()?.is_some() {
+                repository.push()?;
+            }
+            return Ok(Pulled::UpToDate);
+        }
+    };
+    let local = match repository.head()? {
+        Some(local) => local,
+        None => {
+            let content = repository.content_at(&remote)?;
+            return Ok(Pulled::Pulled { commit: remote, content: content, merged: None });
+        }
+    };
+    let base = repository.query(&["merge-base", &local, &remote])?;
+    if base.as_ref() == Some(&remote) {
+        if local != remote {
+            repository.push()?;
+        }
+        return Ok(Pulled::UpToDate);
+    }
+    if base.as_ref() == Some(&local) {
+        let content = repository.content_at(&remote)?;
+        return Ok(Pulled::Pulled { commit: remote, content: content, merged: None });
+    }
+    let passphrase = match passphrase {
+        Some(passphrase) => passphrase,
+        None => return Ok(Pulled::Diverged)
+    };
+    let open = |commit: &str| -> Result<DecryptedVaultFile> {
+        Ok(EncryptedVaultFile::from_bytes(&repository.content_at(commit)?)?.decrypt(passphrase.clone())?)
+    };
+    let local_vault = open(&local)?;
+    let remote_vault = open(&remote)?;
+    let base_vault = base.and_then(|base| open(&base).ok());
+    let (mut merged, conflicts) = DecryptedVaultFile::merge(base_vault.as_ref(), &local_vault, &remote_vault)?;
+    merged.acknowledge(device);
+    let content = merged.encrypt(passphrase.clone(), kdf)?.to_bytes()?;
+    let commit = repository.merge_commit(&content, &local, &remote, "Merge vault")?;
+    Ok(Pulled::Pulled { commit: commit, content: content, merged: Some((merged, conflicts)) })
+}
@@ -0,0 +1,251 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Synchronization of the encrypted vault file with a repassync or WebDAV server.
+//!
+//! The server only stores the vault file as is, it never sees anything but
+//! ciphertext. Each version it stores gets a new revision, sent as its `ETag`.
+//! Uploads are conditional on the revision the local file is based on, so
+//! that changes made on another device are detected instead of overwritten.
+//!
+//! The local vault file is the copy the vault is unlocked from, so that it
+//! stays usable while the server cannot be reached. Changes made meanwhile
+//! are pushed by a later synchronization.
+//!
+//! The vault may also be kept in a folder synchronized by another tool, see
+//! `folder`, or in a git repository pushed to a git remote, see `git`.
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+use secstr::SecStr;
+
+use reqwest;
+
+use io::device::device_id;
+use io::error::VaultError;
+use io::file::{DecryptedVaultFile, EncryptedVaultFile};
+use io::kdf::Kdf;
+
+mod client;
+mod state;
+pub mod folder;
+pub mod git;
+
+pub use self::client::{SyncClient, Fetched, Pushed};
+pub use self::state::SyncState;
+
+#[derive(Debug)]
+pub enum SyncError {
+    /// The server address is not a valid URL.
+    InvalidUrl(String),
+    /// The server could not be reached, or the connection failed.
+    Http(reqwest::Error),
+    /// The server refused the token, or the user name and password.
+    Unauthorized,
+    /// The server answered with an unexpected status.
+    Status(u16),
+    /// The server answered without the revision of the vault.
+    MissingRevision,
+    /// The local synchronization state could not be read or written.
+    Io(io::Error),
+    /// The vault files could not be merged.
+    Vault(VaultError),
+    /// A git command failed.
+    Git(String)
+}
+
+pub type Result<T> = result::Result<T, SyncError>;
+
+impl fmt::Display for SyncError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyncError::InvalidUrl(ref msg) => write!(f, "invalid server address: {}", msg),
+            SyncError::Http(ref e) => write!(f, "{}", e),
+            SyncError::Unauthorized => write!(f, "the server refused the credentials"),
+            SyncError::Status(status) => write!(f, "unexpected server response {}", status),
+            SyncError::MissingRevision => write!(f, "the server did not send the vault revision"),
+            SyncError::Io(ref e) => write!(f, "{}", e),
+            SyncError::Vault(ref e) => write!(f, "{}", e),
+            SyncError::Git(ref msg) => write!(f, "{}", msg)
+        }
+    }
+
+}
+
+impl error::Error for SyncError {
+
+    fn description(&self) -> &str {
+        match *self {
+            SyncError::InvalidUrl(_) => "invalid server address",
+            SyncError::Http(ref e) => e.description(),
+            SyncError::Unauthorized => "unauthorized",
+            SyncError::Status(_) => "unexpected server response",
+            SyncError::MissingRevision => "missing vault revision",
+            SyncError::Io(ref e) => e.description(),
+            SyncError::Vault(ref e) => e.description(),
+            SyncError::Git(_) => "git failed"
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            SyncError::Http(ref e) => Some(e),
+            SyncError::Io(ref e) => Some(e),
+            SyncError::Vault(ref e) => Some(e),
+            _ => None
+        }
+    }
+
+}
+
+impl From<reqwest::Error> for SyncError {
+
+    fn from(e: reqwest::Error) -> SyncError {
+        SyncError::Http(e)
+    }
+
+}
+
+impl From<io::Error> for SyncError {
+
+    fn from(e: io::Error) -> SyncError {
+        SyncError::Io(e)
+    }
+
+}
+
+impl From<VaultError> for SyncError {
+
+    fn from(e: VaultError) -> SyncError {
+        SyncError::Vault(e)
+    }
+
+}
+
+/// What a synchronization did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncOutcome {
+    /// Both sides already had the same version.
+    UpToDate,
+    /// The local file was uploaded.
+    Pushed,
+    /// The server has a newer version, to write over the local file.
+    Pulled(Vec<u8>),
+    /// Both sides changed since the last synchronization.
+    ///
+    /// The state is left untouched, the server version and its revision are
+    /// returned so that the changes can be reconciled.
+    Conflict(Vec<u8>, String)
+}
+
+/// Synchronizes the local vault file, if any, with the server.
+///
+/// Local changes are pushed first. When the local file is not known to be
+/// based on the server version, it is never replaced and a conflict is
+/// reported instead. The state is updated with the new revision, it is up
+/// to the caller to write pulled content and to save the state.
+pub fn synchronize(client: &SyncClient, state: &mut SyncState, local: Option<&[u8]>) -> Result<SyncOutcome> {
+    if let Some(local) = local {
+        if state.dirty || state.etag.is_none() {
+            return push(client, state, local);
+        }
+    }
+    let known = if local.is_some() { state.etag.clone() } else { None };
+    match client.fetch(known.as_ref().map(String::as_str))? {
+        Fetched::NotModified => {
+            Ok(SyncOutcome::UpToDate)
+        },
+        Fetched::Missing => {
+            match local {
+                Some(local) => {
+                    // the vault was removed from the server, upload it again
+                    state.etag = None;
+                    push(client, state, local)
+                },
+                None => Ok(SyncOutcome::UpToDate)
+            }
+        },
+        Fetched::Vault(content, etag) => {
+            state.etag = Some(etag);
+            state.dirty = false;
+            Ok(SyncOutcome::Pulled(content))
+        }
+    }
+}
+
+fn push(client: &SyncClient, state: &mut SyncState, local: &[u8]) -> Result<SyncOutcome> {
+    match client.push(local, state.etag.as_ref().map(String::as_str))? {
+        Pushed::Stored(etag) => {
+            state.etag = Some(etag);
+            state.dirty = false;
+            Ok(SyncOutcome::Pushed)
+        },
+        Pushed::Conflict => {
+            match client.fetch(None)? {
+                Fetched::Vault(content, etag) => {
+                    Ok(SyncOutcome::Conflict(content, etag))
+                },
+                _ => {
+                    // removed in between, the next synchronization uploads it
+                    state.etag = None;
+                    Ok(SyncOutcome::UpToDate)
+                }
+            }
+        }
+    }
+}
+
+/// The result of reconciling a conflict.
+#[derive(Debug)]
+pub enum Reconciled {
+    /// The merged file was pushed.
+    Merged {
+        content: Vec<u8>,
+        vault: DecryptedVaultFile,
+        /// The number of entries changed on both sides, left for the user to settle.
+        conflicts: usize
+    },
+    /// The server version changed again meanwhile.
+    Retry
+}
+
+/// Merges the local and server versions of the vault and pushes the result.
+///
+/// Both are decrypted with the passphrase, as well as `base`, the version they
+/// were last synchronized at, if known. The merged vault is encrypted with
+/// `kdf` and pushed on top of the server revision `etag`. It is up to the
+/// caller to write the merged content, and to keep it as the new base.
+pub fn reconcile(client: &SyncClient, state: &mut SyncState, passphrase: &SecStr, kdf: &Kdf, local: &[u8], remote: &[u8], etag: &str, base: Option<&[u8]>) -> Result<Reconciled> {
+    let open = |content: &[u8]| -> ::std::result::Result<DecryptedVaultFile, VaultError> {
+        EncryptedVaultFile::from_bytes(content)?.decrypt(passphrase.clone())
+    };
+    let local = open(local)?;
+    let remote = open(remote)?;
+    // the base may use a previous passphrase, merging without it only loses deletions
+    let base = base.and_then(|base| open(base).ok());
+    let (mut merged, conflicts) = DecryptedVaultFile::merge(base.as_ref(), &local, &remote)?;
+    merged.acknowledge(device_id()?);
+    let content = merged.encrypt(passphrase.clone(), kdf)?.to_bytes()?;
+    match client.push(&content, Some(etag))? {
+        Pushed::Stored(etag) => {
+            state.etag = Some(etag);
+            state.dirty = false;
+            Ok(Reconciled::Merged { content, vault: merged, conflicts })
+        },
+        Pushed::Conflict => {
+            Ok(Reconciled::Retry)
+        }
+    }
+}
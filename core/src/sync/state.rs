@@ -0,0 +1,111 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::fs::{self, File};
+use std::io::{self, Read, Error, ErrorKind};
+
+use xdg;
+
+use toml;
+
+use io::atomic::write_atomically;
+
+/// What is known of the server version, stored in `$XDG_DATA_HOME/repassync/sync.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncState {
+    /// The revision of the server version the local file is based on.
+    pub etag: Option<String>,
+    /// Whether the local file was changed since it was last synchronized.
+    pub dirty: bool
+}
+
+impl SyncState {
+
+    /// Loads the state, falling back to an unknown server version if it cannot be read.
+    pub fn load() -> SyncState {
+        let xdg_dirs = match xdg::BaseDirectories::with_prefix("repassync") {
+            Ok(xdg_dirs) => xdg_dirs,
+            Err(e) => {
+                warn!("Unable to find data directory: {}", e);
+                return SyncState::default();
+            }
+        };
+        match xdg_dirs.find_data_file("sync.toml") {
+            Some(path) => {
+                let mut content = String::new();
+                match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+                    Ok(_) => {
+                        toml::from_str(&content).unwrap_or_else(|e| {
+                            warn!("Invalid synchronization state: {}", e);
+                            SyncState::default()
+                        })
+                    },
+                    Err(e) => {
+                        warn!("Unable to read synchronization state: {}", e);
+                        SyncState::default()
+                    }
+                }
+            },
+            None => {
+                SyncState::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("repassync")
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let path = xdg_dirs.place_data_file("sync.toml")?;
+        let content = toml::to_string(self).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        write_atomically(path, content.as_bytes())
+    }
+
+    /// The vault file as last synchronized, the base of three-way merges.
+    pub fn load_base() -> Option<Vec<u8>> {
+        let path = xdg::BaseDirectories::with_prefix("repassync").ok()
+            .and_then(|xdg_dirs| xdg_dirs.find_data_file("sync.base"))?;
+        let mut content = vec![];
+        match File::open(path).and_then(|mut file| file.read_to_end(&mut content)) {
+            Ok(_) => Some(content),
+            Err(e) => {
+                warn!("Unable to read synchronization base: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn save_base(content: &[u8]) -> io::Result<()> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix("repassync")
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let path = xdg_dirs.place_data_file("sync.base")?;
+        write_atomically(path, content)
+    }
+
+    /// Forgets the base, when the server changed.
+    pub fn remove_base() -> io::Result<()> {
+        let path = xdg::BaseDirectories::with_prefix("repassync").ok()
+            .and_then(|xdg_dirs| xdg_dirs.find_data_file("sync.base"));
+        match path {
+            Some(path) => fs::remove_file(path),
+            None => Ok(())
+        }
+    }
+
+    /// Records that the local file was changed and must be pushed.
+    pub fn mark_dirty() -> io::Result<()> {
+        let mut state = SyncState::load();
+        state.dirty = true;
+        state.save()
+    }
+
+}
@@ -0,0 +1,50 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use uuid::Uuid;
+
+use util::Otp;
+
+/// An URI the application can open.
+#[derive(Debug, Clone)]
+pub enum Link {
+    /// An `otpauth://` URI, to create an entry with this one-time password generator.
+    NewOtp(Otp),
+    /// A `repassync://entry/<id>` link to an existing entry.
+    Entry(Uuid)
+}
+
+impl Link {
+
+    pub fn parse(uri: &str) -> Option<Link> {
+        let uri = uri.trim();
+        if uri.starts_with("otpauth:") {
+            Otp::parse(uri).map(Link::NewOtp).map_err(|e| {
+                warn!("Unable to open {}: {}", redacted(uri), e);
+            }).ok()
+        } else if uri.starts_with("repassync://entry/") {
+            let id = uri["repassync://entry/".len()..].trim_right_matches('/');
+            Uuid::parse_str(id).map(Link::Entry).map_err(|e| {
+                warn!("Unable to open {}: {}", redacted(uri), e);
+            }).ok()
+        } else {
+            warn!("Unsupported URI scheme {}", uri.split(':').next().unwrap_or(""));
+            None
+        }
+    }
+
+}
+
+/// The URI without its query and fragment, which hold the secret of `otpauth://` URIs.
+fn redacted(uri: &str) -> &str {
+    uri.split(|c| c == '?' || c == '#').next().unwrap_or("")
+}
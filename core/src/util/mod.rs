@@ -15,3 +15,12 @@ mod password_generator;
 
 pub use self::password_strength::*;
 mod password_strength;
+
+pub use self::search::*;
+mod search;
+
+pub use self::otp::*;
+mod otp;
+
+pub use self::link::*;
+mod link;
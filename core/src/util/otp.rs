@@ -0,0 +1,367 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! One-time passwords, as described in RFC 4226 (HOTP) and RFC 6238 (TOTP),
+//! configured with the `otpauth://` URIs used by authenticator applications.
+use std::fmt;
+use std::error;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use secstr::{SecStr, SecVec};
+
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+const BASE32_ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Debug)]
+pub enum OtpError {
+    /// The `otpauth://` URI is malformed or not supported.
+    InvalidUri(String),
+    /// The number of digits or the period cannot be used to generate passwords.
+    InvalidParameters(String),
+    Crypto(ErrorStack)
+}
+
+impl fmt::Display for OtpError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OtpError::InvalidUri(ref msg) => write!(f, "invalid otpauth URI: {}", msg),
+            OtpError::InvalidParameters(ref msg) => write!(f, "invalid one-time password parameters: {}", msg),
+            OtpError::Crypto(ref e) => write!(f, "{}", e)
+        }
+    }
+
+}
+
+impl error::Error for OtpError {
+
+    fn description(&self) -> &str {
+        match *self {
+            OtpError::InvalidUri(_) => "invalid otpauth URI",
+            OtpError::InvalidParameters(_) => "invalid one-time password parameters",
+            OtpError::Crypto(ref e) => e.description()
+        }
+    }
+
+}
+
+impl From<ErrorStack> for OtpError {
+
+    fn from(e: ErrorStack) -> OtpError {
+        OtpError::Crypto(e)
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OtpKind {
+    /// Time based, with a period in seconds.
+    Totp { period: u64 },
+    /// Counter based, with the next counter to use.
+    Hotp { counter: u64 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512
+}
+
+impl OtpAlgorithm {
+
+    fn name(&self) -> &'static str {
+        match *self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512"
+        }
+    }
+
+    fn digest(&self) -> MessageDigest {
+        match *self {
+            OtpAlgorithm::Sha1 => MessageDigest::sha1(),
+            OtpAlgorithm::Sha256 => MessageDigest::sha256(),
+            OtpAlgorithm::Sha512 => MessageDigest::sha512()
+        }
+    }
+
+}
+
+/// A one-time password generator.
+#[derive(Debug, Clone)]
+pub struct Otp {
+    pub kind: OtpKind,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    pub label: String,
+    pub issuer: Option<String>,
+    secret: SecVec<u8>
+}
+
+impl Otp {
+
+    /// Creates a generator of `digits` long passwords, from 6 to 9 as
+    /// allowed by RFC 4226.
+    pub fn new(kind: OtpKind, algorithm: OtpAlgorithm, digits: u32, secret: Vec<u8>) -> Result<Otp, OtpError> {
+        let invalid = |msg: &str| OtpError::InvalidParameters(msg.to_owned());
+        if digits < 6 || digits > 9 {
+            return Err(invalid("digits must be between 6 and 9"));
+        }
+        if let OtpKind::Totp { period: 0 } = kind {
+            return Err(invalid("period must be positive"));
+        }
+        Ok(Otp {
+            kind: kind,
+            algorithm: algorithm,
+            digits: digits,
+            label: String::new(),
+            issuer: None,
+            secret: SecVec::new(secret)
+        })
+    }
+
+    /// Parses an URI such as `otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example`.
+    pub fn parse(uri: &str) -> Result<Otp, OtpError> {
+        let invalid = |msg: &str| OtpError::InvalidUri(msg.to_owned());
+        let rest = strip_prefix(uri.trim(), "otpauth://").ok_or_else(|| invalid("expected otpauth:// scheme"))?;
+        let (kind, rest) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx + 1..]),
+            None => return Err(invalid("missing type"))
+        };
+        let (label, query) = match rest.find('?') {
+            Some(idx) => (&rest[..idx], &rest[idx + 1..]),
+            None => (rest, "")
+        };
+
+        let mut secret = None;
+        let mut issuer = None;
+        let mut algorithm = OtpAlgorithm::Sha1;
+        let mut digits = 6;
+        let mut period = 30;
+        let mut counter = None;
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = match param.find('=') {
+                Some(idx) => (&param[..idx], percent_decode(&param[idx + 1..])?),
+                None => (param, String::new())
+            };
+            match key {
+                "secret" => {
+                    secret = Some(base32_decode(&value).ok_or_else(|| invalid("secret is not base32"))?);
+                },
+                "issuer" => {
+                    issuer = Some(value);
+                },
+                "algorithm" => {
+                    algorithm = match value.to_uppercase().as_str() {
+                        "SHA1" => OtpAlgorithm::Sha1,
+                        "SHA256" => OtpAlgorithm::Sha256,
+                        "SHA512" => OtpAlgorithm::Sha512,
+                        _ => return Err(invalid("unsupported algorithm"))
+                    };
+                },
+                "digits" => {
+                    digits = value.parse().map_err(|_| invalid("invalid digits"))?;
+                },
+                "period" => {
+                    period = value.parse().map_err(|_| invalid("invalid period"))?;
+                },
+                "counter" => {
+                    counter = Some(value.parse().map_err(|_| invalid("invalid counter"))?);
+                },
+                _ => {
+                    // unknown parameters such as `image` are ignored
+                }
+            }
+        }
+
+        let kind = match kind.to_lowercase().as_str() {
+            "totp" => OtpKind::Totp { period: period },
+            "hotp" => OtpKind::Hotp { counter: counter.ok_or_else(|| invalid("missing counter"))? },
+            _ => return Err(invalid("type must be totp or hotp"))
+        };
+
+        let mut otp = Otp::new(kind, algorithm, digits, secret.ok_or_else(|| invalid("missing secret"))?)?;
+        otp.label = percent_decode(label)?;
+        otp.issuer = issuer;
+        Ok(otp)
+    }
+
+    /// Formats the generator back as an `otpauth://` URI.
+    pub fn to_uri(&self) -> SecStr {
+        let mut uri = String::from("otpauth://");
+        match self.kind {
+            OtpKind::Totp { .. } => uri.push_str("totp/"),
+            OtpKind::Hotp { .. } => uri.push_str("hotp/")
+        }
+        uri.push_str(&percent_encode(&self.label));
+        uri.push_str("?secret=");
+        uri.push_str(&base32_encode(self.secret.unsecure()));
+        if let Some(ref issuer) = self.issuer {
+            uri.push_str("&issuer=");
+            uri.push_str(&percent_encode(issuer));
+        }
+        uri.push_str("&algorithm=");
+        uri.push_str(self.algorithm.name());
+        uri.push_str(&format!("&digits={}", self.digits));
+        match self.kind {
+            OtpKind::Totp { period } => uri.push_str(&format!("&period={}", period)),
+            OtpKind::Hotp { counter } => uri.push_str(&format!("&counter={}", counter))
+        }
+        SecStr::new(uri.into_bytes())
+    }
+
+    /// The HOTP value for the given counter (RFC 4226).
+    pub fn hotp(&self, counter: u64) -> Result<String, ErrorStack> {
+        let key = PKey::hmac(self.secret.unsecure())?;
+        let mut signer = Signer::new(self.algorithm.digest(), &key)?;
+        let mut message = [0_u8; 8];
+        for i in 0..8 {
+            message[i] = (counter >> (56 - 8 * i)) as u8;
+        }
+        signer.update(&message)?;
+        let hash = signer.sign_to_vec()?;
+        // dynamic truncation
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = ((hash[offset] as u32 & 0x7f) << 24)
+            | ((hash[offset + 1] as u32) << 16)
+            | ((hash[offset + 2] as u32) << 8)
+            | (hash[offset + 3] as u32);
+        let code = binary as u64 % 10_u64.pow(self.digits);
+        Ok(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// The TOTP value at the given Unix time (RFC 6238), for time based generators.
+    pub fn totp_at(&self, time: u64) -> Result<String, ErrorStack> {
+        match self.kind {
+            OtpKind::Totp { period } => self.hotp(time / period),
+            OtpKind::Hotp { counter } => self.hotp(counter)
+        }
+    }
+
+    /// The current code, and for time based generators the number of seconds it stays valid.
+    pub fn current(&self) -> Result<(String, Option<u64>), ErrorStack> {
+        match self.kind {
+            OtpKind::Totp { period } => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                Ok((self.totp_at(now)?, Some(period - now % period)))
+            },
+            OtpKind::Hotp { counter } => {
+                Ok((self.hotp(counter)?, None))
+            }
+        }
+    }
+
+    /// For counter based generators, moves to the next counter.
+    pub fn increment(&mut self) {
+        if let OtpKind::Hotp { ref mut counter } = self.kind {
+            *counter += 1;
+        }
+    }
+
+}
+
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Decodes RFC 4648 base32, ignoring case, spaces and padding.
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::new();
+    let mut buffer = 0_u64;
+    let mut bits = 0;
+    for c in text.bytes().filter(|&c| c != b'=' && c != b' ') {
+        let c = c.to_ascii_uppercase();
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u64;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if result.is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+/// Encodes RFC 4648 base32, without padding.
+fn base32_encode(data: &[u8]) -> String {
+    let mut result = String::new();
+    let mut buffer = 0_u64;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u64;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
+
+fn percent_decode(text: &str) -> Result<String, OtpError> {
+    let invalid = || OtpError::InvalidUri("invalid percent encoding".to_owned());
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = text.get(i + 1..i + 3).ok_or_else(invalid)?;
+                result.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 3;
+            },
+            b'+' => {
+                result.push(b' ');
+                i += 1;
+            },
+            c => {
+                result.push(c);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(result).map_err(|_| invalid())
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut result = String::new();
+    for &c in text.as_bytes() {
+        match c {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' | b':' | b'@' => {
+                result.push(c as char);
+            },
+            _ => {
+                result.push_str(&format!("%{:02X}", c));
+            }
+        }
+    }
+    result
+}
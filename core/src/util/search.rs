@@ -0,0 +1,120 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use model::Entry;
+
+/// The entry field a search term applies to.
+#[derive(Clone, Debug, PartialEq)]
+enum Field {
+    Any,
+    Name,
+    User,
+    Uri,
+    Tag,
+    Comment
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Term {
+    field: Field,
+    pattern: String
+}
+
+/// A search query over the vault entries.
+///
+/// The query is made of whitespace separated terms, that must all match.
+/// A term may be prefixed by the field it applies to (`name:`, `user:`, `uri:`,
+/// `tag:` or `comment:`), otherwise it matches any of them.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Query {
+    terms: Vec<Term>
+}
+
+impl Query {
+
+    pub fn parse(query: &str) -> Query {
+        let terms = query.split_whitespace().filter_map(|term| {
+            let (field, pattern) = match term.find(':') {
+                Some(idx) => {
+                    let field = match &term[..idx] {
+                        "name" => Some(Field::Name),
+                        "user" => Some(Field::User),
+                        "uri" => Some(Field::Uri),
+                        "tag" => Some(Field::Tag),
+                        "comment" => Some(Field::Comment),
+                        _ => None
+                    };
+                    match field {
+                        Some(field) => (field, &term[idx + 1..]),
+                        // not a known prefix, e.g. a URI scheme
+                        None => (Field::Any, term)
+                    }
+                },
+                None => (Field::Any, term)
+            };
+            if pattern.is_empty() {
+                None
+            } else {
+                Some(Term { field: field, pattern: pattern.to_lowercase() })
+            }
+        }).collect();
+        Query { terms: terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.terms.iter().all(|term| term.matches(entry))
+    }
+
+}
+
+impl Term {
+
+    fn matches(&self, entry: &Entry) -> bool {
+        let pattern = self.pattern.as_str();
+        let optional = |field: &Option<String>| {
+            field.as_ref().map(|value| fuzzy_match(pattern, value)).unwrap_or(false)
+        };
+        let tags = || entry.tags.iter().any(|tag| fuzzy_match(pattern, tag));
+        match self.field {
+            Field::Any => {
+                fuzzy_match(pattern, &entry.name) || optional(&entry.user) || optional(&entry.uri)
+                    || tags() || optional(&entry.comment)
+            },
+            Field::Name => fuzzy_match(pattern, &entry.name),
+            Field::User => optional(&entry.user),
+            Field::Uri => optional(&entry.uri),
+            Field::Tag => tags(),
+            Field::Comment => optional(&entry.comment)
+        }
+    }
+
+}
+
+/// Whether all the characters of the (lowercase) pattern appear in order in the text,
+/// regardless of case.
+fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut pattern = pattern.chars().peekable();
+    for c in text.chars().flat_map(|c| c.to_lowercase()) {
+        match pattern.peek() {
+            Some(&p) if p == c => {
+                pattern.next();
+            },
+            Some(_) => (),
+            None => return true
+        }
+    }
+    pattern.peek().is_none()
+}
@@ -0,0 +1,65 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate tempdir;
+
+use std::fs;
+use std::path::Path;
+
+use tempdir::TempDir;
+
+use repassync_core::io::atomic::write_atomically;
+
+fn files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn atomic_write_replaces_content() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("file");
+    write_atomically(&path, b"first").unwrap();
+    write_atomically(&path, b"second").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"second");
+    // no temporary file is left behind
+    assert_eq!(files(dir.path()), vec!["file"]);
+}
+
+#[test]
+fn failed_rename_keeps_previous_content() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("file");
+    write_atomically(&path, b"previous").unwrap();
+    // renaming a file over a non-empty directory fails, even for root
+    let target = dir.path().join("directory");
+    fs::create_dir(&target).unwrap();
+    fs::write(target.join("content"), b"content").unwrap();
+
+    assert!(write_atomically(&target, b"new").is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"previous");
+    assert_eq!(fs::read(target.join("content")).unwrap(), b"content");
+    // the temporary file is removed
+    assert_eq!(files(dir.path()), vec!["directory", "file"]);
+}
+
+#[test]
+fn write_in_missing_directory_fails() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("missing").join("file");
+    assert!(write_atomically(&path, b"content").is_err());
+    assert!(files(dir.path()).is_empty());
+}
@@ -0,0 +1,89 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate uuid;
+
+use uuid::Uuid;
+
+use repassync_core::changes::{ChangeLog, OperationKind};
+
+fn device_log(device: Uuid) -> ChangeLog {
+    let mut log = ChangeLog::default();
+    log.set_device(device);
+    log
+}
+
+#[test]
+fn changes_are_stamped_with_a_lamport_clock() {
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    let entry = Uuid::new_v4();
+    let mut on_a = device_log(a);
+    let mut on_b = device_log(b);
+    // both devices wrote the vault once, and know of each other
+    on_a.acknowledge();
+    on_b.acknowledge();
+    on_a = ChangeLog::merge(&on_a, &on_b);
+    on_b = ChangeLog::merge(&on_b, &on_a);
+
+    on_a.record(&entry, OperationKind::Added);
+    on_a.record(&entry, OperationKind::Modified);
+    on_b.record(&entry, OperationKind::Modified);
+    assert_eq!(on_a.clock(), 2);
+    assert_eq!(on_b.clock(), 1);
+
+    let mut merged = ChangeLog::merge(&on_b, &on_a);
+    let stamps: Vec<_> = merged.operations().map(|op| (op.device, op.clock)).collect();
+    assert_eq!(stamps.len(), 3);
+    assert_eq!(stamps[2], (a, 2));
+
+    merged.record(&entry, OperationKind::Trashed);
+    assert_eq!(merged.operations().last().map(|op| (op.device, op.clock)), Some((b, 3)));
+}
+
+#[test]
+fn tombstones_are_dropped_once_every_device_knows() {
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    let entry = Uuid::new_v4();
+    let mut on_a = device_log(a);
+    let mut on_b = device_log(b);
+    on_b.acknowledge();
+    on_a = ChangeLog::merge(&on_a, &on_b);
+
+    on_a.record(&entry, OperationKind::Purged);
+    // kept until the other device sees it
+    assert!(on_a.tombstone(&entry).is_some());
+
+    on_b = ChangeLog::merge(&on_b, &on_a);
+    assert!(on_b.tombstone(&entry).is_some());
+    on_b.acknowledge();
+    assert!(on_b.tombstone(&entry).is_none());
+    assert_eq!(on_b.operations().count(), 0);
+
+    on_a = ChangeLog::merge(&on_a, &on_b);
+    assert!(on_a.tombstone(&entry).is_none());
+}
+
+#[test]
+fn new_changes_bring_entries_back() {
+    let entry = Uuid::new_v4();
+    let mut log = device_log(Uuid::new_v4());
+    // another device still has to see the deletion
+    let mut other = device_log(Uuid::new_v4());
+    other.acknowledge();
+    log = ChangeLog::merge(&log, &other);
+
+    log.record(&entry, OperationKind::Purged);
+    assert!(log.tombstone(&entry).is_some());
+    log.record(&entry, OperationKind::Modified);
+    assert!(log.tombstone(&entry).is_none());
+}
@@ -0,0 +1,56 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Fixtures shared by the integration tests, each one uses a part of them.
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use repassync_core::model::Vault;
+use repassync_core::io::file::{DecryptedVaultFile, EncryptedVaultFile};
+use repassync_core::io::kdf::Kdf;
+
+/// The passphrase of the test vaults.
+pub const PASSPHRASE: &str = "passphrase";
+
+pub fn secret(s: &str) -> SecStr {
+    SecStr::new(s.as_bytes().to_vec())
+}
+
+/// Cheap parameters, the default ones are far too slow for tests.
+pub fn test_kdf() -> Kdf {
+    Kdf::argon2id(64, 1, 1)
+}
+
+/// The vault encrypted with `passphrase`.
+pub fn encrypt(vault: &Vault, passphrase: &str) -> EncryptedVaultFile {
+    DecryptedVaultFile::from_vault(vault).unwrap()
+        .encrypt(secret(passphrase), &test_kdf()).unwrap()
+}
+
+/// Writes the vault file, encrypted with `passphrase`.
+pub fn write(vault: &Vault, passphrase: &str, path: &Path) {
+    encrypt(vault, passphrase).to_file(path).unwrap();
+}
+
+/// Opens the content of a vault file encrypted with `PASSPHRASE`, as `device`.
+pub fn read(content: &[u8], device: Uuid) -> Vault {
+    let mut vault = EncryptedVaultFile::from_bytes(content).unwrap()
+        .decrypt(secret(PASSPHRASE)).unwrap()
+        .to_vault().unwrap();
+    vault.set_device(device);
+    vault
+}
@@ -0,0 +1,86 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate secstr;
+extern crate tempdir;
+extern crate uuid;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use tempdir::TempDir;
+
+use uuid::Uuid;
+
+use repassync_core::model::Vault;
+use repassync_core::io::seal::VaultKey;
+use repassync_core::sync::folder::{conflict_copies, is_conflict_copy, merge_conflict_copies};
+
+use common::{PASSPHRASE, read, secret, test_kdf, write};
+
+#[test]
+fn conflict_copies_are_recognized() {
+    let vault = Path::new("/sync/repassync.vault");
+    assert!(is_conflict_copy(vault, Path::new("/sync/repassync.sync-conflict-20180102-030405-ABCDEFG.vault")));
+    assert!(is_conflict_copy(vault, Path::new("/sync/repassync (conflicted copy 2018-01-02 030405).vault")));
+    assert!(is_conflict_copy(vault, Path::new("/sync/repassync (Alice's conflicted copy 2018-01-02).vault")));
+    assert!(is_conflict_copy(vault, Path::new("/sync/repassync_conflict-20180102-030405.vault")));
+
+    assert!(!is_conflict_copy(vault, vault));
+    assert!(!is_conflict_copy(vault, Path::new("/sync/repassync.vault.tmp")));
+    assert!(!is_conflict_copy(vault, Path::new("/sync/other.sync-conflict-20180102-030405-ABCDEFG.vault")));
+    assert!(!is_conflict_copy(vault, Path::new("/elsewhere/repassync.sync-conflict-20180102-030405-ABCDEFG.vault")));
+}
+
+#[test]
+fn conflict_copies_are_merged() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("repassync.vault");
+    let copy = dir.path().join("repassync.sync-conflict-20180102-030405-ABCDEFG.vault");
+    let foreign = dir.path().join("repassync (conflicted copy 2018-01-02 030405).vault");
+    let (laptop, phone) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let mut vault = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    vault.set_device(laptop);
+    let mail = vault.add_entry("mail".to_owned(), secret("hunter2")).unwrap().id().clone();
+    let bank = vault.add_entry("bank".to_owned(), secret("correct horse")).unwrap().id().clone();
+    write(&vault, PASSPHRASE, &path);
+
+    // both devices change the shared file before seeing each other's version
+    let mut on_phone = read(&fs::read(&path).unwrap(), phone);
+    on_phone.update_entry(&mail, |entry| entry.user = Some("alice@example.com".to_owned()));
+    write(&on_phone, PASSPHRASE, &copy);
+    let mut on_laptop = read(&fs::read(&path).unwrap(), laptop);
+    on_laptop.update_entry(&bank, |entry| entry.user = Some("alice".to_owned()));
+    write(&on_laptop, PASSPHRASE, &path);
+    // a vault that cannot be opened is left alone
+    write(&Vault::new("bob".to_owned(), VaultKey::generate().unwrap()), "other", &foreign);
+
+    let merged = merge_conflict_copies(&path, &secret(PASSPHRASE), &test_kdf(), laptop).unwrap().unwrap();
+    assert_eq!(merged.conflicts, 0);
+    assert_eq!(merged.merged, vec![copy.clone()]);
+    assert_eq!(merged.skipped, vec![foreign.clone()]);
+    // nothing is written yet
+    assert_eq!(conflict_copies(&path).unwrap().len(), 2);
+
+    merged.write(&path).unwrap();
+    assert_eq!(conflict_copies(&path).unwrap(), vec![foreign]);
+    let restored = read(&fs::read(&path).unwrap(), laptop);
+    assert_eq!(restored.get_entry(&mail).unwrap().user, Some("alice@example.com".to_owned()));
+    assert_eq!(restored.get_entry(&bank).unwrap().user, Some("alice".to_owned()));
+    assert!(fs::metadata(&copy).is_err());
+
+    assert!(merge_conflict_copies(&path, &secret(PASSPHRASE), &test_kdf(), laptop).unwrap().is_none());
+}
@@ -0,0 +1,141 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate secstr;
+extern crate tempdir;
+extern crate uuid;
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use secstr::SecStr;
+
+use tempdir::TempDir;
+
+use uuid::Uuid;
+
+use repassync_core::model::Vault;
+use repassync_core::io::seal::VaultKey;
+use repassync_core::sync::git::{synchronize, GitRepository, Pulled};
+
+use common::{PASSPHRASE, read, secret, test_kdf, write};
+
+/// Synchronizes, checking out what was pulled, and returns whether something was.
+fn pull(repository: &GitRepository, passphrase: Option<&SecStr>, device: Uuid) -> bool {
+    match synchronize(repository, passphrase, &test_kdf(), device).unwrap() {
+        Pulled::UpToDate => false,
+        Pulled::Pulled { commit, content, .. } => {
+            repository.advance(&commit, &content).unwrap();
+            true
+        },
+        Pulled::Diverged => panic!("diverged")
+    }
+}
+
+#[test]
+fn saves_are_committed() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("repassync.vault");
+    let repository = GitRepository::open(&path).unwrap();
+    assert!(!repository.commit("Save vault").unwrap());
+    assert!(repository.history().unwrap().is_empty());
+
+    let mut vault = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    let mail = vault.add_entry("mail".to_owned(), secret("hunter2")).unwrap().id().clone();
+    write(&vault, PASSPHRASE, &path);
+    let first = fs::read(&path).unwrap();
+    assert!(repository.commit("Save vault").unwrap());
+    assert!(!repository.commit("Save vault").unwrap());
+
+    vault.update_entry(&mail, |entry| entry.user = Some("alice".to_owned()));
+    write(&vault, PASSPHRASE, &path);
+    assert!(repository.commit("Edit mail").unwrap());
+
+    let history = repository.history().unwrap();
+    assert_eq!(history.iter().map(|commit| commit.message.as_str()).collect::<Vec<_>>(), vec!["Edit mail", "Save vault"]);
+    assert_eq!(repository.content_at(&history[1].id).unwrap(), first);
+
+    // an entry is brought back as it was
+    let past = read(&first, Uuid::new_v4());
+    assert!(vault.restore_past_entry(&past, &mail).unwrap());
+    assert_eq!(vault.get_entry(&mail).unwrap().user, None);
+}
+
+#[test]
+fn remote_urls_are_not_taken_for_options() {
+    let dir = TempDir::new("repassync").unwrap();
+    let repository = GitRepository::open(&dir.path().join("repassync.vault")).unwrap();
+    let remote_url = |dir: &TempDir| {
+        let output = Command::new("git").args(&["remote", "get-url", "origin"]).current_dir(dir.path()).output().unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    };
+    repository.set_remote("--upload-pack=touch pwned").unwrap();
+    assert_eq!(remote_url(&dir), "--upload-pack=touch pwned");
+    repository.set_remote("-v").unwrap();
+    assert_eq!(remote_url(&dir), "-v");
+}
+
+#[test]
+fn divergent_versions_are_merged_through_the_remote() {
+    let remote = TempDir::new("repassync-remote").unwrap();
+    assert!(Command::new("git").args(&["init", "-q", "--bare"]).current_dir(remote.path()).status().unwrap().success());
+    let remote_url = remote.path().to_str().unwrap();
+    let (laptop_dir, phone_dir) = (TempDir::new("repassync-laptop").unwrap(), TempDir::new("repassync-phone").unwrap());
+    let (laptop_path, phone_path) = (laptop_dir.path().join("repassync.vault"), phone_dir.path().join("repassync.vault"));
+    let (laptop, phone) = (Uuid::new_v4(), Uuid::new_v4());
+    let passphrase = secret(PASSPHRASE);
+
+    let mut vault = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    vault.set_device(laptop);
+    let mail = vault.add_entry("mail".to_owned(), secret("hunter2")).unwrap().id().clone();
+    let bank = vault.add_entry("bank".to_owned(), secret("correct horse")).unwrap().id().clone();
+    write(&vault, PASSPHRASE, &laptop_path);
+    let on_laptop = GitRepository::open(&laptop_path).unwrap();
+    on_laptop.set_remote(remote_url).unwrap();
+    assert!(!pull(&on_laptop, None, laptop));
+
+    // the phone starts from the pushed history
+    let on_phone = GitRepository::open(&phone_path).unwrap();
+    on_phone.set_remote(remote_url).unwrap();
+    assert!(pull(&on_phone, None, phone));
+    assert_eq!(fs::read(&phone_path).unwrap(), fs::read(&laptop_path).unwrap());
+
+    // both devices change the vault
+    let mut vault = read(&fs::read(&laptop_path).unwrap(), laptop);
+    vault.update_entry(&bank, |entry| entry.user = Some("alice".to_owned()));
+    write(&vault, PASSPHRASE, &laptop_path);
+    assert!(!pull(&on_laptop, Some(&passphrase), laptop));
+    let mut vault = read(&fs::read(&phone_path).unwrap(), phone);
+    vault.update_entry(&mail, |entry| entry.user = Some("alice@example.com".to_owned()));
+    write(&vault, PASSPHRASE, &phone_path);
+
+    match synchronize(&on_phone, None, &test_kdf(), phone).unwrap() {
+        Pulled::Diverged => (),
+        pulled => panic!("unexpected result {:?}", pulled)
+    }
+    match synchronize(&on_phone, Some(&passphrase), &test_kdf(), phone).unwrap() {
+        Pulled::Pulled { commit, content, merged: Some((_, 0)) } => on_phone.advance(&commit, &content).unwrap(),
+        pulled => panic!("unexpected result {:?}", pulled)
+    }
+    // the merge is pushed, and fast-forwards the laptop
+    assert!(!pull(&on_phone, Some(&passphrase), phone));
+    assert!(pull(&on_laptop, Some(&passphrase), laptop));
+
+    let merged = read(&fs::read(&laptop_path).unwrap(), laptop);
+    assert_eq!(merged.get_entry(&mail).unwrap().user, Some("alice@example.com".to_owned()));
+    assert_eq!(merged.get_entry(&bank).unwrap().user, Some("alice".to_owned()));
+    assert_eq!(on_laptop.history().unwrap().len(), 4);
+    assert!(!on_laptop.commit("Save vault").unwrap());
+}
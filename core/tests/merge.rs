@@ -0,0 +1,162 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate secstr;
+extern crate uuid;
+
+mod common;
+
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use repassync_core::model::{Vault, Resolution};
+use repassync_core::io::file::DecryptedVaultFile;
+use repassync_core::io::seal::VaultKey;
+
+use common::{PASSPHRASE, encrypt, secret};
+
+/// The vault as read back from its file, like the copies merged when synchronizing.
+fn file(vault: &Vault) -> DecryptedVaultFile {
+    encrypt(vault, PASSPHRASE).decrypt(secret(PASSPHRASE)).unwrap()
+}
+
+/// A base vault with two entries, and their identifiers.
+fn base() -> (Vault, Uuid, Uuid) {
+    let mut vault = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    let mail = vault.add_entry("mail".to_owned(), secret("hunter2")).unwrap().id().clone();
+    let bank = vault.add_entry("bank".to_owned(), secret("correct horse")).unwrap().id().clone();
+    (file(&vault).to_vault().unwrap(), mail, bank)
+}
+
+fn merge(base: &Vault, local: &Vault, remote: &Vault) -> (Vault, usize) {
+    let (merged, conflicts) = DecryptedVaultFile::merge(Some(&file(base)), &file(local), &file(remote)).unwrap();
+    (merged.to_vault().unwrap(), conflicts)
+}
+
+#[test]
+fn independent_changes_are_merged() {
+    let (base, mail, bank) = base();
+    let mut local = base.clone();
+    local.update_entry(&mail, |entry| entry.user = Some("alice@example.com".to_owned()));
+    let mut remote = base.clone();
+    remote.trash_entry(&bank);
+    let added = remote.add_entry("forum".to_owned(), secret("swordfish")).unwrap().id().clone();
+
+    let (merged, conflicts) = merge(&base, &local, &remote);
+
+    assert_eq!(conflicts, 0);
+    assert_eq!(merged.conflicts().count(), 0);
+    assert_eq!(merged.get_entry(&mail).unwrap().user, Some("alice@example.com".to_owned()));
+    assert!(merged.get_entry(&bank).is_none());
+    assert!(merged.get_trashed_entry(&bank).is_some());
+    let forum = merged.get_entry(&added).unwrap();
+    assert_eq!(merged.reveal_password(forum).unwrap(), secret("swordfish"));
+}
+
+#[test]
+fn concurrent_changes_are_conflicts() {
+    let (base, mail, _) = base();
+    let mut local = base.clone();
+    local.update_entry(&mail, |entry| entry.user = Some("local".to_owned()));
+    let mut remote = base.clone();
+    remote.update_entry(&mail, |entry| entry.user = Some("remote".to_owned()));
+
+    let (mut merged, conflicts) = merge(&base, &local, &remote);
+
+    assert_eq!(conflicts, 1);
+    let kept = merged.get_entry(&mail).unwrap().user.clone().unwrap();
+    let other = merged.conflicts().next().unwrap().other.clone().unwrap().user.unwrap();
+    let versions: HashSet<_> = vec![kept, other].into_iter().collect();
+    assert_eq!(versions, vec!["local".to_owned(), "remote".to_owned()].into_iter().collect());
+
+    assert!(merged.resolve_conflict(&mail, Resolution::Both));
+    assert_eq!(merged.conflicts().count(), 0);
+    let users: HashSet<_> = merged.into_iter().filter(|entry| entry.name == "mail").map(|entry| entry.user.clone().unwrap()).collect();
+    assert_eq!(users, versions);
+}
+
+#[test]
+fn modification_wins_over_deletion() {
+    let (base, mail, _) = base();
+    let mut local = base.clone();
+    local.trash_entry(&mail);
+    local.purge_entry(&mail);
+    let mut remote = base.clone();
+    remote.update_entry(&mail, |entry| entry.user = Some("remote".to_owned()));
+
+    let (mut merged, conflicts) = merge(&base, &local, &remote);
+
+    assert_eq!(conflicts, 1);
+    assert_eq!(merged.get_entry(&mail).unwrap().user, Some("remote".to_owned()));
+    assert!(merged.conflicts().next().unwrap().other.is_none());
+
+    // confirms the deletion
+    assert!(merged.resolve_conflict(&mail, Resolution::Other));
+    assert!(merged.get_entry(&mail).is_none());
+    assert!(!merged.resolve_conflict(&mail, Resolution::Other));
+}
+
+#[test]
+fn conflicts_are_kept_in_the_file() {
+    let (base, mail, _) = base();
+    let mut local = base.clone();
+    local.update_entry(&mail, |entry| entry.user = Some("local".to_owned()));
+    let mut remote = base.clone();
+    remote.trash_entry(&mail);
+
+    let (merged, _) = merge(&base, &local, &remote);
+    let mut restored = file(&merged).to_vault().unwrap();
+
+    let conflict = restored.conflicts().next().unwrap().clone();
+    assert_eq!(conflict.id, mail);
+    assert!(conflict.deleted.is_some());
+    assert!(restored.resolve_conflict(&mail, Resolution::Other));
+    assert!(restored.get_entry(&mail).is_none());
+    assert!(restored.get_trashed_entry(&mail).is_some());
+}
+
+#[test]
+fn merging_without_base_keeps_everything() {
+    let (base, _, _) = base();
+    let mut local = base.clone();
+    local.add_entry("forum".to_owned(), secret("swordfish")).unwrap();
+    // created separately, with another key
+    let mut remote = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    let shop = remote.add_entry("shop".to_owned(), secret("letmein")).unwrap().id().clone();
+
+    let (merged, conflicts) = DecryptedVaultFile::merge(None, &file(&local), &file(&remote)).unwrap();
+    let merged = merged.to_vault().unwrap();
+
+    assert_eq!(conflicts, 0);
+    assert_eq!(merged.into_iter().count(), 4);
+    let shop = merged.get_entry(&shop).unwrap();
+    assert_eq!(merged.reveal_password(shop).unwrap(), secret("letmein"));
+}
+
+#[test]
+fn purged_entries_are_not_brought_back() {
+    let (base, mail, _) = base();
+    let mut local = base.clone();
+    local.trash_entry(&mail);
+    local.purge_entry(&mail);
+
+    // without a base, only the tombstone tells the entry was deleted
+    let (merged, conflicts) = DecryptedVaultFile::merge(None, &file(&local), &file(&base)).unwrap();
+    let merged = merged.to_vault().unwrap();
+
+    assert_eq!(conflicts, 0);
+    assert!(merged.get_entry(&mail).is_none());
+    assert!(merged.get_trashed_entry(&mail).is_none());
+    assert!(merged.changes().tombstone(&mail).is_some());
+}
@@ -0,0 +1,110 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+
+use repassync_core::util::{Otp, OtpKind, OtpAlgorithm};
+
+const RFC4226_SECRET: &'static [u8] = b"12345678901234567890";
+
+#[test]
+fn hotp_rfc4226_vectors() {
+    let otp = Otp::new(OtpKind::Hotp { counter: 0 }, OtpAlgorithm::Sha1, 6, RFC4226_SECRET.to_vec()).unwrap();
+    let expected = ["755224", "287082", "359152", "969429", "338314",
+                    "254676", "287922", "162583", "399871", "520489"];
+    for (counter, code) in expected.iter().enumerate() {
+        assert_eq!(otp.hotp(counter as u64).unwrap(), *code);
+    }
+}
+
+#[test]
+fn hotp_counter_follows_rfc4226_vectors() {
+    // the base32 secret is 12345678901234567890
+    let mut otp = Otp::parse("otpauth://hotp/label?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=0").unwrap();
+    for code in ["755224", "287082", "359152", "969429", "338314"].iter() {
+        assert_eq!(otp.current().unwrap(), (code.to_string(), None));
+        otp.increment();
+    }
+    assert_eq!(otp.kind, OtpKind::Hotp { counter: 5 });
+}
+
+#[test]
+fn totp_rfc6238_vectors() {
+    let sha1 = Otp::new(OtpKind::Totp { period: 30 }, OtpAlgorithm::Sha1, 8,
+                        b"12345678901234567890".to_vec()).unwrap();
+    let sha256 = Otp::new(OtpKind::Totp { period: 30 }, OtpAlgorithm::Sha256, 8,
+                          b"12345678901234567890123456789012".to_vec()).unwrap();
+    let sha512 = Otp::new(OtpKind::Totp { period: 30 }, OtpAlgorithm::Sha512, 8,
+                          b"1234567890123456789012345678901234567890123456789012345678901234".to_vec()).unwrap();
+    let expected = [
+        (59, "94287082", "46119246", "90693936"),
+        (1111111109, "07081804", "68084774", "25091201"),
+        (1111111111, "14050471", "67062674", "99943326"),
+        (1234567890, "89005924", "91819424", "93441116"),
+        (2000000000, "69279037", "90698825", "38618901"),
+        (20000000000, "65353130", "77737706", "47863826")
+    ];
+    for &(time, code1, code256, code512) in expected.iter() {
+        assert_eq!(sha1.totp_at(time).unwrap(), code1);
+        assert_eq!(sha256.totp_at(time).unwrap(), code256);
+        assert_eq!(sha512.totp_at(time).unwrap(), code512);
+    }
+}
+
+#[test]
+fn parse_otpauth_uri() {
+    let otp = Otp::parse("otpauth://totp/Example:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Example&digits=8&period=60&algorithm=SHA256").unwrap();
+    assert_eq!(otp.kind, OtpKind::Totp { period: 60 });
+    assert_eq!(otp.algorithm, OtpAlgorithm::Sha256);
+    assert_eq!(otp.digits, 8);
+    assert_eq!(otp.label, "Example:alice@example.com");
+    assert_eq!(otp.issuer, Some("Example".to_owned()));
+
+    let defaults = Otp::parse("otpauth://totp/label?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+    assert_eq!(defaults.kind, OtpKind::Totp { period: 30 });
+    assert_eq!(defaults.algorithm, OtpAlgorithm::Sha1);
+    assert_eq!(defaults.digits, 6);
+    // the base32 secret is 12345678901234567890
+    assert_eq!(defaults.totp_at(59).unwrap(), "287082");
+}
+
+#[test]
+fn invalid_otpauth_uris() {
+    assert!(Otp::parse("https://example.com").is_err());
+    assert!(Otp::parse("otpauth://totp/label").is_err());
+    assert!(Otp::parse("otpauth://totp/label?secret=not*base32").is_err());
+    assert!(Otp::parse("otpauth://hotp/label?secret=GEZDGNBV").is_err());
+    assert!(Otp::parse("otpauth://totp/label?secret=GEZDGNBV&digits=4").is_err());
+    assert!(Otp::parse("otpauth://totp/label?secret=GEZDGNBV&period=0").is_err());
+}
+
+#[test]
+fn invalid_parameters() {
+    let secret = RFC4226_SECRET.to_vec();
+    assert!(Otp::new(OtpKind::Hotp { counter: 0 }, OtpAlgorithm::Sha1, 5, secret.clone()).is_err());
+    assert!(Otp::new(OtpKind::Hotp { counter: 0 }, OtpAlgorithm::Sha1, 10, secret.clone()).is_err());
+    assert!(Otp::new(OtpKind::Totp { period: 30 }, OtpAlgorithm::Sha1, 20, secret.clone()).is_err());
+    assert!(Otp::new(OtpKind::Totp { period: 0 }, OtpAlgorithm::Sha1, 6, secret.clone()).is_err());
+    assert!(Otp::new(OtpKind::Totp { period: 30 }, OtpAlgorithm::Sha1, 9, secret).is_ok());
+}
+
+#[test]
+fn otpauth_uri_round_trip() {
+    let mut otp = Otp::parse("otpauth://hotp/Label%20with%20spaces?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=3&issuer=ACME%20Co").unwrap();
+    otp.increment();
+    let uri = String::from_utf8(otp.to_uri().unsecure().to_vec()).unwrap();
+    let parsed = Otp::parse(&uri).unwrap();
+    assert_eq!(parsed.kind, OtpKind::Hotp { counter: 4 });
+    assert_eq!(parsed.label, "Label with spaces");
+    assert_eq!(parsed.issuer, Some("ACME Co".to_owned()));
+    assert_eq!(parsed.hotp(4).unwrap(), otp.hotp(4).unwrap());
+}
@@ -0,0 +1,55 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Goes through the user directories, so it sets the environment of the
+//! whole test binary and must stay alone in its file.
+extern crate repassync_core;
+extern crate secstr;
+extern crate tempdir;
+extern crate uuid;
+
+mod common;
+
+use std::env;
+
+use tempdir::TempDir;
+
+use repassync_core::model::Vault;
+use repassync_core::io::file::{save_vault, vault_path, EncryptedVaultFile};
+use repassync_core::io::seal::VaultKey;
+
+use common::{PASSPHRASE, secret, test_kdf};
+
+#[test]
+fn saved_vault_is_loaded_back() {
+    let home = TempDir::new("repassync-home").unwrap();
+    env::set_var("XDG_DATA_HOME", home.path().join("data"));
+    env::set_var("XDG_CONFIG_HOME", home.path().join("config"));
+
+    let mut vault = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    let id = vault.add_entry("gmail".to_owned(), secret("hunter2")).unwrap().id().clone();
+    vault.update_entry(&id, |entry| entry.user = Some("alice@gmail.com".to_owned()));
+    save_vault(&vault, secret(PASSPHRASE), &test_kdf()).unwrap();
+
+    let path = vault_path().unwrap();
+    assert_eq!(path, home.path().join("data/repassync/repassync.vault"));
+    let restored = EncryptedVaultFile::from_file(&path).unwrap()
+        .decrypt(secret(PASSPHRASE)).unwrap()
+        .to_vault().unwrap();
+
+    assert_eq!(restored.owner(), "alice");
+    let entry = restored.get_entry(&id).expect("entry is restored");
+    assert_eq!(entry.name, "gmail");
+    assert_eq!(entry.user, Some("alice@gmail.com".to_owned()));
+    assert_eq!(restored.reveal_password(entry).unwrap(), secret("hunter2"));
+}
@@ -0,0 +1,127 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate secstr;
+extern crate uuid;
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use repassync_core::model::Vault;
+use repassync_core::io::seal::VaultKey;
+use repassync_core::util::{PasswordGenerator, Query, Quality, check_password_quality, Link};
+
+#[test]
+fn generated_passwords_use_requested_characters() {
+    let password = PasswordGenerator::new(32, false, false, true, false).generate();
+    assert_eq!(password.chars().count(), 32);
+    assert!(password.chars().all(|c| c.is_ascii_digit()));
+
+    let password = PasswordGenerator::new(64, true, true, false, false).generate();
+    assert!(password.chars().all(|c| c.is_ascii_alphabetic()));
+}
+
+#[test]
+fn password_quality() {
+    match check_password_quality("") {
+        Quality::Weak => (),
+        quality => panic!("empty password is {:?}", quality)
+    }
+    match check_password_quality("aaa") {
+        Quality::Weak | Quality::Low => (),
+        quality => panic!("short password is {:?}", quality)
+    }
+}
+
+fn github() -> Vault {
+    let mut vault = Vault::new("".to_owned(), VaultKey::generate().unwrap());
+    let id = vault.add_entry("GitHub".to_owned(), SecStr::new(b"password".to_vec())).unwrap().id().clone();
+    vault.update_entry(&id, |entry| {
+        entry.user = Some("alice".to_owned());
+        entry.uri = Some("https://github.com".to_owned());
+        entry.comment = Some("Recovery codes printed".to_owned());
+        entry.tags.insert("work".to_owned());
+    });
+    vault
+}
+
+fn matches(vault: &Vault, query: &str) -> bool {
+    let entry = vault.into_iter().next().unwrap();
+    Query::parse(query).matches(entry)
+}
+
+#[test]
+fn empty_query_matches_everything() {
+    let vault = github();
+    assert!(Query::parse("").is_empty());
+    assert!(Query::parse("  tag: ").is_empty());
+    assert!(matches(&vault, ""));
+}
+
+#[test]
+fn prefixed_terms_only_match_their_field() {
+    let vault = github();
+    assert!(matches(&vault, "name:github"));
+    assert!(matches(&vault, "user:ali"));
+    assert!(matches(&vault, "uri:github.com"));
+    assert!(matches(&vault, "tag:work"));
+    assert!(matches(&vault, "comment:recovery"));
+    // the value is in another field
+    assert!(!matches(&vault, "name:alice"));
+    assert!(!matches(&vault, "user:github"));
+    assert!(!matches(&vault, "tag:alice"));
+    // unknown prefixes are part of the pattern, e.g. URI schemes
+    assert!(matches(&vault, "https://github"));
+    assert!(!matches(&vault, "ftp://github"));
+}
+
+#[test]
+fn search_ignores_case() {
+    let vault = github();
+    assert!(matches(&vault, "github"));
+    assert!(matches(&vault, "GITHUB"));
+    assert!(matches(&vault, "tag:WORK"));
+    assert!(matches(&vault, "user:Alice"));
+    assert!(matches(&vault, "comment:RECOVERY"));
+}
+
+#[test]
+fn search_is_fuzzy() {
+    let vault = github();
+    assert!(matches(&vault, "ghb"));
+    assert!(matches(&vault, "name:gthb"));
+    assert!(matches(&vault, "user:ace"));
+    // characters must appear in order
+    assert!(!matches(&vault, "name:hubgit"));
+    assert!(!matches(&vault, "gitlab"));
+    // all the terms must match
+    assert!(matches(&vault, "ghb user:ali tag:work"));
+    assert!(!matches(&vault, "github tag:home"));
+}
+
+#[test]
+fn parse_links() {
+    match Link::parse("otpauth://totp/label?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ") {
+        Some(Link::NewOtp(otp)) => assert_eq!(otp.label, "label"),
+        link => panic!("unexpected {:?}", link)
+    }
+    let id = Uuid::new_v4();
+    match Link::parse(&format!("repassync://entry/{}/", id)) {
+        Some(Link::Entry(entry)) => assert_eq!(entry, id),
+        link => panic!("unexpected {:?}", link)
+    }
+    assert!(Link::parse("repassync://entry/not-an-id").is_none());
+    assert!(Link::parse("otpauth://totp/label").is_none());
+    assert!(Link::parse("https://example.com").is_none());
+}
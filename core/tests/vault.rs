@@ -0,0 +1,235 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate secstr;
+extern crate serde_cbor;
+extern crate chrono;
+extern crate tempdir;
+extern crate uuid;
+
+mod common;
+
+use std::fs;
+
+use chrono::Duration;
+
+use tempdir::TempDir;
+
+use repassync_core::model::Vault;
+use repassync_core::io::error::VaultError;
+use repassync_core::io::file::{DecryptedVaultFile, EncryptedVaultFile};
+use repassync_core::io::kdf::Kdf;
+use repassync_core::io::seal::VaultKey;
+
+use common::{PASSPHRASE, encrypt, secret, test_kdf, write};
+
+fn sample_vault() -> Vault {
+    let mut vault = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    let id = vault.add_entry("gmail".to_owned(), secret("hunter2")).unwrap().id().clone();
+    vault.update_entry(&id, |entry| {
+        entry.user = Some("alice@gmail.com".to_owned());
+        entry.uri = Some("https://mail.google.com".to_owned());
+        entry.comment = Some("personal".to_owned());
+        entry.tags.insert("mail".to_owned());
+    });
+    vault.set_otp(&id, Some(secret("otpauth://totp/gmail?secret=JBSWY3DPEHPK3PXP"))).unwrap();
+    // entries may share a name
+    vault.add_entry("gmail".to_owned(), secret("correct horse")).unwrap();
+    let trashed = vault.add_entry("old".to_owned(), secret("old")).unwrap().id().clone();
+    vault.trash_entry(&trashed);
+    vault
+}
+
+fn assert_same_entries(restored: &Vault, vault: &Vault) {
+    assert_eq!(restored.owner(), vault.owner());
+    assert_eq!(restored.into_iter().count(), vault.into_iter().count());
+    assert_eq!(restored.trash().count(), vault.trash().count());
+    for entry in vault {
+        let other = restored.get_entry(entry.id()).expect("entry is restored");
+        assert_eq!(other.name, entry.name);
+        assert_eq!(restored.reveal_password(other).unwrap(), vault.reveal_password(entry).unwrap());
+        assert_eq!(restored.reveal_otp(other).unwrap(), vault.reveal_otp(entry).unwrap());
+        assert_eq!(other.user, entry.user);
+        assert_eq!(other.uri, entry.uri);
+        assert_eq!(other.comment, entry.comment);
+        assert_eq!(other.tags, entry.tags);
+        // dates are stored with a second precision
+        assert_eq!(other.created().timestamp(), entry.created().timestamp());
+        assert_eq!(other.last_modified().timestamp(), entry.last_modified().timestamp());
+    }
+}
+
+#[test]
+fn model_round_trip() {
+    let vault = sample_vault();
+    let restored = DecryptedVaultFile::from_vault(&vault).unwrap().to_vault().unwrap();
+    assert_same_entries(&restored, &vault);
+}
+
+#[test]
+fn vault_file_round_trip() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("repassync.vault");
+    let vault = sample_vault();
+
+    write(&vault, PASSPHRASE, &path);
+
+    let file = EncryptedVaultFile::from_file(&path).unwrap();
+    assert_eq!(file.kdf(), test_kdf());
+    let restored = file.decrypt(secret(PASSPHRASE)).unwrap().to_vault().unwrap();
+    assert_same_entries(&restored, &vault);
+}
+
+#[test]
+fn wrong_passphrase_is_rejected() {
+    let file = encrypt(&sample_vault(), PASSPHRASE);
+    match file.decrypt(secret("not the passphrase")) {
+        Err(VaultError::WrongPassphrase) => (),
+        other => panic!("unexpected result {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn missing_file_is_not_found() {
+    let dir = TempDir::new("repassync").unwrap();
+    match EncryptedVaultFile::from_file(dir.path().join("repassync.vault")) {
+        Err(VaultError::NotFound) => (),
+        other => panic!("unexpected result {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn garbage_file_is_corrupted() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("repassync.vault");
+    fs::write(&path, b"not a vault").unwrap();
+    match EncryptedVaultFile::from_file(&path) {
+        Err(VaultError::Corrupted(_)) => (),
+        other => panic!("unexpected result {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn tampered_file_is_rejected() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("repassync.vault");
+    write(&sample_vault(), PASSPHRASE, &path);
+
+    let mut raw = fs::read(&path).unwrap();
+    // inside the authentication tag, written last
+    let last = raw.len() - 5;
+    raw[last] ^= 0x01;
+    fs::write(&path, &raw).unwrap();
+
+    let decrypted = EncryptedVaultFile::from_file(&path).and_then(|file| file.decrypt(secret(PASSPHRASE)));
+    match decrypted {
+        Err(VaultError::WrongPassphrase) => (),
+        other => panic!("unexpected result {:?}", other.map(|_| ()))
+    }
+}
+
+#[test]
+fn weak_key_derivation_is_below_floor() {
+    assert!(!Kdf::default().is_below_floor());
+    assert!(test_kdf().is_below_floor());
+    assert!(Kdf::pbkdf2(100_000).is_below_floor());
+}
+
+#[test]
+fn excessive_key_derivation_costs_are_rejected() {
+    let dir = TempDir::new("repassync").unwrap();
+    let path = dir.path().join("repassync.vault");
+    write(&sample_vault(), PASSPHRASE, &path);
+
+    // the header asks for 4 TiB instead of 64 KiB
+    let raw = fs::read(&path).unwrap();
+    let (field, cheap) = (&b"mem_cost"[..], &[0x18, 0x40][..]);
+    let at = raw.windows(field.len() + cheap.len())
+        .position(|window| &window[..field.len()] == field && &window[field.len()..] == cheap)
+        .expect("memory cost in the header") + field.len();
+    let mut tampered = raw[..at].to_vec();
+    tampered.extend_from_slice(&[0x1a, 0xff, 0xff, 0xff, 0xff]);
+    tampered.extend_from_slice(&raw[at + cheap.len()..]);
+    fs::write(&path, &tampered).unwrap();
+
+    let decrypted = EncryptedVaultFile::from_file(&path).and_then(|file| file.decrypt(secret(PASSPHRASE)));
+    match decrypted {
+        Err(VaultError::Corrupted(_)) => (),
+        other => panic!("unexpected result {:?}", other.map(|_| ()))
+    }
+    for kdf in &[Kdf::argon2id(64, u32::max_value(), 1), Kdf::argon2id(64, 1, u32::max_value()), Kdf::pbkdf2(u32::max_value())] {
+        match kdf.derive_key(&secret(PASSPHRASE), &[0; 16]) {
+            Err(VaultError::Corrupted(_)) => (),
+            other => panic!("unexpected result {:?}", other.map(|_| ()))
+        }
+    }
+}
+
+#[test]
+fn entry_details_are_sealed() {
+    let mut vault = sample_vault();
+    let id = vault.add_entry("bank".to_owned(), secret("correct horse")).unwrap().id().clone();
+    vault.update_entry(&id, |entry| entry.comment = Some("PIN 1234".to_owned()));
+    let file = DecryptedVaultFile::from_vault(&vault).unwrap();
+
+    let raw = serde_cbor::to_vec(&file).unwrap();
+    for clear in &[&b"alice@gmail.com"[..], &b"PIN 1234"[..]] {
+        assert!(!raw.windows(clear.len()).any(|window| window == *clear));
+    }
+    let restored = file.to_vault().unwrap();
+    assert_eq!(restored.get_entry(&id).unwrap().comment, Some("PIN 1234".to_owned()));
+}
+
+#[test]
+fn sealed_secrets_need_the_vault_key() {
+    let key = VaultKey::generate().unwrap();
+    let sealed = key.seal(&secret("hunter2")).unwrap();
+    assert_eq!(key.open(&sealed).unwrap(), secret("hunter2"));
+    // a fresh salt and nonce every time
+    assert!(key.seal(&secret("hunter2")).unwrap() != sealed);
+
+    let other = VaultKey::generate().unwrap();
+    assert!(other.open(&sealed).is_err());
+}
+
+#[test]
+fn unrevealed_password_is_not_replaced() {
+    let mut vault = sample_vault();
+    let id = vault.add_entry("bank".to_owned(), secret("correct horse")).unwrap().id().clone();
+    assert!(!vault.change_password(&id, secret("correct horse")).unwrap());
+    assert!(vault.change_password(&id, secret("battery staple")).unwrap());
+    assert_eq!(vault.reveal_password(vault.get_entry(&id).unwrap()).unwrap(), secret("battery staple"));
+
+    // an entry sealed with another key cannot be revealed
+    let entry = vault.get_entry(&id).unwrap().clone();
+    let mut other = Vault::new("alice".to_owned(), VaultKey::generate().unwrap());
+    other.insert_entry(entry);
+    assert!(other.change_password(&id, secret("")).is_err());
+    assert_eq!(vault.reveal_password(other.get_entry(&id).unwrap()).unwrap(), secret("battery staple"));
+}
+
+#[test]
+fn trash_restore_and_purge() {
+    let mut vault = Vault::new("".to_owned(), VaultKey::generate().unwrap());
+    let id = vault.add_entry("entry".to_owned(), secret("password")).unwrap().id().clone();
+
+    assert!(vault.trash_entry(&id));
+    assert!(vault.get_entry(&id).is_none());
+    assert!(vault.restore_entry(&id));
+    assert!(vault.get_entry(&id).is_some());
+
+    vault.trash_entry(&id);
+    assert_eq!(vault.purge_trash(Duration::days(30)), 0);
+    assert_eq!(vault.purge_trash(Duration::zero()), 1);
+    assert!(!vault.restore_entry(&id));
+}
@@ -0,0 +1,175 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate tiny_http;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+use std::thread;
+
+use tiny_http::{Server, Request, Response, Header};
+
+use repassync_core::sync::{synchronize, SyncClient, SyncError, SyncOutcome, SyncState, Fetched, Pushed};
+
+/// `alice@example.org:secret`, as sent with basic authentication.
+const CREDENTIALS: &str = "Basic YWxpY2VAZXhhbXBsZS5vcmc6c2VjcmV0";
+
+/// What a WebDAV server stores: files with their revision, and collections.
+#[derive(Default)]
+struct Dav {
+    files: HashMap<String, (u64, Vec<u8>)>,
+    collections: HashSet<String>,
+    revision: u64
+}
+
+/// Starts a WebDAV stand-in on a free local port, with the `/dav/alice/` collection.
+///
+/// Like some servers, it does not send the revision of uploaded files.
+fn start_server() -> String {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let addr = server.server_addr();
+    let mut dav = Dav::default();
+    dav.collections.insert("/dav/alice/".to_owned());
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let response = respond(&mut dav, &mut request);
+            request.respond(response).unwrap();
+        }
+    });
+    format!("webdav://alice%40example.org@{}/dav/alice/vaults/repassync.vault", addr)
+}
+
+fn respond(dav: &mut Dav, request: &mut Request) -> Response<Cursor<Vec<u8>>> {
+    if header(request, "Authorization").as_ref().map(String::as_str) != Some(CREDENTIALS) {
+        return status(401);
+    }
+    let path = request.url().to_owned();
+    match request.method().to_string().as_str() {
+        "GET" => {
+            match dav.files.get(&path) {
+                Some(&(revision, _)) if header(request, "If-None-Match") == Some(etag(revision)) => status(304),
+                Some(&(revision, ref content)) => {
+                    Response::from_data(content.clone())
+                        .with_header(Header::from_bytes(&b"ETag"[..], etag(revision).as_bytes()).unwrap())
+                },
+                None => status(404)
+            }
+        },
+        "PUT" => {
+            let parent = format!("{}/", &path[..path.rfind('/').unwrap()]);
+            if !dav.collections.contains(&parent) {
+                return status(409);
+            }
+            let current = dav.files.get(&path).map(|&(revision, _)| etag(revision));
+            let allowed = match (header(request, "If-Match"), header(request, "If-None-Match")) {
+                (Some(tag), _) => current == Some(tag),
+                (None, Some(ref tag)) if tag == "*" => current.is_none(),
+                _ => true
+            };
+            if !allowed {
+                return status(412);
+            }
+            let mut content = vec![];
+            request.as_reader().read_to_end(&mut content).unwrap();
+            dav.revision += 1;
+            dav.files.insert(path, (dav.revision, content));
+            status(if current.is_some() { 204 } else { 201 })
+        },
+        "MKCOL" => {
+            if dav.collections.insert(path) { status(201) } else { status(405) }
+        },
+        _ => status(405)
+    }
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request.headers().iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str().to_owned())
+}
+
+fn etag(revision: u64) -> String {
+    format!("\"rev-{}\"", revision)
+}
+
+fn status(code: u16) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(vec![]).with_status_code(code)
+}
+
+fn client(url: &str) -> SyncClient {
+    SyncClient::new(url, Some("secret".to_owned())).unwrap()
+}
+
+#[test]
+fn vault_is_synchronized_through_webdav() {
+    let url = start_server();
+
+    // the folder of the vault is created on the first upload
+    let mut laptop = SyncState::default();
+    assert_eq!(synchronize(&client(&url), &mut laptop, Some(&b"version 1"[..])).unwrap(), SyncOutcome::Pushed);
+    assert_eq!(laptop.etag, Some("rev-1".to_owned()));
+
+    let mut phone = SyncState::default();
+    assert_eq!(synchronize(&client(&url), &mut phone, None).unwrap(), SyncOutcome::Pulled(b"version 1".to_vec()));
+    assert_eq!(synchronize(&client(&url), &mut phone, Some(&b"version 1"[..])).unwrap(), SyncOutcome::UpToDate);
+
+    phone.dirty = true;
+    assert_eq!(synchronize(&client(&url), &mut phone, Some(&b"version 2"[..])).unwrap(), SyncOutcome::Pushed);
+    laptop.dirty = true;
+    match synchronize(&client(&url), &mut laptop, Some(&b"laptop"[..])).unwrap() {
+        SyncOutcome::Conflict(content, etag) => {
+            assert_eq!(content, b"version 2".to_vec());
+            assert_eq!(Some(etag), phone.etag);
+        },
+        outcome => panic!("unexpected outcome {:?}", outcome)
+    }
+}
+
+#[test]
+fn uploads_are_conditional() {
+    let url = start_server();
+    let client = client(&url);
+
+    assert_eq!(client.fetch(None).unwrap(), Fetched::Missing);
+    let first = match client.push(b"version 1", None).unwrap() {
+        Pushed::Stored(etag) => etag,
+        Pushed::Conflict => panic!("conflict on creation")
+    };
+    assert_eq!(client.push(b"again", None).unwrap(), Pushed::Conflict);
+    assert_eq!(client.fetch(Some(first.as_str())).unwrap(), Fetched::NotModified);
+    assert!(client.push(b"version 2", Some(first.as_str())).unwrap() != Pushed::Conflict);
+    assert_eq!(client.push(b"stale", Some(first.as_str())).unwrap(), Pushed::Conflict);
+}
+
+#[test]
+fn credentials_are_required() {
+    let url = start_server();
+
+    match SyncClient::new(&url, Some("wrong".to_owned())).unwrap().fetch(None) {
+        Err(SyncError::Unauthorized) => (),
+        other => panic!("unexpected result {:?}", other)
+    }
+    assert_eq!(client(&url).fetch(None).unwrap(), Fetched::Missing);
+}
+
+#[test]
+fn unreachable_server_is_a_connection_error() {
+    // nothing listens on the discard port
+    let mut state = SyncState { etag: None, dirty: true };
+    match synchronize(&client("webdav://alice@127.0.0.1:9/repassync.vault"), &mut state, Some(&b"offline"[..])) {
+        Err(SyncError::Http(_)) => (),
+        other => panic!("unexpected result {:?}", other)
+    }
+    // still to push
+    assert!(state.dirty);
+}
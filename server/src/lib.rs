@@ -0,0 +1,214 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! A minimal repassync server, storing encrypted vault files for the clients to synchronize.
+//!
+//! Each vault lives at `/vaults/<name>`. `GET` downloads it and `PUT` replaces
+//! it, every stored version gets a new revision returned in the `ETag` header.
+//! Uploads must be conditional: `If-Match` with the revision they are based on,
+//! or `If-None-Match: *` to create the vault, otherwise they are refused. The
+//! server never decrypts anything, it does not even know the vault format.
+//!
+//! It speaks plain HTTP and is meant for tests and small setups, put it behind
+//! a TLS reverse proxy to expose it.
+extern crate tiny_http;
+extern crate byteorder;
+extern crate repassync_core;
+#[macro_use]
+extern crate log;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Cursor, Read, ErrorKind};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use tiny_http::{Server, Request, Response, Header, Method};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use repassync_core::io::atomic::write_atomically;
+
+/// The largest vault accepted, in bytes.
+const MAX_VAULT_SIZE: u64 = 64 * 1024 * 1024;
+
+pub struct RepassyncServer {
+    server: Server,
+    store: Store,
+    token: Option<String>
+}
+
+impl RepassyncServer {
+
+    /// Listens on `addr`, storing the vaults in `dir` and requiring `token` if any.
+    pub fn bind(addr: &str, dir: PathBuf, token: Option<String>) -> Result<RepassyncServer, Box<Error + Send + Sync>> {
+        let server = Server::http(addr)?;
+        Ok(RepassyncServer {
+            server: server,
+            store: Store { dir: dir },
+            token: token
+        })
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.server_addr()
+    }
+
+    /// Serves the requests one after the other, forever.
+    pub fn run(&self) {
+        for mut request in self.server.incoming_requests() {
+            let response = self.respond(&mut request);
+            debug!("{} {} -> {}", request.method(), request.url(), response.status_code().0);
+            if let Err(e) = request.respond(response) {
+                warn!("Unable to send response: {}", e);
+            }
+        }
+    }
+
+    fn respond(&self, request: &mut Request) -> Response<Cursor<Vec<u8>>> {
+        if !self.is_authorized(request) {
+            return status(401);
+        }
+        let name = match vault_name(request.url()) {
+            Some(name) => name.to_owned(),
+            None => return status(404)
+        };
+        let result = match *request.method() {
+            Method::Get => self.get(&name, request),
+            Method::Put => self.put(&name, request),
+            _ => Ok(status(405))
+        };
+        result.unwrap_or_else(|e| {
+            error!("Unable to access vault {}: {}", name, e);
+            status(500)
+        })
+    }
+
+    fn get(&self, name: &str, request: &Request) -> io::Result<Response<Cursor<Vec<u8>>>> {
+        match self.store.get(name)? {
+            Some((revision, content)) => {
+                if matches(header(request, "If-None-Match"), revision) {
+                    Ok(with_revision(status(304), revision))
+                } else {
+                    Ok(with_revision(Response::from_data(content), revision))
+                }
+            },
+            None => {
+                Ok(status(404))
+            }
+        }
+    }
+
+    fn put(&self, name: &str, request: &mut Request) -> io::Result<Response<Cursor<Vec<u8>>>> {
+        let current = self.store.get(name)?.map(|(revision, _)| revision);
+        let allowed = match (header(request, "If-Match"), header(request, "If-None-Match")) {
+            (Some(tags), _) => current.map(|revision| matches(Some(tags), revision)).unwrap_or(false),
+            (None, Some(ref tags)) if tags.trim() == "*" => current.is_none(),
+            // blind overwrites would lose the changes of other devices
+            _ => return Ok(status(428))
+        };
+        if !allowed {
+            return Ok(status(412));
+        }
+        let mut content = vec![];
+        request.as_reader().take(MAX_VAULT_SIZE + 1).read_to_end(&mut content)?;
+        if content.len() as u64 > MAX_VAULT_SIZE {
+            return Ok(status(413));
+        }
+        let revision = current.unwrap_or(0) + 1;
+        self.store.put(name, revision, &content)?;
+        let code = if current.is_some() { 200 } else { 201 };
+        Ok(with_revision(status(code), revision))
+    }
+
+    fn is_authorized(&self, request: &Request) -> bool {
+        match self.token {
+            Some(ref token) => header(request, "Authorization") == Some(format!("Bearer {}", token)),
+            None => true
+        }
+    }
+
+}
+
+/// The vaults stored as files, each one starting with its revision.
+struct Store {
+    dir: PathBuf
+}
+
+impl Store {
+
+    fn get(&self, name: &str) -> io::Result<Option<(u64, Vec<u8>)>> {
+        match File::open(self.dir.join(name)) {
+            Ok(mut file) => {
+                let revision = file.read_u64::<BigEndian>()?;
+                let mut content = vec![];
+                file.read_to_end(&mut content)?;
+                Ok(Some((revision, content)))
+            },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    fn put(&self, name: &str, revision: u64, content: &[u8]) -> io::Result<()> {
+        let mut raw = Vec::with_capacity(content.len() + 8);
+        raw.write_u64::<BigEndian>(revision)?;
+        raw.extend_from_slice(content);
+        write_atomically(self.dir.join(name), &raw)
+    }
+
+}
+
+/// The vault name in `/vaults/<name>`, restricted to characters safe in a file name.
+fn vault_name(url: &str) -> Option<&str> {
+    if !url.starts_with("/vaults/") {
+        return None;
+    }
+    let name = &url["/vaults/".len()..];
+    let safe = name.chars().all(|c| match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '-' | '_' | '.' => true,
+        _ => false
+    });
+    if safe && !name.is_empty() && !name.starts_with('.') {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request.headers().iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str().to_owned())
+}
+
+/// Whether the `If-Match`/`If-None-Match` list of entity tags contains the revision.
+fn matches(tags: Option<String>, revision: u64) -> bool {
+    let expected = revision.to_string();
+    match tags {
+        Some(tags) => tags.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_left_matches("W/").trim_matches('"') == expected
+        }),
+        None => false
+    }
+}
+
+fn status(code: u16) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(vec![]).with_status_code(code)
+}
+
+fn with_revision(response: Response<Cursor<Vec<u8>>>, revision: u64) -> Response<Cursor<Vec<u8>>> {
+    let etag = format!("\"{}\"", revision);
+    response.with_header(Header::from_bytes(&b"ETag"[..], etag.as_bytes()).unwrap())
+}
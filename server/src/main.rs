@@ -0,0 +1,62 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Runs the reference repassync server.
+extern crate repassync_server;
+
+extern crate clap;
+extern crate env_logger;
+#[macro_use]
+extern crate log;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use clap::{App, Arg};
+
+use repassync_server::RepassyncServer;
+
+fn main() {
+
+    env_logger::init().expect("Failed to initialize logging");
+
+    let matches = App::new("repassync-server")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Store encrypted repassync vaults for synchronization")
+        .arg(Arg::with_name("listen").long("listen").takes_value(true).default_value("127.0.0.1:8080")
+             .help("Address to listen on"))
+        .arg(Arg::with_name("data").long("data").takes_value(true).required(true)
+             .help("Directory where the vaults are stored"))
+        .get_matches();
+
+    let dir = PathBuf::from(matches.value_of("data").unwrap());
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("repassync-server: unable to create {}: {}", dir.display(), e);
+        process::exit(1);
+    }
+    // not an argument, so that it does not show in the process list
+    let token = env::var("REPASSYNC_TOKEN").ok().and_then(|token| if token.is_empty() { None } else { Some(token) });
+
+    match RepassyncServer::bind(matches.value_of("listen").unwrap(), dir, token) {
+        Ok(server) => {
+            info!("Listening on http://{}/vaults/", server.local_addr());
+            server.run();
+        },
+        Err(e) => {
+            eprintln!("repassync-server: {}", e);
+            process::exit(1);
+        }
+    }
+
+}
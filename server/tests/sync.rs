@@ -0,0 +1,132 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+extern crate repassync_core;
+extern crate repassync_server;
+extern crate tempdir;
+
+use std::thread;
+
+use tempdir::TempDir;
+
+use repassync_core::sync::{synchronize, SyncClient, SyncError, SyncOutcome, SyncState, Fetched, Pushed};
+
+use repassync_server::RepassyncServer;
+
+/// Starts a server on a free local port, returns the URL of the `alice` vault.
+fn start_server(dir: &TempDir, token: Option<&str>) -> String {
+    let server = RepassyncServer::bind("127.0.0.1:0", dir.path().to_path_buf(), token.map(str::to_owned)).unwrap();
+    let url = format!("http://{}/vaults/alice", server.local_addr());
+    thread::spawn(move || server.run());
+    url
+}
+
+fn client(url: &str) -> SyncClient {
+    SyncClient::new(url, None).unwrap()
+}
+
+#[test]
+fn pushed_vault_is_pulled_by_another_device() {
+    let dir = TempDir::new("repassync-server").unwrap();
+    let url = start_server(&dir, None);
+
+    let mut laptop = SyncState::default();
+    assert_eq!(synchronize(&client(&url), &mut laptop, Some(&b"version 1"[..])).unwrap(), SyncOutcome::Pushed);
+    assert!(laptop.etag.is_some());
+    assert!(!laptop.dirty);
+
+    let mut phone = SyncState::default();
+    assert_eq!(synchronize(&client(&url), &mut phone, None).unwrap(), SyncOutcome::Pulled(b"version 1".to_vec()));
+    assert_eq!(phone.etag, laptop.etag);
+
+    assert_eq!(synchronize(&client(&url), &mut phone, Some(&b"version 1"[..])).unwrap(), SyncOutcome::UpToDate);
+
+    phone.dirty = true;
+    assert_eq!(synchronize(&client(&url), &mut phone, Some(&b"version 2"[..])).unwrap(), SyncOutcome::Pushed);
+    assert_eq!(synchronize(&client(&url), &mut laptop, Some(&b"version 1"[..])).unwrap(), SyncOutcome::Pulled(b"version 2".to_vec()));
+    assert_eq!(phone.etag, laptop.etag);
+}
+
+#[test]
+fn concurrent_changes_are_a_conflict() {
+    let dir = TempDir::new("repassync-server").unwrap();
+    let url = start_server(&dir, None);
+
+    let mut laptop = SyncState::default();
+    synchronize(&client(&url), &mut laptop, Some(&b"version 1"[..])).unwrap();
+    let mut phone = laptop.clone();
+
+    laptop.dirty = true;
+    phone.dirty = true;
+    assert_eq!(synchronize(&client(&url), &mut laptop, Some(&b"laptop"[..])).unwrap(), SyncOutcome::Pushed);
+
+    let before = phone.clone();
+    match synchronize(&client(&url), &mut phone, Some(&b"phone"[..])).unwrap() {
+        SyncOutcome::Conflict(content, etag) => {
+            assert_eq!(content, b"laptop".to_vec());
+            assert_eq!(Some(etag), laptop.etag);
+        },
+        outcome => panic!("unexpected outcome {:?}", outcome)
+    }
+    // nothing is lost, the phone changes are still to push
+    assert_eq!(phone, before);
+}
+
+#[test]
+fn unsynchronized_vault_does_not_replace_the_server_one() {
+    let dir = TempDir::new("repassync-server").unwrap();
+    let url = start_server(&dir, None);
+
+    synchronize(&client(&url), &mut SyncState::default(), Some(&b"first device"[..])).unwrap();
+
+    let mut second = SyncState::default();
+    match synchronize(&client(&url), &mut second, Some(&b"second device"[..])).unwrap() {
+        SyncOutcome::Conflict(content, _) => assert_eq!(content, b"first device".to_vec()),
+        outcome => panic!("unexpected outcome {:?}", outcome)
+    }
+}
+
+#[test]
+fn conditions_are_checked_on_revisions() {
+    let dir = TempDir::new("repassync-server").unwrap();
+    let url = start_server(&dir, None);
+    let client = client(&url);
+
+    assert_eq!(client.fetch(None).unwrap(), Fetched::Missing);
+    let first = match client.push(b"version 1", None).unwrap() {
+        Pushed::Stored(etag) => etag,
+        Pushed::Conflict => panic!("conflict on creation")
+    };
+    assert_eq!(client.push(b"again", None).unwrap(), Pushed::Conflict);
+    assert_eq!(client.fetch(Some(first.as_str())).unwrap(), Fetched::NotModified);
+
+    let second = match client.push(b"version 2", Some(first.as_str())).unwrap() {
+        Pushed::Stored(etag) => etag,
+        Pushed::Conflict => panic!("conflict on update")
+    };
+    assert!(first != second);
+    assert_eq!(client.push(b"stale", Some(first.as_str())).unwrap(), Pushed::Conflict);
+    assert_eq!(client.fetch(Some(first.as_str())).unwrap(), Fetched::Vault(b"version 2".to_vec(), second));
+}
+
+#[test]
+fn token_is_required() {
+    let dir = TempDir::new("repassync-server").unwrap();
+    let url = start_server(&dir, Some("secret"));
+
+    match SyncClient::new(&url, Some("wrong".to_owned())).unwrap().fetch(None) {
+        Err(SyncError::Unauthorized) => (),
+        other => panic!("unexpected result {:?}", other)
+    }
+    let client = SyncClient::new(&url, Some("secret".to_owned())).unwrap();
+    assert_eq!(client.fetch(None).unwrap(), Fetched::Missing);
+}
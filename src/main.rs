@@ -12,34 +12,35 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 extern crate gtk;
 extern crate gio;
+extern crate gdk;
 extern crate glib;
 
 extern crate chrono;
-extern crate rand;
-extern crate char_iter;
 extern crate xdg;
 extern crate secstr;
-extern crate serde;
-#[macro_use]
-extern crate serde_derive;
-extern crate serde_bytes;
-extern crate serde_cbor;
-extern crate openssl;
-extern crate pwquality;
+extern crate dbus;
+extern crate uuid;
 extern crate env_logger;
 #[macro_use]
 extern crate log;
 
+extern crate repassync_core;
+
 mod ui;
-mod model;
-mod util;
-mod io;
+mod session;
+
+use repassync_core::{model, util, io, settings, sync};
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::env;
 
 use gtk::Application;
-use gio::APPLICATION_FLAGS_NONE;
-use gio::ApplicationExt;
+use gio::{APPLICATION_HANDLES_OPEN, APPLICATION_HANDLES_COMMAND_LINE};
+use gio::{ApplicationExt, FileExt};
 
 use ui::App;
+use ui::open::open_uri;
 
 pub static DATADIR: &'static str = include!(concat!(env!("OUT_DIR"), "/datadir.in"));
 
@@ -47,18 +48,39 @@ fn main() {
 
     env_logger::init().expect("Failed to initialize logging");
 
-    match Application::new("org.gnieh.Repassync", APPLICATION_FLAGS_NONE) {
+    // URIs are forwarded to the primary instance, which opens them
+    match Application::new("org.gnieh.Repassync", APPLICATION_HANDLES_OPEN | APPLICATION_HANDLES_COMMAND_LINE) {
         Ok(app) => {
             // register this application as the default one for the process
             app.set_default();
             // build the application gui
             app.connect_activate(|app| {
-                let rep_app = App::new(&app);
-                rep_app.borrow().show();
+                repassync_app(app).borrow().present();
+            });
+            app.connect_open(|app, files, _| {
+                let rep_app = repassync_app(app);
+                for file in files {
+                    if let Some(uri) = file.get_uri() {
+                        open_uri(rep_app.clone(), &uri);
+                    }
+                }
+                rep_app.borrow().present();
+            });
+            app.connect_command_line(|app, command_line| {
+                let rep_app = repassync_app(app);
+                for arg in command_line.get_arguments().iter().skip(1) {
+                    match arg.to_str() {
+                        Some(uri) => open_uri(rep_app.clone(), uri),
+                        None => warn!("Ignoring invalid argument {:?}", arg)
+                    }
+                }
+                rep_app.borrow().present();
+                0
             });
 
             // Run GTK application
-            app.run(&[]);
+            let args: Vec<String> = env::args().collect();
+            app.run(&args);
 
         },
         Err(e) => {
@@ -68,3 +90,18 @@ fn main() {
 
 
 }
+
+thread_local!(
+    static REPASSYNC_APP: RefCell<Option<Rc<RefCell<App>>>> = RefCell::new(None)
+);
+
+/// The application window, created on first activation.
+fn repassync_app(app: &Application) -> Rc<RefCell<App>> {
+    REPASSYNC_APP.with(|rep_app| {
+        rep_app.borrow_mut().get_or_insert_with(|| {
+            let rep_app = App::new(app);
+            rep_app.borrow().show();
+            rep_app
+        }).clone()
+    })
+}
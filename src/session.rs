@@ -0,0 +1,267 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::process;
+use std::thread;
+
+use dbus::{Connection, ConnectionItem, BusType, Message, Path};
+
+/// A change of the desktop session after which the vault must be locked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionEvent {
+    /// The screen was locked, or the screen saver activated.
+    Locked,
+    /// The machine is about to suspend or hibernate.
+    Suspending
+}
+
+/// The bus to listen on.
+#[derive(Debug, Clone)]
+pub enum Bus {
+    /// The system bus, where logind signals suspend and session locks.
+    System,
+    /// The session bus, where the screen saver signals its activation.
+    Session,
+    /// A bus at the given address, for instance a private test bus.
+    Address(String)
+}
+
+const MATCHES: [&'static str; 3] = [
+    "type='signal',interface='org.freedesktop.login1.Manager',member='PrepareForSleep'",
+    "type='signal',interface='org.freedesktop.ScreenSaver',member='ActiveChanged'",
+    "type='signal',interface='org.gnome.ScreenSaver',member='ActiveChanged'"
+];
+
+/// Listens in a background thread for session lock and suspend signals on the bus.
+///
+/// logind signals suspend and session locks on the system bus, the screen
+/// saver signals its activation on the session bus. Only the locks of the
+/// session this process belongs to are followed.
+/// The callback is called from the listening thread for every relevant signal,
+/// and returns whether to keep listening.
+pub fn watch_session<F: Fn(SessionEvent) -> bool + Send + 'static>(bus: Bus, callback: F) {
+    thread::spawn(move || {
+        let connection = match bus {
+            Bus::System => Connection::get_private(BusType::System),
+            Bus::Session => Connection::get_private(BusType::Session),
+            Bus::Address(ref address) => {
+                Connection::open_private(address).and_then(|connection| {
+                    connection.register().map(|_| connection)
+                })
+            }
+        };
+        let connection = match connection {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Unable to connect to {:?} bus: {}", bus, e);
+                return;
+            }
+        };
+        let session = match own_session(&connection) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                // expected on the session bus, where logind is not
+                info!("Not following session locks on {:?} bus: {}", bus, e);
+                None
+            }
+        };
+        let mut rules: Vec<String> = MATCHES.iter().map(|rule| rule.to_string()).collect();
+        if let Some(ref session) = session {
+            rules.push(format!("type='signal',interface='org.freedesktop.login1.Session',member='Lock',path='{}'", session));
+        }
+        for rule in &rules {
+            if let Err(e) = connection.add_match(rule) {
+                warn!("Unable to listen to session signals on {:?} bus: {}", bus, e);
+                return;
+            }
+        }
+        for item in connection.iter(1000) {
+            if let ConnectionItem::Signal(message) = item {
+                if let Some(event) = session_event(&message, session.as_ref().map(String::as_str)) {
+                    if !callback(event) {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// The object path of the logind session this process belongs to.
+fn own_session(connection: &Connection) -> Result<String, String> {
+    let call = Message::new_method_call("org.freedesktop.login1", "/org/freedesktop/login1",
+                                        "org.freedesktop.login1.Manager", "GetSessionByPID")?
+        .append1(process::id());
+    let reply = connection.send_with_reply_and_block(call, 1000).map_err(|e| e.to_string())?;
+    reply.get1::<Path>()
+        .map(|path| path.to_string())
+        .ok_or_else(|| "no session in the reply".to_owned())
+}
+
+fn session_event(message: &Message, session: Option<&str>) -> Option<SessionEvent> {
+    let (_, path, interface, member) = message.headers();
+    match (interface.as_ref().map(|s| s.as_str()), member.as_ref().map(|s| s.as_str())) {
+        (Some("org.freedesktop.login1.Manager"), Some("PrepareForSleep")) => {
+            // also sent with `false` on resume
+            match message.get1::<bool>() {
+                Some(true) => Some(SessionEvent::Suspending),
+                _ => None
+            }
+        },
+        (Some("org.freedesktop.login1.Session"), Some("Lock")) => {
+            // the other sessions of the machine get their own locks
+            if session.is_some() && path.as_ref().map(|s| s.as_str()) == session {
+                Some(SessionEvent::Locked)
+            } else {
+                None
+            }
+        },
+        (Some("org.freedesktop.ScreenSaver"), Some("ActiveChanged")) |
+        (Some("org.gnome.ScreenSaver"), Some("ActiveChanged")) => {
+            match message.get1::<bool>() {
+                Some(true) => Some(SessionEvent::Locked),
+                _ => None
+            }
+        },
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+    use std::time::Duration;
+
+    use dbus::{Connection, ConnectionItem, Message, Path};
+
+    use super::{Bus, SessionEvent, watch_session};
+
+    const OWN_SESSION: &'static str = "/org/freedesktop/login1/session/_31";
+    const OTHER_SESSION: &'static str = "/org/freedesktop/login1/session/_32";
+
+    /// A private bus, stopped when dropped.
+    struct TestBus {
+        daemon: Child,
+        address: String
+    }
+
+    impl TestBus {
+
+        fn start() -> TestBus {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(&["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon is needed to run this test");
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+            TestBus {
+                daemon: daemon,
+                address: address.trim().to_owned()
+            }
+        }
+
+        fn connect(&self) -> Connection {
+            let connection = Connection::open_private(&self.address).unwrap();
+            connection.register().unwrap();
+            connection
+        }
+
+    }
+
+    impl Drop for TestBus {
+
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+
+    }
+
+    /// Answers the session lookups like logind, with `OWN_SESSION`.
+    fn fake_logind(address: String) {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let connection = Connection::open_private(&address).unwrap();
+            connection.register().unwrap();
+            connection.register_name("org.freedesktop.login1", 0).unwrap();
+            ready_tx.send(()).unwrap();
+            for item in connection.iter(100) {
+                if let ConnectionItem::MethodCall(call) = item {
+                    let reply = call.method_return().append1(Path::new(OWN_SESSION).unwrap());
+                    connection.send(reply).unwrap();
+                }
+            }
+        });
+        ready_rx.recv().unwrap();
+    }
+
+    fn emit(connection: &Connection, path: &str, interface: &str, member: &str, active: Option<bool>) {
+        let mut signal = Message::new_signal(path, interface, member).unwrap();
+        if let Some(active) = active {
+            signal = signal.append1(active);
+        }
+        connection.send(signal).unwrap();
+    }
+
+    fn next_event(events: &Receiver<SessionEvent>) -> SessionEvent {
+        events.recv_timeout(Duration::from_secs(5)).expect("a session event")
+    }
+
+    #[test]
+    fn session_signals_are_reported() {
+        let bus = TestBus::start();
+        fake_logind(bus.address.clone());
+        let (tx, events) = mpsc::channel();
+        watch_session(Bus::Address(bus.address.clone()), move |event| tx.send(event).is_ok());
+
+        let emitter = bus.connect();
+        let screen_saver = |active| {
+            emit(&emitter, "/org/freedesktop/ScreenSaver", "org.freedesktop.ScreenSaver", "ActiveChanged", Some(active));
+        };
+        let sleep = |active| {
+            emit(&emitter, "/org/freedesktop/login1", "org.freedesktop.login1.Manager", "PrepareForSleep", Some(active));
+        };
+
+        // the watcher subscribes in the background, until then signals are lost
+        let mut subscribed = false;
+        for _ in 0..50 {
+            screen_saver(true);
+            if events.recv_timeout(Duration::from_millis(100)).is_ok() {
+                subscribed = true;
+                break;
+            }
+        }
+        assert!(subscribed, "the watcher never received a signal");
+        // skip the events of the signals sent while waiting
+        sleep(true);
+        while next_event(&events) != SessionEvent::Suspending {}
+
+        screen_saver(true);
+        assert_eq!(next_event(&events), SessionEvent::Locked);
+        emit(&emitter, "/org/gnome/ScreenSaver", "org.gnome.ScreenSaver", "ActiveChanged", Some(true));
+        assert_eq!(next_event(&events), SessionEvent::Locked);
+        emit(&emitter, OWN_SESSION, "org.freedesktop.login1.Session", "Lock", None);
+        assert_eq!(next_event(&events), SessionEvent::Locked);
+
+        // signals are delivered in order, so ignored ones are followed by a suspend
+        emit(&emitter, OTHER_SESSION, "org.freedesktop.login1.Session", "Lock", None);
+        screen_saver(false);
+        sleep(false);
+        sleep(true);
+        assert_eq!(next_event(&events), SessionEvent::Suspending);
+    }
+
+}
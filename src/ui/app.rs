@@ -16,40 +16,87 @@ use std::path::Path;
 
 use std::process;
 
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 
 use secstr::SecStr;
 
+use chrono::Duration;
+
+use uuid::Uuid;
+
 use gtk::prelude::*;
 use gtk::*;
 use gio::{Resource, resources_register};
 
-use model::Vault;
+use model::{Vault, Entry};
+
+use settings::Settings;
+
+use sync::git::Commit;
 
-use xdg;
+use util::{Query, Otp, Link};
 
 use ui::vault::{create_vault_ui, create_unlock_vault_ui};
-use ui::entry::create_entry_ui;
+use ui::entry::{CreateEntryUI, create_entry_ui};
 use ui::header_bar::{Header, create_header_bar_ui};
 use ui::main_window::{MainWindow, create_main_window_ui};
-use ui::views::{create_views, create_list_view};
+use ui::views::{create_views, create_list_view, create_trash_view, create_conflicts_view, create_history_view, error_message};
 use ui::widget::create_password_widget;
-
-use io::file::EncryptedVaultFile;
+use ui::trash::{connect_trash_buttons, create_trash_row};
+use ui::conflicts::{connect_conflicts_buttons, create_conflict_row};
+use ui::history::{connect_history_buttons, create_history_row};
+use ui::lock::connect_auto_lock;
+use ui::sync::{SyncStatus, connect_sync};
+use ui::folder::watch_folder;
+use ui::preferences::create_preferences_ui;
+use ui::passphrase::create_change_passphrase_ui;
+
+use io::device::device_id;
+use io::error::{Result, VaultError};
+use io::file::{EncryptedVaultFile, vault_path};
+use io::kdf::Kdf;
+
+/// The number of seconds a notification stays visible.
+const NOTIFICATION_TIMEOUT: u32 = 10;
+
+/// An action offered by a notification, such as undoing a deletion.
+pub type NotificationAction = ::std::boxed::Box<Fn(Rc<RefCell<App>>)>;
 
 enum LoadedVault {
     LockedVault(EncryptedVaultFile),
     UnlockedVault(Vault, SecStr),
+    /// the vault was locked, waiting for pending saves to finish
+    LockingVault,
     NoVault
 }
 
 pub struct App {
+    me: Weak<RefCell<App>>,
     window: ApplicationWindow,
     header: Header,
     main_window: MainWindow,
+    error_message: Label,
     list: FlowBox,
-
+    /// The entry of each card of `list`, in their order, and whether it matches the search.
+    cards: Rc<RefCell<Vec<(Uuid, bool)>>>,
+    trash_list: ListBox,
+    showing_trash: bool,
+    conflicts_list: ListBox,
+    showing_conflicts: bool,
+    history_list: ListBox,
+    showing_history: bool,
+    /// The versions of the vault recorded in its git history, the most recent first.
+    history: Vec<Commit>,
+    search: Query,
+    highlighted: Option<Uuid>,
+    create_entry: Option<CreateEntryUI>,
+    pending_links: Vec<Link>,
+    notification_action: Option<NotificationAction>,
+    notification_serial: u32,
+
+    settings: Settings,
+    kdf: Kdf,
     vault: LoadedVault
 }
 
@@ -80,36 +127,102 @@ impl App {
         window.set_titlebar(&header.stack);
 
 
+        let window_bis = window.clone();
         let main_window_bis = main_window.clone();
         let header_bis = header.clone();
 
-        create_views(&main_window.stack);
+        let error_message = create_views(&main_window.stack);
 
         let list = create_list_view();
         main_window.stack.add_named(&list, "list-vault");
+        let cards: Rc<RefCell<Vec<(Uuid, bool)>>> = Rc::new(RefCell::new(Vec::new()));
+        {
+            let cards = cards.clone();
+            list.set_filter_func(Some(::std::boxed::Box::new(move |child: &FlowBoxChild| {
+                cards.borrow().get(child.get_index() as usize).map(|&(_, visible)| visible).unwrap_or(true)
+            })));
+        }
+
+        let (trash, trash_list) = create_trash_view();
+        main_window.stack.add_named(&trash, "trash-vault");
+
+        let (conflicts, conflicts_list) = create_conflicts_view();
+        main_window.stack.add_named(&conflicts, "conflicts-vault");
+
+        let (history, history_list) = create_history_view();
+        main_window.stack.add_named(&history, "history-vault");
 
         let me = Rc::new(RefCell::new(App {
+            me: Weak::new(),
             window,
             header,
             main_window,
+            error_message,
             list,
-
+            cards,
+            trash_list,
+            showing_trash: false,
+            conflicts_list,
+            showing_conflicts: false,
+            history_list,
+            showing_history: false,
+            history: Vec::new(),
+            search: Query::default(),
+            highlighted: None,
+            create_entry: None,
+            pending_links: Vec::new(),
+            notification_action: None,
+            notification_serial: 0,
+
+            settings: Settings::load(),
+            kdf: Kdf::default(),
             vault: LoadedVault::NoVault
         }));
 
+        me.borrow_mut().me = Rc::downgrade(&me);
+
         let create_entry = create_entry_ui(me.clone());
-        header_bis.new_entry_button.set_popover(&create_entry);
+        header_bis.new_entry_button.set_popover(&create_entry.popover);
+        me.borrow_mut().create_entry = Some(create_entry);
+
+        let preferences = create_preferences_ui(me.clone());
+        header_bis.preferences_button.set_popover(&preferences);
+
+        connect_trash_buttons(me.clone(), &header_bis);
+        connect_conflicts_buttons(me.clone(), &header_bis);
+        connect_history_buttons(me.clone(), &header_bis);
+        connect_auto_lock(me.clone(), &window_bis, &header_bis.lock_button);
+
+        {
+            let app_bis = me.clone();
+            main_window_bis.search_entry.connect_search_changed(move |entry| {
+                let text = entry.get_text().unwrap_or_default();
+                app_bis.borrow_mut().set_search(text.as_str());
+            });
+        }
+
+        {
+            let app_bis = me.clone();
+            main_window_bis.secondary_action_button.connect_clicked(move |_| {
+                let action = app_bis.borrow_mut().take_notification_action();
+                if let Some(action) = action {
+                    action(app_bis.clone());
+                }
+            });
+        }
 
 
         let create_vault = create_vault_ui(me.clone());
         main_window_bis.stack.add_named(&create_vault, "create-vault");
 
-        let unlock_vault = create_unlock_vault_ui();
+        let unlock_vault = create_unlock_vault_ui(me.clone());
         main_window_bis.stack.add_named(&unlock_vault, "unlock-vault");
 
-        let xdg_dirs = xdg::BaseDirectories::with_prefix("repassync").unwrap();
-        match xdg_dirs.find_data_file("repassync.vault") {
-            Some(f) => {
+        let change_passphrase = create_change_passphrase_ui(me.clone());
+        main_window_bis.stack.add_named(&change_passphrase, "change-passphrase");
+
+        match vault_path() {
+            Ok(ref f) if f.exists() => {
                 match EncryptedVaultFile::from_file(f) {
                     Ok(f) => {
                         main_window_bis.stack.set_visible_child_name("unlock-vault");
@@ -117,16 +230,23 @@ impl App {
                     },
                     Err(e) => {
                         error!("Unable to open vault file: {}", e);
-                        main_window_bis.stack.set_visible_child_name("error-vault");
+                        me.borrow().show_error(&e);
                     }
                 }
             },
-            None => {
+            Ok(_) => {
                 main_window_bis.stack.set_visible_child_name("create-vault");
+            },
+            Err(e) => {
+                error!("Unable to find vault file: {}", e);
+                me.borrow().show_error(&e);
             }
 
         }
 
+        connect_sync(me.clone(), &header_bis.sync_button);
+        watch_folder(me.clone());
+
         me
 
     }
@@ -135,16 +255,316 @@ impl App {
         self.window.show_all();
     }
 
-    pub fn set_vault(&mut self, vault: Vault, pass: SecStr) {
+    pub fn present(&self) {
+        self.window.present();
+    }
+
+    /// Keeps a link to open once the vault is unlocked.
+    pub fn defer_link(&mut self, link: Link) {
+        self.pending_links.push(link);
+    }
+
+    pub fn take_pending_links(&mut self) -> Vec<Link> {
+        ::std::mem::replace(&mut self.pending_links, Vec::new())
+    }
+
+    pub fn create_entry_ui(&self) -> Option<CreateEntryUI> {
+        self.create_entry.clone()
+    }
+
+    pub fn show_create_entry(&self) {
+        self.header.new_entry_button.set_active(true);
+    }
+
+    /// Shows the entry in the list and selects it.
+    pub fn show_entry(&mut self, id: &Uuid) {
+        self.showing_trash = false;
+        self.showing_conflicts = false;
+        self.showing_history = false;
+        self.highlighted = Some(id.clone());
+        self.refresh();
+    }
+
+    /// Shows the unlocked vault.
+    ///
+    /// Fails if this device cannot be identified, as the changes made here
+    /// could then not be told apart from the ones of other devices.
+    pub fn set_vault(&mut self, vault: Vault, pass: SecStr, kdf: Kdf) -> ::std::io::Result<()> {
+        let mut vault = vault;
+        vault.set_device(device_id()?);
         self.vault = LoadedVault::UnlockedVault(vault, pass);
+        self.kdf = kdf;
         self.refresh();
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        match self.vault {
+            LoadedVault::UnlockedVault(_, _) => true,
+            _ => false
+        }
+    }
+
+    /// Whether there is a vault file, locked or not.
+    pub fn has_vault(&self) -> bool {
+        match self.vault {
+            LoadedVault::NoVault => false,
+            _ => true
+        }
     }
 
-    pub fn add_entry(&mut self, name: String, pass: SecStr) {
+    /// Whether the vault is being locked, waiting for pending saves.
+    pub fn is_locking(&self) -> bool {
+        match self.vault {
+            LoadedVault::LockingVault => true,
+            _ => false
+        }
+    }
+
+    /// Drops the decrypted vault and its passphrase, returns `false` if it was not unlocked.
+    ///
+    /// The application stays busy until `set_locked` is called.
+    pub fn lock(&mut self) -> bool {
+        if !self.is_unlocked() {
+            return false;
+        }
+        self.vault = LoadedVault::LockingVault;
+        self.showing_trash = false;
+        self.showing_conflicts = false;
+        self.showing_history = false;
+        for child in self.list.get_children() {
+            self.list.remove(&child);
+        }
+        for child in self.trash_list.get_children() {
+            self.trash_list.remove(&child);
+        }
+        for child in self.conflicts_list.get_children() {
+            self.conflicts_list.remove(&child);
+        }
+        for child in self.history_list.get_children() {
+            self.history_list.remove(&child);
+        }
+        self.main_window.notification.set_reveal_child(false);
+        self.notification_action = None;
+        self.refresh();
+        true
+    }
+
+    /// Finishes locking with the vault file as last saved.
+    pub fn set_locked(&mut self, file: Result<EncryptedVaultFile>) {
+        match file {
+            Ok(file) => {
+                self.vault = LoadedVault::LockedVault(file);
+                self.refresh();
+            },
+            Err(e) => {
+                self.vault = LoadedVault::NoVault;
+                self.show_error(&e);
+                self.header.stack.set_visible_child_name("empty-bar");
+                self.header.stack.set_sensitive(true);
+            }
+        }
+    }
+
+    /// Shows the error view, explaining why the vault file cannot be used.
+    pub fn show_error(&self, error: &VaultError) {
+        self.error_message.set_text(&error_message(error));
+        self.main_window.stack.set_visible_child_name("error-vault");
+    }
+
+    /// Whether the given passphrase is the one of the unlocked vault.
+    pub fn check_passphrase(&self, pass: &SecStr) -> bool {
         use self::LoadedVault::*;
         match self.vault {
+            UnlockedVault(_, ref current) => {
+                current == pass
+            },
+            _ => {
+                false
+            }
+        }
+    }
+
+    pub fn set_passphrase(&mut self, pass: SecStr) {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(_, ref mut current) => {
+                *current = pass;
+            },
+            _ => {
+                warn!("Try to change passphrase of locked or inexistent vault");
+            }
+        }
+    }
+
+    pub fn show_change_passphrase(&self) {
+        self.main_window.stack.set_visible_child_name("change-passphrase");
+        self.header.stack.set_visible_child_name("empty-bar");
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        if let Err(e) = settings.save() {
+            error!("Unable to save settings: {}", e);
+        }
+        self.settings = settings;
+    }
+
+    /// The key derivation function used when saving the vault.
+    pub fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+
+    pub fn set_kdf(&mut self, kdf: Kdf) {
+        self.kdf = kdf;
+    }
+
+    /// Permanently deletes the entries that stayed in the trash longer than configured.
+    ///
+    /// Returns whether some entry was deleted, in which case the vault needs saving.
+    pub fn purge_trash(&mut self) -> bool {
+        use self::LoadedVault::*;
+        let days = self.settings.trash_days as i64;
+        let purged = match self.vault {
             UnlockedVault(ref mut vault, _) => {
-                vault.add_entry(name, pass);
+                vault.purge_trash(Duration::days(days))
+            },
+            _ => {
+                0
+            }
+        };
+        if purged > 0 {
+            info!("Purged {} entries from the trash", purged);
+            self.refresh();
+        }
+        purged > 0
+    }
+
+    /// Filters the displayed entries with the given search query.
+    pub fn set_search(&mut self, query: &str) {
+        self.search = Query::parse(query);
+        if !self.search.is_empty() {
+            self.highlighted = None;
+        }
+        match self.vault {
+            // the cards stay, only the ones not matching are hidden
+            LoadedVault::UnlockedVault(ref vault, _) if !self.showing_trash && !self.showing_conflicts && !self.showing_history => {
+                self.filter_cards(vault);
+            },
+            _ => {
+                self.refresh();
+            }
+        }
+    }
+
+    /// Hides the cards of the entries not matching the search.
+    fn filter_cards(&self, vault: &Vault) {
+        let matching = {
+            let mut cards = self.cards.borrow_mut();
+            for card in cards.iter_mut() {
+                card.1 = vault.get_entry(&card.0).map(|entry| self.search.matches(entry)).unwrap_or(false);
+            }
+            cards.iter().any(|&(_, visible)| visible)
+        };
+        self.list.invalidate_filter();
+        if vault.is_empty() {
+            self.main_window.stack.set_visible_child_name("empty-vault");
+        } else if !matching {
+            self.main_window.stack.set_visible_child_name("empty-search");
+        } else {
+            self.main_window.stack.set_visible_child_name("list-vault");
+        }
+    }
+
+    pub fn show_trash(&mut self, show: bool) {
+        self.showing_trash = show;
+        self.showing_conflicts = false;
+        self.showing_history = false;
+        self.refresh();
+    }
+
+    /// Shows the entries changed concurrently on several devices.
+    pub fn show_conflicts(&mut self, show: bool) {
+        self.showing_conflicts = show;
+        self.showing_trash = false;
+        self.showing_history = false;
+        self.refresh();
+    }
+
+    /// Shows the versions of the vault recorded in its history.
+    pub fn show_history(&mut self, show: bool) {
+        self.showing_history = show;
+        self.showing_trash = false;
+        self.showing_conflicts = false;
+        self.refresh();
+    }
+
+    /// Updates the versions of the vault recorded in its history.
+    pub fn set_history(&mut self, history: Vec<Commit>) {
+        if self.history != history {
+            self.history = history;
+            if self.showing_history {
+                self.refresh();
+            }
+        }
+    }
+
+    /// Shows a message in the notification overlay, with an optional labelled action.
+    pub fn notify(&mut self, message: &str, action: Option<(&str, NotificationAction)>) {
+        self.notification_serial = self.notification_serial.wrapping_add(1);
+        self.main_window.notification_label.set_text(message);
+        match action {
+            Some((label, action)) => {
+                self.main_window.secondary_action_button.set_label(label);
+                self.main_window.secondary_action_button.show();
+                self.notification_action = Some(action);
+            },
+            None => {
+                self.main_window.secondary_action_button.hide();
+                self.notification_action = None;
+            }
+        }
+        self.main_window.notification.set_reveal_child(true);
+
+        let serial = self.notification_serial;
+        let me = self.me.clone();
+        timeout_add_seconds(NOTIFICATION_TIMEOUT, move || {
+            if let Some(app) = me.upgrade() {
+                app.borrow_mut().hide_notification(serial);
+            }
+            Continue(false)
+        });
+    }
+
+    /// Hides the notification, unless another one was shown since.
+    fn hide_notification(&mut self, serial: u32) {
+        if serial == self.notification_serial {
+            self.main_window.notification.set_reveal_child(false);
+            self.notification_action = None;
+        }
+    }
+
+    fn take_notification_action(&mut self) -> Option<NotificationAction> {
+        self.main_window.notification.set_reveal_child(false);
+        self.notification_action.take()
+    }
+
+    pub fn add_entry(&mut self, name: String, pass: SecStr, otp: Option<SecStr>) {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(ref mut vault, _) => {
+                let added = vault.add_entry(name, pass).and_then(|entry| {
+                    match otp {
+                        Some(otp) => vault.set_otp(entry.id(), Some(otp)).map(|_| ()),
+                        None => Ok(())
+                    }
+                });
+                if let Err(e) = added {
+                    error!("Unable to add entry: {}", e);
+                }
             },
             _ => {
                 warn!("Try to add entry to locked or inexistent vault");
@@ -153,6 +573,111 @@ impl App {
         self.refresh();
     }
 
+    /// Returns a copy of the locked vault file, if any.
+    pub fn locked_vault(&self) -> Option<EncryptedVaultFile> {
+        use self::LoadedVault::*;
+        match self.vault {
+            LockedVault(ref file) => {
+                Some(file.clone())
+            },
+            _ => {
+                None
+            }
+        }
+    }
+
+    /// The passphrase of the unlocked vault, if any.
+    pub fn passphrase(&self) -> Option<SecStr> {
+        match self.vault {
+            LoadedVault::UnlockedVault(_, ref pass) => Some(pass.clone()),
+            _ => None
+        }
+    }
+
+    /// Returns a copy of the unlocked vault, its passphrase and key derivation function, if any.
+    pub fn vault_snapshot(&self) -> Option<(Vault, SecStr, Kdf)> {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(ref vault, ref pass) => {
+                Some((vault.clone(), pass.clone(), self.kdf))
+            },
+            _ => {
+                None
+            }
+        }
+    }
+
+    /// Applies a modification to the unlocked vault and refreshes the view.
+    pub fn modify_vault<F: FnOnce(&mut Vault)>(&mut self, modify: F) {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(ref mut vault, _) => {
+                modify(vault);
+            },
+            _ => {
+                warn!("Try to modify locked or inexistent vault");
+            }
+        }
+        self.refresh();
+    }
+
+    pub fn entry(&self, id: &Uuid) -> Option<Entry> {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(ref vault, _) => {
+                vault.get_entry(id).cloned()
+            },
+            _ => {
+                None
+            }
+        }
+    }
+
+    /// Decrypts the password of the entry.
+    pub fn reveal_password(&self, id: &Uuid) -> Option<SecStr> {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(ref vault, _) => {
+                vault.get_entry(id).and_then(|entry| {
+                    vault.reveal_password(entry).map_err(|e| {
+                        error!("Unable to decrypt password: {}", e);
+                    }).ok()
+                })
+            },
+            _ => {
+                None
+            }
+        }
+    }
+
+    /// Decrypts the `otpauth://` URI of the entry.
+    pub fn reveal_otp(&self, id: &Uuid) -> Option<SecStr> {
+        use self::LoadedVault::*;
+        match self.vault {
+            UnlockedVault(ref vault, _) => {
+                vault.get_entry(id).and_then(|entry| {
+                    vault.reveal_otp(entry).map_err(|e| {
+                        error!("Unable to decrypt one-time password: {}", e);
+                    }).ok().and_then(|otp| otp)
+                })
+            },
+            _ => {
+                None
+            }
+        }
+    }
+
+    /// The one-time password generator of the entry.
+    pub fn otp(&self, id: &Uuid) -> Option<Otp> {
+        self.reveal_otp(id).and_then(|uri| {
+            String::from_utf8(uri.unsecure().to_vec()).ok().and_then(|uri| {
+                Otp::parse(&uri).map_err(|e| {
+                    error!("Unable to read one-time password: {}", e);
+                }).ok()
+            })
+        })
+    }
+
     pub fn has_entry(&self, name: &String) -> bool {
         use self::LoadedVault::*;
         match self.vault {
@@ -169,21 +694,80 @@ impl App {
     pub fn refresh(&self) {
         use self::LoadedVault::*;
         match self.vault {
-            UnlockedVault(ref vault, _) => {
-                if vault.is_empty() {
-                    self.main_window.stack.set_visible_child_name("empty-vault");
-                } else {
-                    for child in self.list.get_children() {
-                        self.list.remove(&child);
+            UnlockedVault(ref vault, _) if self.showing_trash => {
+                for child in self.trash_list.get_children() {
+                    self.trash_list.remove(&child);
+                }
+                if let Some(me) = self.me.upgrade() {
+                    let mut trash: Vec<_> = vault.trash().collect();
+                    trash.sort_by(|a, b| b.deleted.cmp(&a.deleted));
+                    for trashed in trash {
+                        self.trash_list.add(&create_trash_row(me.clone(), trashed));
                     }
-                    for entry in vault {
-                        self.list.add(&create_password_widget(entry));
+                }
+                self.trash_list.show_all();
+                self.main_window.stack.set_visible_child_name("trash-vault");
+                self.header.stack.set_visible_child_name("trash-bar");
+            },
+            UnlockedVault(ref vault, _) if self.showing_conflicts => {
+                for child in self.conflicts_list.get_children() {
+                    self.conflicts_list.remove(&child);
+                }
+                if let Some(me) = self.me.upgrade() {
+                    for conflict in vault.conflicts() {
+                        self.conflicts_list.add(&create_conflict_row(me.clone(), vault, conflict));
                     }
-                    self.main_window.stack.set_visible_child_name("list-vault");
-                    self.main_window.stack.show_all();
                 }
+                self.conflicts_list.show_all();
+                self.main_window.stack.set_visible_child_name("conflicts-vault");
+                self.header.stack.set_visible_child_name("conflicts-bar");
+            },
+            UnlockedVault(_, _) if self.showing_history => {
+                for child in self.history_list.get_children() {
+                    self.history_list.remove(&child);
+                }
+                if let Some(me) = self.me.upgrade() {
+                    for commit in &self.history {
+                        self.history_list.add(&create_history_row(me.clone(), commit));
+                    }
+                }
+                self.history_list.show_all();
+                self.main_window.stack.set_visible_child_name("history-vault");
+                self.header.stack.set_visible_child_name("history-bar");
+            },
+            UnlockedVault(ref vault, _) => {
+                let entries: Vec<&Entry> = vault.into_iter().collect();
+                for child in self.list.get_children() {
+                    self.list.remove(&child);
+                }
+                // filled before adding the cards, the filter runs as they are added
+                *self.cards.borrow_mut() = entries.iter()
+                    .map(|entry| (entry.id().clone(), self.search.matches(entry)))
+                    .collect();
+                if let Some(me) = self.me.upgrade() {
+                    for entry in entries {
+                        let widget = create_password_widget(me.clone(), entry);
+                        self.list.add(&widget);
+                        if Some(entry.id()) == self.highlighted.as_ref() {
+                            let child = widget.get_parent().and_then(|p| p.downcast::<FlowBoxChild>().ok());
+                            if let Some(child) = child {
+                                self.list.select_child(&child);
+                                child.grab_focus();
+                            }
+                        }
+                    }
+                }
+                self.main_window.stack.show_all();
+                self.filter_cards(vault);
+                self.header.conflicts_button.set_visible(vault.conflicts().next().is_some());
+                self.header.history_button.set_visible(self.settings.git_history);
                 self.header.stack.set_visible_child_name("password-list");
             },
+            LockingVault => {
+                self.header.stack.set_visible_child_name("empty-bar");
+                self.set_busy();
+                return;
+            },
             LockedVault(_) => {
                 self.main_window.stack.set_visible_child_name("unlock-vault");
                 self.header.stack.set_visible_child_name("empty-bar");
@@ -196,6 +780,24 @@ impl App {
         self.header.stack.set_sensitive(true);
     }
 
+    /// Shows the synchronization status in the header bar.
+    pub fn set_sync_status(&self, status: &SyncStatus) {
+        let (name, tooltip) = match *status {
+            SyncStatus::Disabled => {
+                self.header.sync_button.set_visible(false);
+                return;
+            },
+            SyncStatus::Syncing => ("syncing", "Synchronizing…".to_owned()),
+            SyncStatus::Synced => ("synced", "Synchronized, click to synchronize now".to_owned()),
+            SyncStatus::Conflict => ("conflict", "The vault was changed on another device, local changes are not synchronized".to_owned()),
+            SyncStatus::Offline(ref e) => ("failed", format!("The server cannot be reached, changes are synchronized later: {}", e)),
+            SyncStatus::Failed(ref e) => ("failed", format!("Unable to synchronize: {}", e))
+        };
+        self.header.sync_status.set_visible_child_name(name);
+        self.header.sync_button.set_tooltip_text(tooltip.as_str());
+        self.header.sync_button.set_visible(true);
+    }
+
     pub fn set_busy(&self) {
         self.main_window.stack.set_visible_child_name("busy-vault");
         self.header.stack.set_sensitive(false);
@@ -0,0 +1,78 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::cell::RefCell;
+use std::str;
+
+use secstr::SecStr;
+
+use gdk::Atom;
+use gtk::*;
+
+fn clipboard() -> Clipboard {
+    Clipboard::get(&Atom::intern("CLIPBOARD"))
+}
+
+/// Copies some non sensitive text to the clipboard.
+pub fn copy_text(text: &str) {
+    clipboard().set_text(text);
+}
+
+/// Copies a secret to the clipboard, and clears it after `timeout` seconds
+/// if the clipboard still holds it by then.
+pub fn copy_secret(secret: &SecStr, timeout: u32) {
+    let text = match str::from_utf8(secret.unsecure()) {
+        Ok(text) => text,
+        Err(e) => {
+            error!("Unable to copy secret: {}", e);
+            return;
+        }
+    };
+    clipboard().set_text(text);
+
+    let serial = COPIED.with(|copied| {
+        let mut copied = copied.borrow_mut();
+        let serial = copied.as_ref().map(|&(serial, _)| serial.wrapping_add(1)).unwrap_or(0);
+        *copied = Some((serial, secret.clone()));
+        serial
+    });
+
+    timeout_add_seconds(timeout, move || {
+        clear_secret(serial);
+        Continue(false)
+    });
+}
+
+fn clear_secret(serial: u32) {
+    // only the latest copied secret is cleared, earlier timeouts are superseded
+    let secret = COPIED.with(|copied| {
+        let current = copied.borrow().as_ref().map(|&(serial, _)| serial);
+        if current == Some(serial) {
+            copied.borrow_mut().take().map(|(_, secret)| secret)
+        } else {
+            None
+        }
+    });
+    if let Some(secret) = secret {
+        let clipboard = clipboard();
+        let holds_secret = clipboard.wait_for_text()
+            .map(|text| text.as_bytes() == secret.unsecure())
+            .unwrap_or(false);
+        if holds_secret {
+            clipboard.set_text("");
+        }
+    }
+}
+
+thread_local!(
+    static COPIED: RefCell<Option<(u32, SecStr)>> = RefCell::new(None)
+);
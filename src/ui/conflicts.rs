@@ -0,0 +1,110 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use chrono::prelude::*;
+
+use gtk::prelude::*;
+use gtk::*;
+
+use model::{Conflict, Entry, Resolution, Vault};
+
+use ui::App;
+use ui::header_bar::Header;
+use ui::save::save_vault;
+
+pub fn connect_conflicts_buttons(app: Rc<RefCell<App>>, header: &Header) {
+
+    {
+        let app_bis = app.clone();
+        header.conflicts_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().show_conflicts(true);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        header.conflicts_back_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().show_conflicts(false);
+        });
+    }
+
+}
+
+/// Describes a version of the entry: when it was changed, or deleted.
+fn describe(entry: Option<&Entry>, deleted: Option<DateTime<Utc>>) -> String {
+    match (entry, deleted) {
+        (Some(_), Some(deleted)) => deleted.with_timezone(&Local).format("moved to the trash on %Y-%m-%d %H:%M").to_string(),
+        (Some(entry), None) => entry.last_modified().with_timezone(&Local).format("changed on %Y-%m-%d %H:%M").to_string(),
+        (None, _) => "deleted".to_owned()
+    }
+}
+
+pub fn create_conflict_row(app: Rc<RefCell<App>>, vault: &Vault, conflict: &Conflict) -> ListBoxRow {
+
+    let (current, current_deleted) = match vault.get_entry(&conflict.id) {
+        Some(entry) => (Some(entry), None),
+        None => match vault.get_trashed_entry(&conflict.id) {
+            Some(trashed) => (Some(&trashed.entry), Some(trashed.deleted)),
+            None => (None, None)
+        }
+    };
+
+    let row = ListBoxRow::new();
+    let hbox = Box::new(Orientation::Horizontal, 12);
+    hbox.set_margin_left(12);
+    hbox.set_margin_right(12);
+    hbox.set_margin_top(6);
+    hbox.set_margin_bottom(6);
+
+    let vbox = Box::new(Orientation::Vertical, 3);
+
+    let name = current.or(conflict.other.as_ref()).map(|entry| entry.name.as_str()).unwrap_or_default();
+    let name = Label::new(Some(name));
+    name.set_halign(Align::Start);
+
+    let versions = format!("Here: {}\nOn another device: {}",
+                           describe(current, current_deleted),
+                           describe(conflict.other.as_ref(), conflict.deleted));
+    let versions = Label::new(Some(versions.as_str()));
+    versions.set_halign(Align::Start);
+    versions.get_style_context().map(|context| context.add_class("dim-label"));
+
+    vbox.pack_start(&name, false, true, 0);
+    vbox.pack_start(&versions, false, true, 0);
+
+    let keep = Button::new_with_label("Keep this version");
+    let other = Button::new_with_label("Use the other version");
+    let both = Button::new_with_label("Keep both");
+    // there is nothing to keep twice when one side deleted the entry
+    both.set_sensitive(current.is_some() && conflict.other.is_some());
+
+    for &(ref button, resolution) in &[(&keep, Resolution::Current), (&other, Resolution::Other), (&both, Resolution::Both)] {
+        let id = conflict.id.clone();
+        let app_bis = app.clone();
+        button.connect_clicked(move |_| {
+            app_bis.borrow_mut().modify_vault(|vault| { vault.resolve_conflict(&id, resolution); });
+            save_vault(app_bis.clone());
+        });
+    }
+
+    hbox.pack_start(&vbox, true, true, 0);
+    hbox.pack_start(&keep, false, true, 0);
+    hbox.pack_start(&other, false, true, 0);
+    hbox.pack_start(&both, false, true, 0);
+    row.add(&hbox);
+
+    row
+
+}
@@ -0,0 +1,169 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::collections::HashSet;
+
+use secstr::SecStr;
+
+use uuid::Uuid;
+
+use gtk::prelude::*;
+use gtk::*;
+
+use util::Otp;
+
+use ui::App;
+use ui::save::save_vault;
+use ui::generator::connect_password_generator;
+
+/// Creates the popover to edit the entry with the given identifier, if it exists.
+pub fn create_edit_entry_ui(app: Rc<RefCell<App>>, id: &Uuid) -> Option<Popover> {
+    let entry = match app.borrow().entry(id) {
+        Some(entry) => entry,
+        None => {
+            warn!("Try to edit inexistent entry {}", id);
+            return None;
+        }
+    };
+    let password = app.borrow().reveal_password(id);
+    let otp = app.borrow().reveal_otp(id)
+        .map(|otp| String::from_utf8_lossy(otp.unsecure()).into_owned())
+        .unwrap_or_default();
+
+    let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/EditEntry.glade");
+
+    let ui: Popover = builder.get_object("edit-popover").unwrap();
+    let content: Grid = builder.get_object("edit-grid").unwrap();
+    let name_field: Entry = builder.get_object("edit-name").unwrap();
+    let password_field: Entry = builder.get_object("edit-password").unwrap();
+    let show_password: CheckButton = builder.get_object("edit-show-password").unwrap();
+    let user_field: Entry = builder.get_object("edit-user").unwrap();
+    let uri_field: Entry = builder.get_object("edit-uri").unwrap();
+    let tags_field: Entry = builder.get_object("edit-tags").unwrap();
+    let comment_field: Entry = builder.get_object("edit-comment").unwrap();
+    let otp_field: Entry = builder.get_object("edit-otp").unwrap();
+    let save_button: Button = builder.get_object("edit-save-button").unwrap();
+
+    connect_password_generator(&builder, &content, &password_field);
+
+    name_field.set_text(entry.name.as_str());
+    match password {
+        Some(ref password) => password_field.set_text(String::from_utf8_lossy(password.unsecure()).as_ref()),
+        // an empty field would replace the password that could not be decrypted
+        None => password_field.set_sensitive(false)
+    }
+    user_field.set_text(entry.user.as_ref().map(|s| s.as_str()).unwrap_or(""));
+    uri_field.set_text(entry.uri.as_ref().map(|s| s.as_str()).unwrap_or(""));
+    otp_field.set_text(otp.as_str());
+    comment_field.set_text(entry.comment.as_ref().map(|s| s.as_str()).unwrap_or(""));
+    let mut tags: Vec<&String> = entry.tags.iter().collect();
+    tags.sort();
+    let tags: Vec<&str> = tags.into_iter().map(|t| t.as_str()).collect();
+    tags_field.set_text(tags.join(", ").as_str());
+
+    {
+        let password_field_bis = password_field.clone();
+        let otp_field_bis = otp_field.clone();
+        show_password.connect_toggled(move |check| {
+            password_field_bis.set_visibility(check.get_active());
+            otp_field_bis.set_visibility(check.get_active());
+        });
+    }
+
+    {
+        let otp_field_bis = otp_field.clone();
+        let save_button_bis = save_button.clone();
+        name_field.connect_changed(move |field| {
+            check_fields(field, &otp_field_bis, &save_button_bis);
+        });
+    }
+
+    {
+        let name_field_bis = name_field.clone();
+        let save_button_bis = save_button.clone();
+        otp_field.connect_changed(move |field| {
+            check_fields(&name_field_bis, field, &save_button_bis);
+        });
+    }
+
+    {
+        let ui_bis = ui.clone();
+        let id = id.clone();
+        save_button.connect_clicked(move |_| {
+            ui_bis.popdown();
+            let name = name_field.get_text().unwrap_or_else(|| "".to_owned());
+            let new_password = password_field.get_text().unwrap_or_else(|| "".to_owned());
+            let user = non_empty(user_field.get_text());
+            let uri = non_empty(uri_field.get_text());
+            let comment = non_empty(comment_field.get_text());
+            let tags = parse_tags(&tags_field.get_text().unwrap_or_else(|| "".to_owned()));
+            let new_otp = non_empty(otp_field.get_text());
+            let otp_changed = new_otp.as_ref().map(|o| o.as_str()).unwrap_or("") != otp.as_str();
+            let password_shown = password.is_some();
+            app.borrow_mut().modify_vault(move |vault| {
+                vault.update_entry(&id, move |entry| {
+                    entry.name = name;
+                    entry.user = user;
+                    entry.uri = uri;
+                    entry.comment = comment;
+                    entry.tags = tags;
+                });
+                // only seal the password again if it changed
+                if password_shown {
+                    if let Err(e) = vault.change_password(&id, SecStr::new(new_password.into_bytes())) {
+                        error!("Unable to change password: {}", e);
+                    }
+                }
+                if otp_changed {
+                    if let Err(e) = vault.set_otp(&id, new_otp.map(|o| SecStr::new(o.into_bytes()))) {
+                        error!("Unable to change one-time password: {}", e);
+                    }
+                }
+            });
+            save_vault(app.clone());
+        });
+    }
+
+    Some(ui)
+}
+
+/// Only allows saving with a name and a valid (or no) one-time password URI.
+fn check_fields(name_field: &Entry, otp_field: &Entry, save_button: &Button) {
+    let name = name_field.get_text().unwrap_or_else(|| "".to_owned());
+    let otp_valid = match non_empty(otp_field.get_text()) {
+        Some(uri) => Otp::parse(&uri).is_ok(),
+        None => true
+    };
+    if let Some(context) = otp_field.get_style_context() {
+        if otp_valid {
+            context.remove_class("error");
+        } else {
+            context.add_class("error");
+        }
+    }
+    save_button.set_sensitive(!name.is_empty() && otp_valid);
+}
+
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.map(|t| t.trim().to_owned()).and_then(|t| if t.is_empty() { None } else { Some(t) })
+}
+
+fn parse_tags(text: &str) -> HashSet<String> {
+    text.split(',')
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_owned())
+        .collect()
+}
@@ -13,19 +13,26 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use std::thread;
-use std::sync::mpsc;
-
 use secstr::SecStr;
 
 use gtk::prelude::*;
 use gtk::*;
-use glib;
+
+use util::Otp;
 
 use ui::App;
-use util::PasswordGenerator;
+use ui::save::save_vault;
+use ui::generator::connect_password_generator;
+
+/// The popover to create an entry, with the fields that can be pre-filled.
+#[derive(Clone)]
+pub struct CreateEntryUI {
+    pub popover: Popover,
+    pub name: Entry,
+    pub otp: Entry
+}
 
-pub fn create_entry_ui(app: Rc<RefCell<App>>) -> Popover {
+pub fn create_entry_ui(app: Rc<RefCell<App>>) -> CreateEntryUI {
     let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/CreateEntry.glade");
 
     let ui: Popover = builder.get_object("add-popover").unwrap();
@@ -34,75 +41,14 @@ pub fn create_entry_ui(app: Rc<RefCell<App>>) -> Popover {
     let add_button: Button = builder.get_object("add-button").unwrap();
     let password_field: Entry = builder.get_object("new-password").unwrap();
     let show_password: CheckButton = builder.get_object("show-password").unwrap();
+    let otp_field: Entry = builder.get_object("new-otp").unwrap();
     let already_exist: Label = builder.get_object("add-box-already-existing-label").unwrap();
 
-    let length: SpinButton = builder.get_object("password-generator-length").unwrap();
-    let use_lower: CheckButton = builder.get_object("password-generator-use-lower").unwrap();
-    let use_upper: CheckButton = builder.get_object("password-generator-use-upper").unwrap();
-    let use_numbers: CheckButton = builder.get_object("password-generator-use-numbers").unwrap();
-    let use_special: CheckButton = builder.get_object("password-generator-use-special").unwrap();
-
-    let generate_enabled: CheckButton = builder.get_object("password-generator-enable").unwrap();
-    let generate_button: Button = builder.get_object("password-generator-generate-button").unwrap();
-    let spinner: Spinner = builder.get_object("password-generator-working").unwrap();
-
-    {
-        let length_bis = length.clone();
-        let use_lower_bis = use_lower.clone();
-        let use_upper_bis = use_upper.clone();
-        let use_numbers_bis = use_numbers.clone();
-        let use_special_bis = use_special.clone();
-        let generate_button_bis = generate_button.clone();
-        generate_enabled.connect_toggled(move |toggle| {
-            let gen = toggle.get_active();
-            length_bis.set_sensitive(gen);
-            use_lower_bis.set_sensitive(gen);
-            use_upper_bis.set_sensitive(gen);
-            use_numbers_bis.set_sensitive(gen);
-            use_special_bis.set_sensitive(gen);
-            generate_button_bis.set_sensitive(gen);
-        });
-    }
-
-    {
-        let ui_bis = ui.clone();
-        let length_bis = length.clone();
-        let use_lower_bis = use_lower.clone();
-        let use_upper_bis = use_upper.clone();
-        let use_numbers_bis = use_numbers.clone();
-        let use_special_bis = use_special.clone();
-        let password_field_bis = password_field.clone();
-
-        generate_button.connect_clicked(move |_| {
-            content.set_sensitive(false);
-            spinner.start();
-
-            let length = length_bis.get_value_as_int() as usize;
-            let use_lower = use_lower_bis.get_active();
-            let use_upper = use_upper_bis.get_active();
-            let use_numbers = use_numbers_bis.get_active();
-            let use_special = use_special_bis.get_active();
-
-            let me = GeneratorUI {
-                ui: content.clone(),
-                length: length_bis.clone(),
-                use_lower: use_lower_bis.clone(),
-                use_upper: use_upper_bis.clone(),
-                use_numbers: use_numbers_bis.clone(),
-                use_special: use_special_bis.clone(),
-                spinner: spinner.clone(),
-                password_field: password_field_bis.clone()
-            };
-
-            GEN.with(move |gen| {
-                *gen.borrow_mut() =
-                    Some((me, GenThread::new(length, use_lower, use_upper, use_numbers, use_special, || { glib::idle_add(generated); })));
-            });
-        });
-    }
+    connect_password_generator(&builder, &content, &password_field);
 
     {
         let add_button_bis = add_button.clone();
+        let otp_field_bis = otp_field.clone();
         let app_bis = app.clone();
         new_name.connect_changed(move |entry| {
             let txt = entry.get_text().unwrap_or_else(|| "".to_owned());
@@ -112,19 +58,28 @@ pub fn create_entry_ui(app: Rc<RefCell<App>>) -> Popover {
             } else {
                 already_exist.set_opacity(0.0);
             }
-            if !txt.is_empty() {
-                add_button_bis.set_sensitive(!exists);
-            } else {
-                add_button_bis.set_sensitive(false);
-            }
+            // entries are identified independently of their name,
+            // an existing name is only a warning
+            add_button_bis.set_sensitive(!txt.is_empty() && valid_otp(&otp_field_bis));
+        });
+    }
+
+    {
+        let add_button_bis = add_button.clone();
+        let new_name_bis = new_name.clone();
+        otp_field.connect_changed(move |field| {
+            let name = new_name_bis.get_text().unwrap_or_else(|| "".to_owned());
+            add_button_bis.set_sensitive(!name.is_empty() && valid_otp(field));
         });
     }
 
     {
         let password_field_bis = password_field.clone();
+        let otp_field_bis = otp_field.clone();
         show_password.connect_toggled(move |check| {
             let show = check.get_active();
             password_field_bis.set_visibility(show);
+            otp_field_bis.set_visibility(show);
         });
     }
 
@@ -132,70 +87,31 @@ pub fn create_entry_ui(app: Rc<RefCell<App>>) -> Popover {
         let ui_bis = ui.clone();
         let password_field_bis = password_field.clone();
         let new_name_bis = new_name.clone();
+        let otp_field_bis = otp_field.clone();
         let app_bis = app.clone();
         add_button.connect_clicked(move |button| {
             ui_bis.popdown();
             let name = new_name_bis.get_text().unwrap();
             let pwd = SecStr::new(password_field_bis.get_text().unwrap_or_else(|| "".to_owned()).into_bytes());
+            let otp = otp_field_bis.get_text()
+                .map(|otp| otp.trim().to_owned())
+                .and_then(|otp| if otp.is_empty() { None } else { Some(SecStr::new(otp.into_bytes())) });
             new_name_bis.set_text("");
             password_field_bis.set_text("");
-            app_bis.borrow_mut().add_entry(name, pwd);
+            otp_field_bis.set_text("");
+            app_bis.borrow_mut().add_entry(name, pwd, otp);
+            save_vault(app_bis.clone());
         });
     }
 
-    ui
-}
-
-thread_local!(
-    static GEN: RefCell<Option<(GeneratorUI, GenThread)>> = RefCell::new(None)
-);
-
-#[derive(Clone)]
-struct GeneratorUI {
-    ui: Grid,
-    length: SpinButton,
-    use_lower: CheckButton,
-    use_upper: CheckButton,
-    use_numbers: CheckButton,
-    use_special: CheckButton,
-    spinner: Spinner,
-    password_field: Entry
-}
-
-fn generated() -> Continue {
-    GEN.with(move |gen| {
-        if let Some((ref ui, ref gen_thread)) = *gen.borrow() {
-            let pass = gen_thread.password_channel.recv().unwrap();
-            ui.password_field.set_text(pass.as_str());
-            ui.spinner.stop();
-            ui.ui.set_sensitive(true);
-        }
-        *gen.borrow_mut() = None;
-    });
-    Continue(false)
-}
-
-struct GenThread {
-    password_channel: mpsc::Receiver<String>
+    CreateEntryUI {
+        popover: ui,
+        name: new_name,
+        otp: otp_field
+    }
 }
 
-impl GenThread {
-
-    fn new<F: Fn() + Send + 'static>(length: usize, use_lower: bool, use_upper: bool, use_numbers: bool, use_special: bool, callback: F) -> Self {
-        let (tx, rx) = mpsc::channel();
-
-        thread::spawn(move || {
-            let mut gen = PasswordGenerator::new(
-                length,
-                use_lower,
-                use_upper,
-                use_numbers,
-                use_special);
-            let pass = gen.generate();
-            tx.send(pass).unwrap();
-            callback();
-        });
-
-        GenThread { password_channel: rx }
-    }
+fn valid_otp(otp_field: &Entry) -> bool {
+    let otp = otp_field.get_text().unwrap_or_else(|| "".to_owned());
+    otp.trim().is_empty() || Otp::parse(&otp).is_ok()
 }
@@ -0,0 +1,300 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::sync::mpsc;
+
+use gtk::{Continue, timeout_add};
+use gio::{self, FileExt, FileMonitor, FileMonitorExt, FILE_MONITOR_NONE};
+use glib;
+
+use secstr::SecStr;
+
+use ui::App;
+use ui::views::error_message;
+use ui::save::is_saving;
+use ui::lock::lock_vault;
+use ui::sync::{PulledVault, show_merged, show_pulled, vault_saved};
+
+use io::atomic::write_atomically;
+use io::device::device_id;
+use io::error::Result;
+use io::file::{EncryptedVaultFile, vault_path};
+use io::kdf::Kdf;
+
+use sync::folder::{Merged, is_conflict_copy, merge_conflict_copies};
+
+/// How long changes to the folder must settle before it is checked, in milliseconds.
+const SETTLE_DELAY: u32 = 1000;
+
+/// Watches the folder of the vault file for changes made by other programs,
+/// such as a file synchronization tool.
+///
+/// When the vault file is replaced, the new version is shown. Conflict copies
+/// left by the tool are merged into the vault once it is unlocked.
+pub fn watch_folder(app: Rc<RefCell<App>>) {
+    let path = match vault_path() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Unable to watch the vault folder: {}", e);
+            return;
+        }
+    };
+    let monitor = match path.parent() {
+        Some(dir) => gio::File::new_for_path(dir).monitor_directory(FILE_MONITOR_NONE, None),
+        None => return
+    };
+    let monitor = match monitor {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            warn!("Unable to watch the vault folder: {}", e);
+            return;
+        }
+    };
+    {
+        let path = path.clone();
+        monitor.connect_changed(move |_, file, other_file, _| {
+            let concerned = |file: &gio::File| {
+                file.get_path().map(|changed| changed == path || is_conflict_copy(&path, &changed)).unwrap_or(false)
+            };
+            if concerned(file) || other_file.as_ref().map(&concerned).unwrap_or(false) {
+                schedule_check(app.clone());
+            }
+        });
+    }
+    let previous = LOCAL.with(|local| ::std::mem::replace(&mut local.borrow_mut().monitor, Some(monitor)));
+    if let Some(previous) = previous {
+        previous.cancel();
+    }
+    vault_written();
+}
+
+/// Records the content of the vault file as written by the application, so
+/// that it is not mistaken for a change made by another program.
+pub fn vault_written() {
+    let content = vault_path().ok().and_then(|path| fs::read(path).ok());
+    LOCAL.with(|local| local.borrow_mut().known = content);
+}
+
+/// Uses the vault folder newly set in the preferences, once pending saves are finished.
+///
+/// A vault found there replaces the current one, and must be unlocked.
+/// Otherwise the current vault file is copied there.
+pub fn change_folder(app: Rc<RefCell<App>>, previous: PathBuf) {
+    timeout_add(100, move || {
+        if is_saving() {
+            return Continue(true);
+        }
+        match vault_path() {
+            Ok(ref path) if path.exists() => {
+                if app.borrow().is_unlocked() {
+                    lock_vault(app.clone());
+                } else if !app.borrow().is_locking() {
+                    let file = EncryptedVaultFile::from_file(path);
+                    if let Err(ref e) = file {
+                        error!("Unable to open vault file: {}", e);
+                    }
+                    app.borrow_mut().set_locked(file);
+                }
+            },
+            Ok(ref path) if previous.exists() => {
+                let copied = fs::read(&previous).and_then(|content| write_atomically(path, &content));
+                match copied {
+                    Ok(()) => {
+                        let message = format!("The vault was copied to {}", path.display());
+                        app.borrow_mut().notify(&message, None);
+                    },
+                    Err(e) => {
+                        error!("Unable to copy vault file: {}", e);
+                        let message = format!("The vault could not be copied to {}: {}", path.display(), e);
+                        app.borrow_mut().notify(&message, None);
+                    }
+                }
+            },
+            Ok(_) => {},
+            Err(e) => {
+                error!("Unable to find vault file: {}", e);
+                app.borrow().show_error(&e);
+            }
+        }
+        watch_folder(app.clone());
+        Continue(false)
+    });
+}
+
+/// Checks the folder once changes settled, unless a check is already scheduled.
+fn schedule_check(app: Rc<RefCell<App>>) {
+    let scheduled = LOCAL.with(|local| ::std::mem::replace(&mut local.borrow_mut().scheduled, true));
+    if !scheduled {
+        timeout_add(SETTLE_DELAY, move || {
+            LOCAL.with(|local| local.borrow_mut().scheduled = false);
+            check_folder(app.clone());
+            Continue(false)
+        });
+    }
+}
+
+/// Looks for a new version of the vault file and for conflict copies, in the background.
+pub fn check_folder(app: Rc<RefCell<App>>) {
+    let running = FOLDER.with(|folder| folder.borrow().is_some());
+    if running || is_saving() || app.borrow().is_locking() {
+        schedule_check(app);
+        return;
+    }
+    let path = match vault_path() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Unable to check the vault folder: {}", e);
+            return;
+        }
+    };
+    let known = LOCAL.with(|local| local.borrow().known.clone());
+    let passphrase = app.borrow().passphrase();
+    let kdf = app.borrow().kdf();
+    FOLDER.with(move |folder| {
+        *folder.borrow_mut() =
+            Some((app, FolderThread::new(path, known, passphrase, kdf, || { glib::idle_add(checked); })));
+    });
+}
+
+fn checked() -> Continue {
+    let result = FOLDER.with(move |folder| {
+        let result = match *folder.borrow() {
+            Some((ref app, ref folder_thread)) => Some((app.clone(), folder_thread.result_channel.recv().unwrap())),
+            None => None
+        };
+        *folder.borrow_mut() = None;
+        result
+    });
+    if let Some((app, result)) = result {
+        apply(app, result);
+    }
+    Continue(false)
+}
+
+fn apply(app: Rc<RefCell<App>>, result: Checked) {
+    let Checked { path, known, change } = result;
+    // whether the vault was saved while checking
+    let saved = is_saving() || LOCAL.with(|local| local.borrow().known != known);
+    match change {
+        Ok(FolderChange::Unchanged) => {},
+        Ok(_) if saved => {
+            // the saved vault is checked again
+            schedule_check(app);
+        },
+        Ok(FolderChange::Replaced(content, pulled)) => {
+            LOCAL.with(|local| local.borrow_mut().known = Some(content));
+            show_pulled(app.clone(), pulled, "Vault updated from another device", None);
+            vault_saved(app);
+        },
+        Ok(FolderChange::Merged(merged)) => {
+            match merged.write(&path) {
+                Ok(()) => {
+                    vault_written();
+                    show_merged(app.clone(), merged.content, merged.vault, merged.conflicts);
+                    vault_saved(app);
+                },
+                Err(e) => {
+                    error!("Unable to write merged vault: {}", e);
+                    let message = format!("The conflict copies could not be merged. {}", error_message(&e));
+                    app.borrow_mut().notify(&message, None);
+                }
+            }
+        },
+        Err(e) => {
+            warn!("Unable to check the vault folder: {}", e);
+        }
+    }
+}
+
+/// What is known locally of the vault folder.
+struct LocalState {
+    /// Keeps watching the folder.
+    monitor: Option<FileMonitor>,
+    /// The content of the vault file as last written or read by the application.
+    known: Option<Vec<u8>>,
+    /// Whether a check is scheduled.
+    scheduled: bool
+}
+
+thread_local!(
+    static LOCAL: RefCell<LocalState> = RefCell::new(LocalState {
+        monitor: None,
+        known: None,
+        scheduled: false
+    })
+);
+
+thread_local!(
+    static FOLDER: RefCell<Option<(Rc<RefCell<App>>, FolderThread)>> = RefCell::new(None)
+);
+
+enum FolderChange {
+    Unchanged,
+    /// The vault file was replaced by another program.
+    Replaced(Vec<u8>, PulledVault),
+    /// Conflict copies were merged, but not written yet.
+    Merged(Merged)
+}
+
+struct Checked {
+    path: PathBuf,
+    /// The content of the vault file known when the check started.
+    known: Option<Vec<u8>>,
+    change: Result<FolderChange>
+}
+
+struct FolderThread {
+    result_channel: mpsc::Receiver<Checked>
+}
+
+impl FolderThread {
+    fn new<F: Fn() + Send + 'static>(path: PathBuf, known: Option<Vec<u8>>, passphrase: Option<SecStr>, kdf: Kdf, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let change = check(&path, known.as_ref(), passphrase, &kdf);
+            tx.send(Checked { path, known, change }).unwrap();
+            callback();
+        });
+
+        FolderThread { result_channel: rx }
+    }
+}
+
+fn check(path: &Path, known: Option<&Vec<u8>>, passphrase: Option<SecStr>, kdf: &Kdf) -> Result<FolderChange> {
+    if let Some(ref pass) = passphrase {
+        if let Some(merged) = merge_conflict_copies(path, pass, kdf, device_id()?)? {
+            return Ok(FolderChange::Merged(merged));
+        }
+    }
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        // removed by another program, the vault is still in memory
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(FolderChange::Unchanged),
+        Err(e) => return Err(e.into())
+    };
+    if Some(&content) == known {
+        return Ok(FolderChange::Unchanged);
+    }
+    let file = EncryptedVaultFile::from_bytes(&content)?;
+    let vault = passphrase.as_ref().map(|pass| {
+        file.decrypt(pass.clone()).and_then(|decrypted| decrypted.to_vault())
+    });
+    Ok(FolderChange::Replaced(content, PulledVault { file, vault, passphrase }))
+}
@@ -15,7 +15,18 @@ use gtk::*;
 #[derive(Clone, Debug)]
 pub struct Header {
     pub new_entry_button: MenuButton,
+    pub preferences_button: MenuButton,
     pub search_button: ToggleButton,
+    pub trash_button: Button,
+    pub lock_button: Button,
+    pub sync_button: Button,
+    pub sync_status: Stack,
+    pub trash_back_button: Button,
+    pub trash_empty_button: Button,
+    pub conflicts_button: Button,
+    pub conflicts_back_button: Button,
+    pub history_button: Button,
+    pub history_back_button: Button,
     pub stack: Stack
 }
 
@@ -24,7 +35,18 @@ pub fn create_header_bar_ui(search_bar: &SearchBar) -> Header {
     let stack: Stack = builder.get_object("header").unwrap();
 
     let new_entry_button: MenuButton = builder.get_object("add-toggle-button").unwrap();
+    let preferences_button: MenuButton = builder.get_object("preferences-toggle-button").unwrap();
     let search_button: ToggleButton = builder.get_object("search").unwrap();
+    let trash_button: Button = builder.get_object("trash-button").unwrap();
+    let lock_button: Button = builder.get_object("lock-button").unwrap();
+    let sync_button: Button = builder.get_object("sync-button").unwrap();
+    let sync_status: Stack = builder.get_object("sync-status").unwrap();
+    let trash_back_button: Button = builder.get_object("trash-back-button").unwrap();
+    let trash_empty_button: Button = builder.get_object("trash-empty-button").unwrap();
+    let conflicts_button: Button = builder.get_object("conflicts-button").unwrap();
+    let conflicts_back_button: Button = builder.get_object("conflicts-back-button").unwrap();
+    let history_button: Button = builder.get_object("history-button").unwrap();
+    let history_back_button: Button = builder.get_object("history-back-button").unwrap();
 
     stack.set_visible_child_name("empty-bar");
 
@@ -43,7 +65,18 @@ pub fn create_header_bar_ui(search_bar: &SearchBar) -> Header {
 
     Header {
         new_entry_button,
+        preferences_button,
         search_button,
+        trash_button,
+        lock_button,
+        sync_button,
+        sync_status,
+        trash_back_button,
+        trash_empty_button,
+        conflicts_button,
+        conflicts_back_button,
+        history_button,
+        history_back_button,
         stack
     }
 }
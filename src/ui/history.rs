@@ -0,0 +1,390 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::fs;
+use std::thread;
+use std::sync::mpsc;
+
+use chrono::prelude::*;
+
+use gtk::prelude::*;
+use gtk::*;
+use glib;
+
+use secstr::SecStr;
+
+use ui::App;
+use ui::header_bar::Header;
+use ui::save::{is_saving, save_vault};
+use ui::folder::vault_written;
+use ui::sync::{PulledVault, show_merged, show_pulled, vault_saved};
+
+use model::Vault;
+
+use io::device::device_id;
+use io::file::{EncryptedVaultFile, vault_path};
+use io::kdf::Kdf;
+
+use settings::Settings;
+
+use sync;
+use sync::git::{Commit, GitRepository, Pulled, synchronize};
+
+pub fn connect_history_buttons(app: Rc<RefCell<App>>, header: &Header) {
+
+    {
+        let app_bis = app.clone();
+        header.history_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().show_history(true);
+            // the list is refreshed once synchronized
+            sync_history(app_bis.clone());
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        header.history_back_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().show_history(false);
+        });
+    }
+
+}
+
+/// Records the vault file in its git history and synchronizes it with the
+/// remote, in the background: once unlocked and after each save.
+///
+/// A synchronization requested while one is running follows it.
+pub fn sync_history(app: Rc<RefCell<App>>) {
+    let settings = app.borrow().settings().clone();
+    if !settings.git_history || is_saving() {
+        // the end of the save starts a synchronization
+        return;
+    }
+    let running = GIT.with(|git| git.borrow().is_some());
+    if running {
+        AGAIN.with(|again| *again.borrow_mut() = true);
+        return;
+    }
+    let passphrase = app.borrow().passphrase();
+    let kdf = app.borrow().kdf();
+    GIT.with(move |git| {
+        *git.borrow_mut() =
+            Some((app, GitThread::new(settings, passphrase, kdf, || { glib::idle_add(synchronized); })));
+    });
+}
+
+fn synchronized() -> Continue {
+    let result = GIT.with(move |git| {
+        let result = match *git.borrow() {
+            Some((ref app, ref git_thread)) => Some((app.clone(), git_thread.result_channel.recv().unwrap())),
+            None => None
+        };
+        *git.borrow_mut() = None;
+        result
+    });
+    if let Some((app, result)) = result {
+        apply(app.clone(), result);
+        let again = AGAIN.with(|again| ::std::mem::replace(&mut *again.borrow_mut(), false));
+        if again {
+            sync_history(app);
+        }
+    }
+    Continue(false)
+}
+
+fn apply(app: Rc<RefCell<App>>, result: Synchronized) {
+    let Synchronized { content, outcome, pulled } = result;
+    let (repository, pulled_commit, history) = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            // kept locally, pushed by a later synchronization
+            warn!("Unable to synchronize the vault history: {}", e);
+            return;
+        }
+    };
+    app.borrow_mut().set_history(history);
+    let (commit, new_content, merged) = match pulled_commit {
+        Pulled::Pulled { commit, content, merged } => (commit, content, merged),
+        Pulled::UpToDate | Pulled::Diverged => return
+    };
+    // whether the vault file was written while synchronizing
+    let written = is_saving() || vault_path().ok().and_then(|path| fs::read(path).ok()) != content;
+    if written {
+        // the written vault is merged with the next synchronization
+        AGAIN.with(|again| *again.borrow_mut() = true);
+        return;
+    }
+    if let Err(e) = repository.advance(&commit, &new_content) {
+        error!("Unable to update the vault from its history: {}", e);
+        app.borrow_mut().notify(&format!("The vault could not be updated from its history: {}", e), None);
+        return;
+    }
+    vault_written();
+    match (merged, pulled) {
+        (Some((vault, conflicts)), _) => show_merged(app.clone(), new_content, vault, conflicts),
+        (None, Some(pulled)) => show_pulled(app.clone(), pulled, "Vault updated from its history", None),
+        (None, None) => error!("The vault from the history is invalid")
+    }
+    vault_saved(app);
+    // a merge is still to push
+    AGAIN.with(|again| *again.borrow_mut() = true);
+}
+
+pub fn create_history_row(app: Rc<RefCell<App>>, commit: &Commit) -> ListBoxRow {
+
+    let row = ListBoxRow::new();
+    let hbox = Box::new(Orientation::Horizontal, 12);
+    hbox.set_margin_left(12);
+    hbox.set_margin_right(12);
+    hbox.set_margin_top(6);
+    hbox.set_margin_bottom(6);
+
+    let vbox = Box::new(Orientation::Vertical, 3);
+
+    let date = commit.date.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+    let date = Label::new(Some(date.as_str()));
+    date.set_halign(Align::Start);
+
+    let message = Label::new(Some(commit.message.as_str()));
+    message.set_halign(Align::Start);
+    message.get_style_context().map(|context| context.add_class("dim-label"));
+
+    vbox.pack_start(&date, false, true, 0);
+    vbox.pack_start(&message, false, true, 0);
+
+    let entries = Button::new_with_label("Entries…");
+    entries.set_tooltip_text("Restore one entry as it was in this version");
+    let restore = Button::new_with_label("Restore");
+    restore.set_tooltip_text("Restore the whole vault as it was in this version");
+
+    {
+        let id = commit.id.clone();
+        let app_bis = app.clone();
+        entries.connect_clicked(move |button| {
+            open_version(app_bis.clone(), id.clone(), Opening::Entries(button.clone()));
+        });
+    }
+
+    {
+        let id = commit.id.clone();
+        let date = commit.date;
+        let app_bis = app.clone();
+        restore.connect_clicked(move |_| {
+            open_version(app_bis.clone(), id.clone(), Opening::Vault(date));
+        });
+    }
+
+    hbox.pack_start(&vbox, true, true, 0);
+    hbox.pack_start(&entries, false, true, 0);
+    hbox.pack_start(&restore, false, true, 0);
+    row.add(&hbox);
+
+    row
+
+}
+
+/// Decrypts a past version of the vault in the background, then restores it or lists its entries.
+fn open_version(app: Rc<RefCell<App>>, commit: String, opening: Opening) {
+    let running = OPEN.with(|open| open.borrow().is_some());
+    let passphrase = app.borrow().passphrase();
+    let passphrase = match passphrase {
+        Some(passphrase) if !running => passphrase,
+        _ => return
+    };
+    let settings = app.borrow().settings().clone();
+    app.borrow().set_busy();
+    OPEN.with(move |open| {
+        *open.borrow_mut() =
+            Some((app, opening, OpenThread::new(settings, commit, passphrase, || { glib::idle_add(opened); })));
+    });
+}
+
+fn opened() -> Continue {
+    let result = OPEN.with(move |open| {
+        let result = match *open.borrow() {
+            Some((ref app, ref opening, ref open_thread)) => Some((app.clone(), opening.clone(), open_thread.result_channel.recv().unwrap())),
+            None => None
+        };
+        *open.borrow_mut() = None;
+        result
+    });
+    if let Some((app, opening, past)) = result {
+        app.borrow().refresh();
+        let past = match past {
+            Ok(past) => past,
+            Err(e) => {
+                warn!("Unable to open a past version of the vault: {}", e);
+                let message = format!("This version could not be opened, it may have another passphrase: {}", e);
+                app.borrow_mut().notify(&message, None);
+                return Continue(false);
+            }
+        };
+        match opening {
+            Opening::Vault(date) => {
+                let mut restored = Ok(());
+                app.borrow_mut().modify_vault(|vault| restored = vault.restore_past_version(&past));
+                if let Err(e) = restored {
+                    error!("Unable to restore a past version of the vault: {}", e);
+                }
+                save_vault(app.clone());
+                app.borrow_mut().show_history(false);
+                let message = date.with_timezone(&Local).format("Vault restored as of %Y-%m-%d %H:%M").to_string();
+                app.borrow_mut().notify(&message, None);
+            },
+            Opening::Entries(button) => {
+                show_past_entries(app, &button, past);
+            }
+        }
+    }
+    Continue(false)
+}
+
+/// Lists the entries of a past version in a popover, each one can be restored.
+fn show_past_entries(app: Rc<RefCell<App>>, button: &Button, past: Vault) {
+    let popover = Popover::new(Some(button));
+    let list = Box::new(Orientation::Vertical, 6);
+    list.set_margin_left(12);
+    list.set_margin_right(12);
+    list.set_margin_top(12);
+    list.set_margin_bottom(12);
+
+    let past = Rc::new(past);
+    let mut entries: Vec<_> = (&*past).into_iter().collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    if entries.is_empty() {
+        let empty = Label::new(Some("This version has no entry"));
+        empty.get_style_context().map(|context| context.add_class("dim-label"));
+        list.pack_start(&empty, false, true, 0);
+    }
+    for entry in entries {
+        let hbox = Box::new(Orientation::Horizontal, 12);
+        let name = Label::new(Some(entry.name.as_str()));
+        name.set_halign(Align::Start);
+        let restore = Button::new_with_label("Restore");
+        {
+            let id = entry.id().clone();
+            let name = entry.name.clone();
+            let past = past.clone();
+            let app_bis = app.clone();
+            let popover = popover.clone();
+            restore.connect_clicked(move |_| {
+                popover.popdown();
+                let mut restored = Ok(false);
+                app_bis.borrow_mut().modify_vault(|vault| restored = vault.restore_past_entry(&past, &id));
+                match restored {
+                    Ok(_) => {
+                        save_vault(app_bis.clone());
+                        app_bis.borrow_mut().notify(&format!("{} restored", name), None);
+                    },
+                    Err(e) => error!("Unable to restore a past version of an entry: {}", e)
+                }
+            });
+        }
+        hbox.pack_start(&name, true, true, 0);
+        hbox.pack_start(&restore, false, true, 0);
+        list.pack_start(&hbox, false, true, 0);
+    }
+
+    popover.add(&list);
+    list.show_all();
+    popover.popup();
+}
+
+thread_local!(
+    static GIT: RefCell<Option<(Rc<RefCell<App>>, GitThread)>> = RefCell::new(None)
+);
+
+thread_local!(
+    /// Whether another synchronization was requested while one was running.
+    static AGAIN: RefCell<bool> = RefCell::new(false)
+);
+
+thread_local!(
+    static OPEN: RefCell<Option<(Rc<RefCell<App>>, Opening, OpenThread)>> = RefCell::new(None)
+);
+
+/// What a past version of the vault is opened for.
+#[derive(Clone)]
+enum Opening {
+    /// Restoring the whole vault, saved at that date.
+    Vault(DateTime<Utc>),
+    /// Listing its entries next to the button.
+    Entries(Button)
+}
+
+struct Synchronized {
+    /// The content of the vault file when the synchronization started.
+    content: Option<Vec<u8>>,
+    outcome: sync::Result<(GitRepository, Pulled, Vec<Commit>)>,
+    /// The vault pulled from the remote, decrypted with the passphrase of the unlocked vault.
+    pulled: Option<PulledVault>
+}
+
+struct GitThread {
+    result_channel: mpsc::Receiver<Synchronized>
+}
+
+impl GitThread {
+    fn new<F: Fn() + Send + 'static>(settings: Settings, passphrase: Option<SecStr>, kdf: Kdf, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let content = vault_path().ok().and_then(|path| fs::read(path).ok());
+            let outcome = settings.git_repository().unwrap_or_else(|| Err(sync::SyncError::Git("the history is disabled".to_owned())))
+                .and_then(|repository| {
+                    let pulled = synchronize(&repository, passphrase.as_ref(), &kdf, device_id()?)?;
+                    let history = repository.history()?;
+                    Ok((repository, pulled, history))
+                });
+            let pulled = match outcome {
+                Ok((_, Pulled::Pulled { ref content, merged: None, .. }, _)) => {
+                    EncryptedVaultFile::from_bytes(content).ok().map(|file| {
+                        let vault = passphrase.as_ref().map(|pass| {
+                            file.decrypt(pass.clone()).and_then(|decrypted| decrypted.to_vault())
+                        });
+                        PulledVault { file, vault, passphrase: passphrase.clone() }
+                    })
+                },
+                _ => None
+            };
+            tx.send(Synchronized { content, outcome, pulled }).unwrap();
+            callback();
+        });
+
+        GitThread { result_channel: rx }
+    }
+}
+
+struct OpenThread {
+    result_channel: mpsc::Receiver<sync::Result<Vault>>
+}
+
+impl OpenThread {
+    fn new<F: Fn() + Send + 'static>(settings: Settings, commit: String, passphrase: SecStr, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let past = settings.git_repository().unwrap_or_else(|| Err(sync::SyncError::Git("the history is disabled".to_owned())))
+                .and_then(|repository| {
+                    let file = EncryptedVaultFile::from_bytes(&repository.content_at(&commit)?)?;
+                    Ok(file.decrypt(passphrase)?.to_vault()?)
+                });
+            tx.send(past).unwrap();
+            callback();
+        });
+
+        OpenThread { result_channel: rx }
+    }
+}
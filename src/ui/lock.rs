@@ -0,0 +1,123 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use gtk::prelude::*;
+use gtk::*;
+use glib;
+
+use ui::App;
+use ui::save::is_saving;
+
+use io::file::{EncryptedVaultFile, vault_path};
+
+use session::{Bus, SessionEvent, watch_session};
+
+/// How often inactivity is checked, in seconds.
+const IDLE_CHECK_INTERVAL: u32 = 15;
+
+/// Locks the vault on inactivity, on session lock or suspend, and from the lock button.
+pub fn connect_auto_lock(app: Rc<RefCell<App>>, window: &ApplicationWindow, lock_button: &Button) {
+
+    {
+        let app_bis = app.clone();
+        lock_button.connect_clicked(move |_| {
+            lock_vault(app_bis.clone());
+        });
+    }
+
+    window.connect_event(move |_, _| {
+        LAST_ACTIVITY.with(|last| last.set(Instant::now()));
+        Inhibit(false)
+    });
+
+    {
+        let app_bis = app.clone();
+        timeout_add_seconds(IDLE_CHECK_INTERVAL, move || {
+            let minutes = app_bis.borrow().settings().lock_timeout;
+            let idle = LAST_ACTIVITY.with(|last| last.get().elapsed());
+            if minutes > 0 && idle >= Duration::from_secs(minutes as u64 * 60) && app_bis.borrow().is_unlocked() {
+                info!("Locking vault after {} minutes of inactivity", minutes);
+                lock_vault(app_bis.clone());
+            }
+            Continue(true)
+        });
+    }
+
+    let (tx, rx) = mpsc::channel();
+    for bus in vec![Bus::System, Bus::Session] {
+        let tx = tx.clone();
+        watch_session(bus, move |event| {
+            // the receiver is dropped along with the application
+            let sent = tx.send(event).is_ok();
+            if sent {
+                glib::idle_add(session_changed);
+            }
+            sent
+        });
+    }
+
+    SESSION.with(move |session| {
+        *session.borrow_mut() = Some((app, rx));
+    });
+
+}
+
+/// Drops the decrypted vault and goes back to the locked state.
+///
+/// Pending saves are finished first, and the locked vault is then read back
+/// from the file so that it contains every change.
+pub fn lock_vault(app: Rc<RefCell<App>>) {
+    if app.borrow_mut().lock() {
+        timeout_add(100, move || {
+            if is_saving() {
+                Continue(true)
+            } else {
+                let file = vault_path().and_then(EncryptedVaultFile::from_file);
+                if let Err(ref e) = file {
+                    error!("Unable to open vault file: {}", e);
+                }
+                app.borrow_mut().set_locked(file);
+                Continue(false)
+            }
+        });
+    }
+}
+
+fn session_changed() -> Continue {
+    SESSION.with(move |session| {
+        if let Some((ref app, ref events)) = *session.borrow() {
+            let mut lock = false;
+            while let Ok(event) = events.try_recv() {
+                debug!("Session event {:?}", event);
+                lock = lock || event == SessionEvent::Locked || event == SessionEvent::Suspending;
+            }
+            if lock {
+                lock_vault(app.clone());
+            }
+        }
+    });
+    Continue(false)
+}
+
+thread_local!(
+    static LAST_ACTIVITY: Cell<Instant> = Cell::new(Instant::now())
+);
+
+thread_local!(
+    static SESSION: RefCell<Option<(Rc<RefCell<App>>, mpsc::Receiver<SessionEvent>)>> = RefCell::new(None)
+);
@@ -17,7 +17,11 @@ pub struct MainWindow {
     pub ui: Box,
     pub search_bar: SearchBar,
     pub search_entry: SearchEntry,
-    pub stack: Stack
+    pub stack: Stack,
+    pub notification: Revealer,
+    pub notification_label: Label,
+    pub secondary_action_button: Button,
+    pub close_notification_button: Button
 }
 
 pub fn create_main_window_ui() -> MainWindow {
@@ -28,12 +32,27 @@ pub fn create_main_window_ui() -> MainWindow {
     let search_bar: SearchBar = builder.get_object("search_bar").unwrap();
     let search_entry: SearchEntry = builder.get_object("search_entry").unwrap();
     let stack: Stack = builder.get_object("stack").unwrap();
+    let notification: Revealer = builder.get_object("notification").unwrap();
+    let notification_label: Label = builder.get_object("notification_label").unwrap();
+    let secondary_action_button: Button = builder.get_object("secondary_action_button").unwrap();
+    let close_notification_button: Button = builder.get_object("close_notification_button").unwrap();
+
+    {
+        let notification_bis = notification.clone();
+        close_notification_button.connect_clicked(move |_| {
+            notification_bis.set_reveal_child(false);
+        });
+    }
 
     MainWindow {
         ui,
         search_bar,
         search_entry,
-        stack
+        stack,
+        notification,
+        notification_label,
+        secondary_action_button,
+        close_notification_button
     }
 
 }
@@ -19,3 +19,16 @@ mod header_bar;
 mod main_window;
 mod views;
 mod widget;
+mod save;
+mod preferences;
+mod passphrase;
+mod generator;
+mod edit;
+mod trash;
+mod conflicts;
+mod clipboard;
+mod lock;
+mod sync;
+mod folder;
+mod history;
+pub mod open;
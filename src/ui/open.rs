@@ -0,0 +1,67 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::str;
+
+use gtk::prelude::*;
+
+use util::Link;
+
+use ui::App;
+
+/// Opens an `otpauth://` or `repassync://` URI passed to the application.
+///
+/// If the vault is locked, the link is opened once it is unlocked.
+pub fn open_uri(app: Rc<RefCell<App>>, uri: &str) {
+    if let Some(link) = Link::parse(uri) {
+        open_link(app, link);
+    }
+}
+
+/// Opens the links received while the vault was locked.
+pub fn open_pending_links(app: Rc<RefCell<App>>) {
+    let links = app.borrow_mut().take_pending_links();
+    for link in links {
+        open_link(app.clone(), link);
+    }
+}
+
+fn open_link(app: Rc<RefCell<App>>, link: Link) {
+    if !app.borrow().is_unlocked() {
+        app.borrow_mut().defer_link(link);
+        return;
+    }
+    match link {
+        Link::NewOtp(otp) => {
+            let create_entry = app.borrow().create_entry_ui();
+            if let Some(create_entry) = create_entry {
+                let name = otp.issuer.clone().unwrap_or_else(|| otp.label.clone());
+                let uri = otp.to_uri();
+                // the fields callbacks borrow the application
+                create_entry.name.set_text(name.as_str());
+                create_entry.otp.set_text(str::from_utf8(uri.unsecure()).unwrap_or(""));
+                app.borrow_mut().show_trash(false);
+                app.borrow().show_create_entry();
+            }
+        },
+        Link::Entry(id) => {
+            if app.borrow().entry(&id).is_some() {
+                app.borrow_mut().show_entry(&id);
+            } else {
+                warn!("No entry {} to open", id);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use gtk::prelude::*;
+use gtk::*;
+
+use secstr::SecStr;
+
+use util::check_password_quality;
+
+use ui::App;
+use ui::save::save_vault_as;
+
+pub fn create_change_passphrase_ui(app: Rc<RefCell<App>>) -> Box {
+    let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/ChangePassphrase.glade");
+
+    let ui: Box = builder.get_object("change-passphrase-box").unwrap();
+    let current: Entry = builder.get_object("change-passphrase-current").unwrap();
+    let current_error: Label = builder.get_object("change-passphrase-current-error").unwrap();
+    let passphrase: Entry = builder.get_object("change-passphrase-new-1").unwrap();
+    let show_passphrase: CheckButton = builder.get_object("change-passphrase-show-password").unwrap();
+    let passphrase_confirm: Entry = builder.get_object("change-passphrase-new-2").unwrap();
+    let level: LevelBar = builder.get_object("change-passphrase-strength").unwrap();
+    let confirm_hint: Label = builder.get_object("change-passphrase-confirm-hint").unwrap();
+    let change: Button = builder.get_object("change-passphrase-change").unwrap();
+    let cancel: Button = builder.get_object("change-passphrase-cancel").unwrap();
+
+    let me = ChangePassphraseUI {
+        current,
+        current_error,
+        passphrase,
+        passphrase_confirm,
+        level,
+        confirm_hint,
+        change: change.clone()
+    };
+
+    {
+        let me_bis = me.clone();
+        show_passphrase.connect_toggled(move |toggle| {
+            me_bis.passphrase.set_visibility(toggle.get_active());
+        });
+    }
+
+    {
+        let me_bis = me.clone();
+        me.current.connect_changed(move |_| {
+            me_bis.current_error.set_opacity(0.0);
+        });
+    }
+
+    {
+        let me_bis = me.clone();
+        me.passphrase.connect_changed(move |entry| {
+            let value = entry.get_text().unwrap_or_else(|| "".to_owned());
+            let quality = check_password_quality(value.as_str());
+            me_bis.level.set_value(f64::from(quality as i32));
+            me_bis.check_confirmation();
+        });
+    }
+
+    {
+        let me_bis = me.clone();
+        me.passphrase_confirm.connect_changed(move |_| {
+            me_bis.check_confirmation();
+        });
+    }
+
+    {
+        let me_bis = me.clone();
+        let app_bis = app.clone();
+        change.connect_clicked(move |_| {
+            let current = SecStr::new(me_bis.current.get_text().unwrap_or_else(|| "".to_owned()).into_bytes());
+            if !app_bis.borrow().check_passphrase(&current) {
+                me_bis.current_error.set_opacity(1.0);
+                me_bis.current.grab_focus();
+                return;
+            }
+            let pass = SecStr::new(me_bis.passphrase.get_text().unwrap_or_else(|| "".to_owned()).into_bytes());
+            me_bis.clear();
+            // saving encrypts the vault again with a fresh salt and nonce
+            let kdf = app_bis.borrow().kdf();
+            save_vault_as(app_bis.clone(), pass, kdf);
+            app_bis.borrow().refresh();
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        cancel.connect_clicked(move |_| {
+            me.clear();
+            app_bis.borrow().refresh();
+        });
+    }
+
+    ui
+}
+
+#[derive(Clone)]
+struct ChangePassphraseUI {
+    current: Entry,
+    current_error: Label,
+    passphrase: Entry,
+    passphrase_confirm: Entry,
+    level: LevelBar,
+    confirm_hint: Label,
+    change: Button
+}
+
+impl ChangePassphraseUI {
+
+    fn check_confirmation(&self) {
+        let value = self.passphrase.get_text().unwrap_or_else(|| "".to_owned());
+        let confirm_value = self.passphrase_confirm.get_text().unwrap_or_else(|| "".to_owned());
+        if value.len() > 0 && value == confirm_value {
+            self.change.set_sensitive(true);
+            self.confirm_hint.set_opacity(0.0);
+        } else {
+            self.change.set_sensitive(false);
+            if confirm_value.len() > 0 {
+                self.confirm_hint.set_opacity(1.0);
+            } else {
+                self.confirm_hint.set_opacity(0.0);
+            }
+        }
+    }
+
+    fn clear(&self) {
+        self.current.set_text("");
+        self.passphrase.set_text("");
+        self.passphrase_confirm.set_text("");
+        self.current_error.set_opacity(0.0);
+    }
+
+}
@@ -0,0 +1,266 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::thread;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use gtk::prelude::*;
+use gtk::*;
+use glib;
+
+use ui::App;
+use ui::save::save_vault;
+use ui::sync::{sync_vault, reset_sync};
+use ui::folder::change_folder;
+use ui::history::sync_history;
+
+use io::error::Result;
+use io::kdf::{Kdf, KdfAlgorithm};
+
+pub fn create_preferences_ui(app: Rc<RefCell<App>>) -> Popover {
+    let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/Preferences.glade");
+
+    let ui: Popover = builder.get_object("preferences-popover").unwrap();
+    let unlock_time: SpinButton = builder.get_object("preferences-unlock-time").unwrap();
+    let trash_days: SpinButton = builder.get_object("preferences-trash-days").unwrap();
+    let clipboard_timeout: SpinButton = builder.get_object("preferences-clipboard-timeout").unwrap();
+    let lock_timeout: SpinButton = builder.get_object("preferences-lock-timeout").unwrap();
+    let sync_url: Entry = builder.get_object("preferences-sync-url").unwrap();
+    let sync_token: Entry = builder.get_object("preferences-sync-token").unwrap();
+    let vault_dir: Entry = builder.get_object("preferences-vault-dir").unwrap();
+    let git_history: CheckButton = builder.get_object("preferences-git-history").unwrap();
+    let git_remote: Entry = builder.get_object("preferences-git-remote").unwrap();
+    let kdf_label: Label = builder.get_object("preferences-kdf-label").unwrap();
+    let kdf_warning: Label = builder.get_object("preferences-kdf-warning").unwrap();
+    let calibrate: Button = builder.get_object("preferences-kdf-calibrate").unwrap();
+    let spinner: Spinner = builder.get_object("preferences-kdf-working").unwrap();
+    let change_passphrase: Button = builder.get_object("preferences-change-passphrase").unwrap();
+
+    let me = PreferencesUI {
+        kdf_label,
+        kdf_warning,
+        calibrate: calibrate.clone(),
+        spinner
+    };
+
+    unlock_time.set_value(app.borrow().settings().unlock_time as f64);
+    trash_days.set_value(app.borrow().settings().trash_days as f64);
+    clipboard_timeout.set_value(app.borrow().settings().clipboard_timeout as f64);
+    lock_timeout.set_value(app.borrow().settings().lock_timeout as f64);
+    sync_url.set_text(&app.borrow().settings().sync_url);
+    sync_token.set_text(&app.borrow().settings().sync_token);
+    vault_dir.set_text(&app.borrow().settings().vault_dir);
+    git_history.set_active(app.borrow().settings().git_history);
+    git_remote.set_text(&app.borrow().settings().git_remote);
+    git_remote.set_sensitive(app.borrow().settings().git_history);
+
+    {
+        let app_bis = app.clone();
+        let me_bis = me.clone();
+        ui.connect_show(move |_| {
+            me_bis.show_kdf(&app_bis.borrow().kdf());
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        unlock_time.connect_value_changed(move |spin| {
+            let mut settings = app_bis.borrow().settings().clone();
+            settings.unlock_time = spin.get_value_as_int() as u64;
+            app_bis.borrow_mut().set_settings(settings);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        trash_days.connect_value_changed(move |spin| {
+            let mut settings = app_bis.borrow().settings().clone();
+            settings.trash_days = spin.get_value_as_int() as u32;
+            app_bis.borrow_mut().set_settings(settings);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        clipboard_timeout.connect_value_changed(move |spin| {
+            let mut settings = app_bis.borrow().settings().clone();
+            settings.clipboard_timeout = spin.get_value_as_int() as u32;
+            app_bis.borrow_mut().set_settings(settings);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        lock_timeout.connect_value_changed(move |spin| {
+            let mut settings = app_bis.borrow().settings().clone();
+            settings.lock_timeout = spin.get_value_as_int() as u32;
+            app_bis.borrow_mut().set_settings(settings);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        let git_remote_bis = git_remote.clone();
+        git_history.connect_toggled(move |toggle| {
+            let mut settings = app_bis.borrow().settings().clone();
+            settings.git_history = toggle.get_active();
+            git_remote_bis.set_sensitive(settings.git_history);
+            app_bis.borrow_mut().set_settings(settings);
+            app_bis.borrow().refresh();
+            sync_history(app_bis.clone());
+        });
+    }
+
+    {
+        // applied once done editing, not on every key stroke
+        let app_bis = app.clone();
+        ui.connect_closed(move |_| {
+            let url = sync_url.get_text().unwrap_or_default().trim().to_owned();
+            let token = sync_token.get_text().unwrap_or_default();
+            let dir = vault_dir.get_text().unwrap_or_default().trim().to_owned();
+            let remote = git_remote.get_text().unwrap_or_default().trim().to_owned();
+            let mut settings = app_bis.borrow().settings().clone();
+            if settings.git_remote != remote {
+                settings.git_remote = remote;
+                app_bis.borrow_mut().set_settings(settings.clone());
+                sync_history(app_bis.clone());
+            }
+            if settings.vault_dir != dir {
+                let previous = settings.vault_path();
+                settings.vault_dir = dir;
+                app_bis.borrow_mut().set_settings(settings.clone());
+                match previous {
+                    Ok(previous) => change_folder(app_bis.clone(), previous),
+                    Err(e) => error!("Unable to find previous vault file: {}", e)
+                }
+            }
+            if settings.sync_url == url && settings.sync_token == token {
+                return;
+            }
+            let url_changed = settings.sync_url != url;
+            settings.sync_url = url;
+            settings.sync_token = token;
+            app_bis.borrow_mut().set_settings(settings);
+            if url_changed {
+                reset_sync(app_bis.clone());
+            } else {
+                sync_vault(app_bis.clone());
+            }
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        let ui_bis = ui.clone();
+        change_passphrase.connect_clicked(move |_| {
+            ui_bis.popdown();
+            app_bis.borrow().show_change_passphrase();
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        calibrate.connect_clicked(move |button| {
+            button.set_sensitive(false);
+            me.spinner.start();
+            let target = app_bis.borrow().settings().unlock_time();
+            let app_ter = app_bis.clone();
+            let me_bis = me.clone();
+            CALIBRATE.with(move |calibrate| {
+                *calibrate.borrow_mut() =
+                    Some((app_ter, me_bis, CalibrateThread::new(target, || { glib::idle_add(calibrated); })));
+            });
+        });
+    }
+
+    ui
+}
+
+/// A short description of the key derivation parameters.
+fn describe_kdf(kdf: &Kdf) -> String {
+    match kdf.algorithm {
+        KdfAlgorithm::Pbkdf2Sha256 => {
+            format!("PBKDF2-SHA256, {} iterations", kdf.time_cost)
+        },
+        KdfAlgorithm::Argon2id => {
+            format!("Argon2id, {} MiB, {} passes, {} lanes", kdf.mem_cost / 1024, kdf.time_cost, kdf.lanes)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PreferencesUI {
+    kdf_label: Label,
+    kdf_warning: Label,
+    calibrate: Button,
+    spinner: Spinner
+}
+
+impl PreferencesUI {
+
+    fn show_kdf(&self, kdf: &Kdf) {
+        self.kdf_label.set_text(describe_kdf(kdf).as_str());
+        if kdf.is_below_floor() {
+            self.kdf_warning.set_opacity(1.0);
+        } else {
+            self.kdf_warning.set_opacity(0.0);
+        }
+    }
+
+}
+
+fn calibrated() -> Continue {
+    CALIBRATE.with(move |calibrate| {
+        if let Some((ref app, ref ui, ref calibrate_thread)) = *calibrate.borrow() {
+            match calibrate_thread.kdf_channel.recv().unwrap() {
+                Ok(kdf) => {
+                    app.borrow_mut().set_kdf(kdf);
+                    ui.show_kdf(&kdf);
+                    // re-encrypt the vault with the new parameters
+                    save_vault(app.clone());
+                },
+                Err(e) => {
+                    error!("Unable to calibrate key derivation: {}", e);
+                }
+            }
+            ui.spinner.stop();
+            ui.calibrate.set_sensitive(true);
+        }
+        *calibrate.borrow_mut() = None;
+    });
+    Continue(false)
+}
+
+thread_local!(
+    static CALIBRATE: RefCell<Option<(Rc<RefCell<App>>, PreferencesUI, CalibrateThread)>> = RefCell::new(None)
+);
+
+struct CalibrateThread {
+    kdf_channel: mpsc::Receiver<Result<Kdf>>
+}
+
+impl CalibrateThread {
+    fn new<F: Fn() + Send + 'static>(target: Duration, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            tx.send(Kdf::calibrate(target)).unwrap();
+            callback();
+        });
+
+        CalibrateThread { kdf_channel: rx }
+    }
+}
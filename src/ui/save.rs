@@ -0,0 +1,154 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::thread;
+use std::sync::mpsc;
+
+use secstr::SecStr;
+
+use gtk::Continue;
+use glib;
+
+use ui::App;
+use ui::views::error_message;
+use ui::sync::vault_saved;
+use ui::folder::vault_written;
+use ui::history::sync_history;
+
+use model::Vault;
+
+use io::error::Result;
+use io::file;
+use io::kdf::Kdf;
+
+/// Saves the currently unlocked vault of the application to disk.
+///
+/// The vault is encrypted and written in a background thread. If a save is
+/// already running, the most recent state is saved once it finishes, so
+/// that writes never overlap.
+pub fn save_vault(app: Rc<RefCell<App>>) {
+    let snapshot = app.borrow().vault_snapshot();
+    if let Some(snapshot) = snapshot {
+        schedule(app, snapshot, false);
+    }
+}
+
+/// Saves the currently unlocked vault encrypted with a new passphrase and
+/// key derivation function.
+///
+/// The application keeps using the previous ones until the file is written,
+/// so that they always match the file on disk.
+pub fn save_vault_as(app: Rc<RefCell<App>>, pass: SecStr, kdf: Kdf) {
+    let snapshot = app.borrow().vault_snapshot();
+    if let Some((vault, _, _)) = snapshot {
+        schedule(app, (vault, pass, kdf), true);
+    }
+}
+
+fn schedule(app: Rc<RefCell<App>>, snapshot: (Vault, SecStr, Kdf), rekeyed: bool) {
+    SAVE.with(move |save| {
+        let mut save = save.borrow_mut();
+        match *save {
+            Some((_, _, ref mut pending)) => {
+                let pending_rekeyed = match *pending {
+                    Some((_, true)) => true,
+                    _ => false
+                };
+                if pending_rekeyed && !rekeyed {
+                    // the new credentials are not applied yet, the latest state is saved with them
+                    if let Some(((ref mut vault, _, _), _)) = *pending {
+                        *vault = snapshot.0;
+                    }
+                } else {
+                    *pending = Some((snapshot, rekeyed));
+                }
+            },
+            None => {
+                *save = Some((app, SaveThread::new(snapshot, rekeyed, || { glib::idle_add(saved); }), None));
+            }
+        }
+    });
+}
+
+/// Whether a save is running or waiting to run.
+pub fn is_saving() -> bool {
+    SAVE.with(|save| save.borrow().is_some())
+}
+
+fn saved() -> Continue {
+    let saved_app = SAVE.with(move |save| {
+        let (saved_app, next) = match *save.borrow_mut() {
+            Some((ref app, ref save_thread, ref mut pending)) => {
+                let saved_app = match save_thread.result_channel.recv().unwrap() {
+                    Ok(()) => {
+                        if let Some((ref pass, kdf)) = save_thread.credentials {
+                            app.borrow_mut().set_passphrase(pass.clone());
+                            app.borrow_mut().set_kdf(kdf);
+                            // a save requested meanwhile used the previous credentials
+                            if let Some(((_, ref mut pending_pass, ref mut pending_kdf), false)) = *pending {
+                                *pending_pass = pass.clone();
+                                *pending_kdf = kdf;
+                            }
+                        }
+                        Some(app.clone())
+                    },
+                    Err(e) => {
+                        error!("Unable to save vault file: {}", e);
+                        app.borrow_mut().notify(&format!("Your changes could not be saved. {}", error_message(&e)), None);
+                        None
+                    }
+                };
+                (saved_app, pending.take().map(|snapshot| (app.clone(), snapshot)))
+            },
+            None => (None, None)
+        };
+        *save.borrow_mut() = next.map(|(app, (snapshot, rekeyed))| {
+            (app, SaveThread::new(snapshot, rekeyed, || { glib::idle_add(saved); }), None)
+        });
+        saved_app
+    });
+    // outside of the borrow, synchronizing checks whether a save is running
+    if let Some(app) = saved_app {
+        vault_written();
+        vault_saved(app.clone());
+        sync_history(app);
+    }
+    Continue(false)
+}
+
+thread_local!(
+    static SAVE: RefCell<Option<(Rc<RefCell<App>>, SaveThread, Option<((Vault, SecStr, Kdf), bool)>)>> = RefCell::new(None)
+);
+
+struct SaveThread {
+    result_channel: mpsc::Receiver<Result<()>>,
+    /// The new credentials to apply to the application once saved.
+    credentials: Option<(SecStr, Kdf)>
+}
+
+impl SaveThread {
+    fn new<F: Fn() + Send + 'static>(snapshot: (Vault, SecStr, Kdf), rekeyed: bool, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (vault, passphrase, kdf) = snapshot;
+        let credentials = if rekeyed { Some((passphrase.clone(), kdf)) } else { None };
+
+        thread::spawn(move || {
+            tx.send(file::save_vault(&vault, passphrase, &kdf)).unwrap();
+            callback();
+        });
+
+        SaveThread { result_channel: rx, credentials: credentials }
+    }
+}
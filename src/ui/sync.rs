@@ -0,0 +1,497 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use std::fs;
+use std::io::ErrorKind;
+use std::thread;
+use std::sync::mpsc;
+
+use gtk::prelude::*;
+use gtk::*;
+use glib;
+
+use secstr::SecStr;
+
+use ui::{App, NotificationAction};
+use ui::save::{is_saving, save_vault};
+use ui::lock::lock_vault;
+use ui::folder::vault_written;
+use ui::views::device_error_message;
+
+use model::Vault;
+
+use io::atomic::write_atomically;
+use io::error::{self, VaultError};
+use io::file::{DecryptedVaultFile, EncryptedVaultFile, vault_path};
+use io::kdf::Kdf;
+
+use sync::{self, reconcile, synchronize, Reconciled, SyncClient, SyncError, SyncOutcome, SyncState};
+
+/// How often the vault is synchronized, in seconds.
+const SYNC_INTERVAL: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncStatus {
+    /// No server is configured.
+    Disabled,
+    Syncing,
+    Synced,
+    /// The vault changed both locally and on the server and could not be
+    /// merged yet, local changes are kept.
+    Conflict,
+    /// The server could not be reached, local changes are pushed by a later synchronization.
+    Offline(String),
+    /// The last synchronization failed.
+    Failed(String)
+}
+
+/// Synchronizes the vault in the background: periodically, after each save
+/// and from the status button of the header bar.
+pub fn connect_sync(app: Rc<RefCell<App>>, sync_button: &Button) {
+
+    {
+        let app_bis = app.clone();
+        sync_button.connect_clicked(move |_| {
+            sync_vault(app_bis.clone());
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        timeout_add_seconds(SYNC_INTERVAL, move || {
+            sync_vault(app_bis.clone());
+            Continue(true)
+        });
+    }
+
+    sync_vault(app);
+
+}
+
+/// Records that the vault file was saved, and pushes it.
+pub fn vault_saved(app: Rc<RefCell<App>>) {
+    LOCAL.with(|local| {
+        let mut local = local.borrow_mut();
+        local.generation += 1;
+        local.state.dirty = true;
+        if let Err(e) = local.state.save() {
+            error!("Unable to save synchronization state: {}", e);
+        }
+    });
+    sync_vault(app);
+}
+
+/// Forgets what is known of the server, after its address changed.
+pub fn reset_sync(app: Rc<RefCell<App>>) {
+    LOCAL.with(|local| {
+        let mut local = local.borrow_mut();
+        local.state = SyncState { etag: None, dirty: true };
+        if let Err(e) = local.state.save() {
+            error!("Unable to save synchronization state: {}", e);
+        }
+        if let Err(e) = SyncState::remove_base() {
+            error!("Unable to remove synchronization base: {}", e);
+        }
+    });
+    sync_vault(app);
+}
+
+/// Downloads the vault from the configured server, in place of creating one.
+pub fn download_vault(app: Rc<RefCell<App>>) {
+    let client = app.borrow().settings().sync_client();
+    if let Some(Err(e)) = client {
+        app.borrow_mut().notify(&format!("Unable to download the vault: {}", e), None);
+        return;
+    }
+    LOCAL.with(|local| local.borrow_mut().downloading = true);
+    app.borrow().set_busy();
+    reset_sync(app);
+}
+
+/// Starts a synchronization, unless one is running, in which case another one follows it.
+pub fn sync_vault(app: Rc<RefCell<App>>) {
+    let client = app.borrow().settings().sync_client();
+    let client = match client {
+        Some(Ok(client)) => client,
+        Some(Err(e)) => {
+            app.borrow().set_sync_status(&SyncStatus::Failed(e.to_string()));
+            return;
+        },
+        None => {
+            app.borrow().set_sync_status(&SyncStatus::Disabled);
+            return;
+        }
+    };
+    if is_saving() {
+        // the end of the save starts a synchronization
+        return;
+    }
+    let running = SYNC.with(|sync| sync.borrow().is_some());
+    if running {
+        LOCAL.with(|local| local.borrow_mut().again = true);
+        return;
+    }
+    let content = vault_path().map_err(|e| e.to_string()).and_then(|path| {
+        match fs::read(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string())
+        }
+    });
+    let content = match content {
+        Ok(content) => content,
+        Err(e) => {
+            app.borrow().set_sync_status(&SyncStatus::Failed(e));
+            return;
+        }
+    };
+    let (state, generation) = LOCAL.with(|local| {
+        let local = local.borrow();
+        (local.state.clone(), local.generation)
+    });
+    let passphrase = app.borrow().passphrase();
+    let kdf = app.borrow().kdf();
+    app.borrow().set_sync_status(&SyncStatus::Syncing);
+    SYNC.with(move |sync| {
+        *sync.borrow_mut() =
+            Some((app, SyncThread::new(client, state, generation, content, passphrase, kdf, || { glib::idle_add(synced); })));
+    });
+}
+
+fn synced() -> Continue {
+    let result = SYNC.with(move |sync| {
+        let result = match *sync.borrow() {
+            Some((ref app, ref sync_thread)) => Some((app.clone(), sync_thread.result_channel.recv().unwrap())),
+            None => None
+        };
+        *sync.borrow_mut() = None;
+        result
+    });
+    if let Some((app, result)) = result {
+        let status = apply(app.clone(), result);
+        app.borrow().set_sync_status(&status);
+        let again = LOCAL.with(|local| ::std::mem::replace(&mut local.borrow_mut().again, false));
+        let downloading = !again && LOCAL.with(|local| ::std::mem::replace(&mut local.borrow_mut().downloading, false));
+        if downloading && !app.borrow().has_vault() {
+            // back to the creation of a vault
+            app.borrow().refresh();
+            let message = match status {
+                SyncStatus::Failed(ref e) | SyncStatus::Offline(ref e) => format!("Unable to download the vault: {}", e),
+                _ => "There is no vault on the server yet".to_owned()
+            };
+            app.borrow_mut().notify(&message, None);
+        }
+        if again {
+            sync_vault(app);
+        }
+    }
+    Continue(false)
+}
+
+fn apply(app: Rc<RefCell<App>>, result: Synced) -> SyncStatus {
+    let Synced { state, generation, outcome, pulled, merged } = result;
+    // whether the vault was saved while synchronizing
+    let saved = is_saving() || LOCAL.with(|local| local.borrow().generation != generation);
+    let status = match outcome {
+        Ok(SyncOutcome::UpToDate) | Ok(SyncOutcome::Pushed) => {
+            LOCAL.with(|local| {
+                let mut local = local.borrow_mut();
+                local.state.etag = state.etag;
+                // changes saved meanwhile are still to push
+                local.state.dirty = local.state.dirty && saved;
+            });
+            SyncStatus::Synced
+        },
+        Ok(SyncOutcome::Pulled(_)) if saved => {
+            // pushing the saved changes reports the conflict
+            LOCAL.with(|local| local.borrow_mut().again = true);
+            return SyncStatus::Syncing;
+        },
+        Ok(SyncOutcome::Pulled(content)) => {
+            match pulled {
+                Some(pulled) => {
+                    match vault_path().and_then(|path| Ok(write_atomically(path, &content)?)) {
+                        Ok(()) => {
+                            LOCAL.with(|local| local.borrow_mut().state = state);
+                            save_base(&content);
+                            vault_written();
+                            show_pulled(app, pulled, "Vault updated from another device", None);
+                            SyncStatus::Synced
+                        },
+                        Err(e) => {
+                            error!("Unable to write pulled vault: {}", e);
+                            SyncStatus::Failed(e.to_string())
+                        }
+                    }
+                },
+                None => {
+                    SyncStatus::Failed("the server sent an invalid vault file".to_owned())
+                }
+            }
+        },
+        Ok(SyncOutcome::Conflict(_, _)) => {
+            match merged {
+                Some(Ok(Reconciled::Merged { ref content, ref vault, conflicts })) if saved && app.borrow().is_unlocked() => {
+                    // the merged vault is on the server, the changes saved meanwhile are pushed on top of it
+                    match fold_merged(app.clone(), vault, conflicts) {
+                        Ok(()) => {
+                            LOCAL.with(|local| {
+                                let mut local = local.borrow_mut();
+                                local.state.etag = state.etag;
+                                local.state.dirty = true;
+                                local.again = true;
+                            });
+                            save_base(content);
+                            SyncStatus::Syncing
+                        },
+                        Err(e) => {
+                            error!("Unable to merge vault: {}", e);
+                            SyncStatus::Failed(e.to_string())
+                        }
+                    }
+                },
+                Some(Ok(Reconciled::Merged { .. })) | Some(Ok(Reconciled::Retry)) if saved => {
+                    // the saved changes are merged with the next synchronization
+                    LOCAL.with(|local| local.borrow_mut().again = true);
+                    return SyncStatus::Syncing;
+                },
+                Some(Ok(Reconciled::Merged { content, vault, conflicts })) => {
+                    match vault_path().and_then(|path| Ok(write_atomically(path, &content)?)) {
+                        Ok(()) => {
+                            LOCAL.with(|local| local.borrow_mut().state = state);
+                            save_base(&content);
+                            vault_written();
+                            show_merged(app, content, vault, conflicts);
+                            SyncStatus::Synced
+                        },
+                        Err(e) => {
+                            error!("Unable to write merged vault: {}", e);
+                            SyncStatus::Failed(e.to_string())
+                        }
+                    }
+                },
+                Some(Ok(Reconciled::Retry)) => {
+                    // the server version changed while merging
+                    LOCAL.with(|local| local.borrow_mut().again = true);
+                    return SyncStatus::Syncing;
+                },
+                Some(Err(SyncError::Vault(e))) => {
+                    // most likely the passphrase was changed on another device
+                    warn!("Unable to merge vault: {}", e);
+                    SyncStatus::Conflict
+                },
+                Some(Err(e)) => {
+                    warn!("Unable to merge vault: {}", e);
+                    SyncStatus::Failed(e.to_string())
+                },
+                None => {
+                    SyncStatus::Conflict
+                }
+            }
+        },
+        Err(SyncError::Http(e)) => {
+            info!("Unable to reach synchronization server: {}", e);
+            SyncStatus::Offline(e.to_string())
+        },
+        Err(e) => {
+            warn!("Unable to synchronize vault: {}", e);
+            SyncStatus::Failed(e.to_string())
+        }
+    };
+    LOCAL.with(|local| {
+        if let Err(e) = local.borrow().state.save() {
+            error!("Unable to save synchronization state: {}", e);
+        }
+    });
+    status
+}
+
+/// Keeps the vault file as last synchronized, to merge later changes.
+fn save_base(content: &[u8]) {
+    if let Err(e) = SyncState::save_base(content) {
+        error!("Unable to save synchronization base: {}", e);
+    }
+}
+
+/// Merges the vault pushed by a synchronization into the unlocked one,
+/// changed since, and saves the result.
+fn fold_merged(app: Rc<RefCell<App>>, merged: &DecryptedVaultFile, conflicts: usize) -> error::Result<()> {
+    let snapshot = app.borrow().vault_snapshot();
+    let (vault, _, _) = match snapshot {
+        Some(snapshot) => snapshot,
+        None => return Ok(())
+    };
+    let local = DecryptedVaultFile::from_vault(&vault)?;
+    // the change logs tell which side changed each entry
+    let (folded, more_conflicts) = DecryptedVaultFile::merge(None, &local, merged)?;
+    let folded = folded.to_vault()?;
+    app.borrow_mut().modify_vault(move |vault| *vault = folded);
+    save_vault(app.clone());
+    let conflicts = conflicts + more_conflicts;
+    if conflicts == 0 {
+        app.borrow_mut().notify("Merged with changes from another device", None);
+    } else {
+        let message = format!("Merged with changes from another device, {} entries were changed on both", conflicts);
+        let resolve: NotificationAction = ::std::boxed::Box::new(|app: Rc<RefCell<App>>| {
+            app.borrow_mut().show_conflicts(true);
+        });
+        app.borrow_mut().notify(&message, Some(("Resolve", resolve)));
+    }
+    Ok(())
+}
+
+/// Shows the merged vault, offering to settle the conflicts.
+pub fn show_merged(app: Rc<RefCell<App>>, content: Vec<u8>, vault: DecryptedVaultFile, conflicts: usize) {
+    let file = match EncryptedVaultFile::from_bytes(&content) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Unable to read merged vault: {}", e);
+            lock_vault(app);
+            return;
+        }
+    };
+    let pulled = PulledVault {
+        file,
+        vault: Some(vault.to_vault()),
+        passphrase: app.borrow().passphrase()
+    };
+    if conflicts == 0 {
+        show_pulled(app, pulled, "Merged with changes from another device", None);
+    } else {
+        let message = format!("Merged with changes from another device, {} entries were changed on both", conflicts);
+        let resolve: NotificationAction = ::std::boxed::Box::new(|app: Rc<RefCell<App>>| {
+            app.borrow_mut().show_conflicts(true);
+        });
+        show_pulled(app, pulled, &message, Some(("Resolve", resolve)));
+    }
+}
+
+/// Shows the vault pulled from the server, or changed in its folder, in place of the local one.
+pub fn show_pulled(app: Rc<RefCell<App>>, pulled: PulledVault, message: &str, action: Option<(&str, NotificationAction)>) {
+    let unlocked = app.borrow().is_unlocked();
+    // the vault may have been locked and unlocked again meanwhile
+    let same = pulled.passphrase.as_ref().map(|pass| app.borrow().check_passphrase(pass)).unwrap_or(false);
+    if unlocked && same {
+        match pulled.vault {
+            Some(Ok(vault)) => {
+                let kdf = pulled.file.kdf().upgraded();
+                let pass = pulled.passphrase.unwrap();
+                let set = app.borrow_mut().set_vault(vault, pass, kdf);
+                match set {
+                    Ok(()) => app.borrow_mut().notify(message, action),
+                    Err(e) => {
+                        error!("Unable to identify this device: {}", e);
+                        lock_vault(app.clone());
+                        app.borrow_mut().notify(&device_error_message(&e), None);
+                    }
+                }
+            },
+            Some(Err(VaultError::WrongPassphrase)) => {
+                lock_vault(app.clone());
+                app.borrow_mut().notify("The passphrase was changed on another device", None);
+            },
+            _ => {
+                lock_vault(app);
+            }
+        }
+    } else if unlocked {
+        lock_vault(app);
+    } else if !app.borrow().is_locking() {
+        app.borrow_mut().set_locked(Ok(pulled.file));
+    }
+}
+
+/// What is known locally of the synchronization.
+struct LocalState {
+    state: SyncState,
+    /// The number of saves so far, to detect the ones happening while synchronizing.
+    generation: u64,
+    /// Whether another synchronization was requested while one was running.
+    again: bool,
+    /// Whether the vault is being downloaded from the creation view.
+    downloading: bool
+}
+
+thread_local!(
+    static LOCAL: RefCell<LocalState> = RefCell::new(LocalState {
+        state: SyncState::load(),
+        generation: 0,
+        again: false,
+        downloading: false
+    })
+);
+
+thread_local!(
+    static SYNC: RefCell<Option<(Rc<RefCell<App>>, SyncThread)>> = RefCell::new(None)
+);
+
+/// A vault file pulled from the server, or written by another program.
+pub struct PulledVault {
+    pub file: EncryptedVaultFile,
+    /// The content decrypted with the passphrase of the unlocked vault, if any.
+    pub vault: Option<error::Result<Vault>>,
+    pub passphrase: Option<SecStr>
+}
+
+struct Synced {
+    state: SyncState,
+    generation: u64,
+    outcome: sync::Result<SyncOutcome>,
+    pulled: Option<PulledVault>,
+    /// The local and server versions merged on a conflict, when the vault is unlocked.
+    merged: Option<sync::Result<Reconciled>>
+}
+
+struct SyncThread {
+    result_channel: mpsc::Receiver<Synced>
+}
+
+impl SyncThread {
+    fn new<F: Fn() + Send + 'static>(client: SyncClient, state: SyncState, generation: u64, content: Option<Vec<u8>>, passphrase: Option<SecStr>, kdf: Kdf, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut state = state;
+            let outcome = synchronize(&client, &mut state, content.as_ref().map(Vec::as_slice));
+            if let (&Ok(SyncOutcome::Pushed), &Some(ref content)) = (&outcome, &content) {
+                if let Err(e) = SyncState::save_base(content) {
+                    error!("Unable to save synchronization base: {}", e);
+                }
+            }
+            let merged = match (&outcome, &content, &passphrase) {
+                (&Ok(SyncOutcome::Conflict(ref remote, ref etag)), &Some(ref local), &Some(ref pass)) => {
+                    let base = SyncState::load_base();
+                    Some(reconcile(&client, &mut state, pass, &kdf, local, remote, etag, base.as_ref().map(Vec::as_slice)))
+                },
+                _ => None
+            };
+            let pulled = match outcome {
+                Ok(SyncOutcome::Pulled(ref content)) => {
+                    EncryptedVaultFile::from_bytes(content).ok().map(|file| {
+                        let vault = passphrase.as_ref().map(|pass| {
+                            file.decrypt(pass.clone()).and_then(|decrypted| decrypted.to_vault())
+                        });
+                        PulledVault { file, vault, passphrase }
+                    })
+                },
+                _ => None
+            };
+            tx.send(Synced { state, generation, outcome, pulled, merged }).unwrap();
+            callback();
+        });
+
+        SyncThread { result_channel: rx }
+    }
+}
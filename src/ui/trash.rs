@@ -0,0 +1,100 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use chrono::prelude::*;
+
+use gtk::prelude::*;
+use gtk::*;
+
+use model::TrashedEntry;
+
+use ui::App;
+use ui::header_bar::Header;
+use ui::save::save_vault;
+
+pub fn connect_trash_buttons(app: Rc<RefCell<App>>, header: &Header) {
+
+    {
+        let app_bis = app.clone();
+        header.trash_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().show_trash(true);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        header.trash_back_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().show_trash(false);
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        header.trash_empty_button.connect_clicked(move |_| {
+            app_bis.borrow_mut().modify_vault(|vault| vault.empty_trash());
+            save_vault(app_bis.clone());
+        });
+    }
+
+}
+
+pub fn create_trash_row(app: Rc<RefCell<App>>, trashed: &TrashedEntry) -> ListBoxRow {
+
+    let row = ListBoxRow::new();
+    let hbox = Box::new(Orientation::Horizontal, 12);
+    hbox.set_margin_left(12);
+    hbox.set_margin_right(12);
+    hbox.set_margin_top(6);
+    hbox.set_margin_bottom(6);
+
+    let name = Label::new(Some(trashed.entry.name.as_str()));
+    name.set_halign(Align::Start);
+    name.set_hexpand(true);
+
+    let deleted = trashed.deleted.with_timezone(&Local).format("Deleted on %Y-%m-%d %H:%M").to_string();
+    let date = Label::new(Some(deleted.as_str()));
+    date.get_style_context().map(|context| context.add_class("dim-label"));
+
+    let restore = Button::new_with_label("Restore");
+    let purge = Button::new_with_label("Delete");
+    purge.get_style_context().map(|context| context.add_class("destructive-action"));
+
+    {
+        let id = trashed.entry.id().clone();
+        let app_bis = app.clone();
+        restore.connect_clicked(move |_| {
+            app_bis.borrow_mut().modify_vault(|vault| { vault.restore_entry(&id); });
+            save_vault(app_bis.clone());
+        });
+    }
+
+    {
+        let id = trashed.entry.id().clone();
+        let app_bis = app.clone();
+        purge.connect_clicked(move |_| {
+            app_bis.borrow_mut().modify_vault(|vault| { vault.purge_entry(&id); });
+            save_vault(app_bis.clone());
+        });
+    }
+
+    hbox.pack_start(&name, true, true, 0);
+    hbox.pack_start(&date, false, true, 0);
+    hbox.pack_start(&restore, false, true, 0);
+    hbox.pack_start(&purge, false, true, 0);
+    row.add(&hbox);
+
+    row
+
+}
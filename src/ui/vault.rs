@@ -15,6 +15,7 @@ use std::cell::RefCell;
 
 use std::thread;
 use std::sync::mpsc;
+use std::time::Duration;
 
 use gtk::prelude::*;
 use gtk::*;
@@ -25,9 +26,20 @@ use secstr::SecStr;
 use util::check_password_quality;
 
 use ui::App;
+use ui::save::save_vault;
+use ui::open::open_pending_links;
+use ui::views::{device_error_message, error_message};
+use ui::sync::download_vault;
+use ui::folder::check_folder;
+use ui::history::sync_history;
 
 use model::Vault;
 
+use io::error::Result;
+use io::file::EncryptedVaultFile;
+use io::kdf::Kdf;
+use io::seal::VaultKey;
+
 pub fn create_vault_ui(app: Rc<RefCell<App>>) -> Box {
     let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/CreateVault.glade");
 
@@ -38,6 +50,9 @@ pub fn create_vault_ui(app: Rc<RefCell<App>>) -> Box {
     let level: LevelBar = builder.get_object("create-vault-password-strength").unwrap();
     let confirm_hint: Label = builder.get_object("create-vault-confirm-hint").unwrap();
     let create: Button = builder.get_object("create-vault-create").unwrap();
+    let sync_url: Entry = builder.get_object("create-vault-sync-url").unwrap();
+    let sync_token: Entry = builder.get_object("create-vault-sync-token").unwrap();
+    let download: Button = builder.get_object("create-vault-download-button").unwrap();
 
     {
         let show_passphrase_bis = show_passphrase.clone();
@@ -126,14 +141,34 @@ pub fn create_vault_ui(app: Rc<RefCell<App>>) -> Box {
         create.connect_clicked(move |_| {
             app_bis.borrow().set_busy();
             let pass = SecStr::new(passphrase.get_text().unwrap().into_bytes());
+            let unlock_time = app_bis.borrow().settings().unlock_time();
             let app_ter = app_bis.clone();
             CREATE.with(move |create| {
                 *create.borrow_mut() =
-                    Some((app_ter, CreateThread::new(pass, || { glib::idle_add(created); })));
+                    Some((app_ter, CreateThread::new(pass, unlock_time, || { glib::idle_add(created); })));
             });
         });
     }
 
+    {
+        let download_bis = download.clone();
+        sync_url.connect_changed(move |entry| {
+            let value = entry.get_text().unwrap_or_default();
+            download_bis.set_sensitive(!value.trim().is_empty());
+        });
+    }
+
+    {
+        let app_bis = app.clone();
+        download.connect_clicked(move |_| {
+            let mut settings = app_bis.borrow().settings().clone();
+            settings.sync_url = sync_url.get_text().unwrap_or_default().trim().to_owned();
+            settings.sync_token = sync_token.get_text().unwrap_or_default();
+            app_bis.borrow_mut().set_settings(settings);
+            download_vault(app_bis.clone());
+        });
+    }
+
     ui
 
 }
@@ -141,8 +176,20 @@ pub fn create_vault_ui(app: Rc<RefCell<App>>) -> Box {
 fn created() -> Continue {
     CREATE.with(move |create| {
         if let Some((ref app, ref create_thread)) = *create.borrow() {
-            let (vault, pass) = create_thread.vault_channel.recv().unwrap();
-            app.borrow_mut().set_vault(vault, pass);
+            let (vault, pass, kdf) = create_thread.vault_channel.recv().unwrap();
+            let set = app.borrow_mut().set_vault(vault, pass, kdf);
+            match set {
+                Ok(()) => {
+                    app.borrow_mut().purge_trash();
+                    save_vault(app.clone());
+                    open_pending_links(app.clone());
+                },
+                Err(e) => {
+                    error!("Unable to identify this device: {}", e);
+                    app.borrow().refresh();
+                    app.borrow_mut().notify(&device_error_message(&e), None);
+                }
+            }
         }
         *create.borrow_mut() = None;
     });
@@ -154,16 +201,21 @@ thread_local!(
 );
 
 struct CreateThread {
-    vault_channel: mpsc::Receiver<(Vault, SecStr)>
+    vault_channel: mpsc::Receiver<(Vault, SecStr, Kdf)>
 }
 
 impl CreateThread {
-    fn new<F: Fn() + Send + 'static>(passphrase: SecStr, callback: F) -> Self {
+    fn new<F: Fn() + Send + 'static>(passphrase: SecStr, unlock_time: Duration, callback: F) -> Self {
         let (tx, rx) = mpsc::channel();
 
         thread::spawn(move || {
-            let vault = Vault::new("".to_owned());
-            tx.send((vault, passphrase)).unwrap();
+            let key = VaultKey::generate().expect("Error while generating vault key");
+            let vault = Vault::new("".to_owned(), key);
+            let kdf = Kdf::calibrate(unlock_time).unwrap_or_else(|e| {
+                error!("Unable to calibrate key derivation: {}", e);
+                Kdf::default()
+            });
+            tx.send((vault, passphrase, kdf)).unwrap();
             callback();
         });
 
@@ -171,7 +223,7 @@ impl CreateThread {
     }
 }
 
-pub fn create_unlock_vault_ui() -> Box {
+pub fn create_unlock_vault_ui(app: Rc<RefCell<App>>) -> Box {
 
     let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/UnlockVault.glade");
 
@@ -183,8 +235,10 @@ pub fn create_unlock_vault_ui() -> Box {
     {
         let password_bis = password.clone();
         let unlock_bis = unlock.clone();
+        let error_bis = error.clone();
         password_bis.connect_changed(move |pwd| {
             let value = pwd.get_text();
+            error_bis.set_opacity(0.0);
             unlock_bis.set_sensitive(value.map(|t| t.len() > 0).unwrap_or_else(|| false));
         });
     }
@@ -192,9 +246,100 @@ pub fn create_unlock_vault_ui() -> Box {
     {
         let password_bis = password.clone();
         let unlock_bis = unlock.clone();
-        unlock_bis.connect_clicked(move |b| {
+        let app_bis = app.clone();
+        unlock_bis.connect_clicked(move |_| {
+            let file = app_bis.borrow().locked_vault();
+            if let Some(file) = file {
+                app_bis.borrow().set_busy();
+                let pass = SecStr::new(password_bis.get_text().unwrap_or_else(|| "".to_owned()).into_bytes());
+                let app_ter = app_bis.clone();
+                let ui = UnlockUI {
+                    password: password_bis.clone(),
+                    error: error.clone()
+                };
+                UNLOCK.with(move |unlock| {
+                    *unlock.borrow_mut() =
+                        Some((app_ter, ui, UnlockThread::new(file, pass, || { glib::idle_add(unlocked); })));
+                });
+            } else {
+                warn!("Try to unlock a vault that is not locked");
+            }
         });
     }
 
     ui
 }
+
+fn unlocked() -> Continue {
+    UNLOCK.with(move |unlock| {
+        if let Some((ref app, ref ui, ref unlock_thread)) = *unlock.borrow() {
+            match unlock_thread.vault_channel.recv().unwrap() {
+                Ok((vault, pass, kdf)) => {
+                    let set = app.borrow_mut().set_vault(vault, pass, kdf);
+                    match set {
+                        Ok(()) => {
+                            ui.password.set_text("");
+                            ui.error.set_opacity(0.0);
+                            if app.borrow_mut().purge_trash() {
+                                save_vault(app.clone());
+                            }
+                            open_pending_links(app.clone());
+                            // conflict copies wait for the passphrase
+                            check_folder(app.clone());
+                            // so do versions to merge from the git remote
+                            sync_history(app.clone());
+                        },
+                        Err(e) => {
+                            error!("Unable to identify this device: {}", e);
+                            ui.error.set_text(&device_error_message(&e));
+                            ui.error.set_opacity(1.0);
+                            app.borrow().refresh();
+                            ui.password.grab_focus();
+                        }
+                    }
+                },
+                Err(e) => {
+                    warn!("Unable to unlock vault: {}", e);
+                    ui.error.set_text(&error_message(&e));
+                    ui.error.set_opacity(1.0);
+                    app.borrow().refresh();
+                    ui.password.grab_focus();
+                }
+            }
+        }
+        *unlock.borrow_mut() = None;
+    });
+    Continue(false)
+}
+
+thread_local!(
+    static UNLOCK: RefCell<Option<(Rc<RefCell<App>>, UnlockUI, UnlockThread)>> = RefCell::new(None)
+);
+
+struct UnlockUI {
+    password: Entry,
+    error: Label
+}
+
+struct UnlockThread {
+    vault_channel: mpsc::Receiver<Result<(Vault, SecStr, Kdf)>>
+}
+
+impl UnlockThread {
+    fn new<F: Fn() + Send + 'static>(file: EncryptedVaultFile, passphrase: SecStr, callback: F) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            // files still using PBKDF2 are migrated to the default parameters on next save
+            let kdf = file.kdf().upgraded();
+            let vault =
+                file.decrypt(passphrase.clone())
+                    .and_then(|decrypted| decrypted.to_vault())
+                    .map(|vault| (vault, passphrase, kdf));
+            tx.send(vault).unwrap();
+            callback();
+        });
+
+        UnlockThread { vault_channel: rx }
+    }
+}
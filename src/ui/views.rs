@@ -12,11 +12,16 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 use gtk::*;
 
-pub fn create_views(stack: &Stack) {
+use io::error::VaultError;
+
+/// Adds the static views to the stack, returns the label of the error view.
+pub fn create_views(stack: &Stack) -> Label {
+    let (error, error_message) = create_error_view();
     stack.add_named(&create_empty_view(), "empty-vault");
-    stack.add_named(&create_error_view(), "error-vault");
+    stack.add_named(&error, "error-vault");
     stack.add_named(&create_busy_view(), "busy-vault");
     stack.add_named(&create_empty_search_view(), "empty-search");
+    error_message
 }
 
 fn create_empty_view() -> Box {
@@ -40,6 +45,48 @@ pub fn create_list_view() -> FlowBox {
 
 }
 
+pub fn create_trash_view() -> (ScrolledWindow, ListBox) {
+
+    let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/Trash.glade");
+
+    let view: ScrolledWindow = builder.get_object("trash-vault").unwrap();
+    let list: ListBox = builder.get_object("trash-list").unwrap();
+    let placeholder: Label = builder.get_object("trash-placeholder").unwrap();
+
+    list.set_placeholder(Some(&placeholder));
+
+    (view, list)
+
+}
+
+pub fn create_conflicts_view() -> (ScrolledWindow, ListBox) {
+
+    let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/Conflicts.glade");
+
+    let view: ScrolledWindow = builder.get_object("conflicts-vault").unwrap();
+    let list: ListBox = builder.get_object("conflicts-list").unwrap();
+    let placeholder: Label = builder.get_object("conflicts-placeholder").unwrap();
+
+    list.set_placeholder(Some(&placeholder));
+
+    (view, list)
+
+}
+
+pub fn create_history_view() -> (ScrolledWindow, ListBox) {
+
+    let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/History.glade");
+
+    let view: ScrolledWindow = builder.get_object("history-vault").unwrap();
+    let list: ListBox = builder.get_object("history-list").unwrap();
+    let placeholder: Label = builder.get_object("history-placeholder").unwrap();
+
+    list.set_placeholder(Some(&placeholder));
+
+    (view, list)
+
+}
+
 fn create_empty_search_view() -> Box {
 
     let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/EmptySearch.glade");
@@ -60,12 +107,40 @@ fn create_busy_view() -> Box {
 
 }
 
-fn create_error_view() -> Box {
+fn create_error_view() -> (Box, Label) {
 
     let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/ErrorVault.glade");
 
     let view: Box = builder.get_object("error-box").unwrap();
+    let message: Label = builder.get_object("error-message").unwrap();
 
-    view
+    (view, message)
+
+}
+
+/// A message explaining that this device could not be identified, see `App::set_vault`.
+pub fn device_error_message(error: &::std::io::Error) -> String {
+    format!("This device could not be identified, so the vault stays locked: {}.", error)
+}
 
+/// A message explaining the error to the user, and what they can do about it.
+pub fn error_message(error: &VaultError) -> String {
+    match *error {
+        VaultError::NotFound =>
+            "Your vault file could not be found. It may have been moved or deleted.".to_owned(),
+        VaultError::PermissionDenied =>
+            "Your vault file could not be accessed. Please check its permissions.".to_owned(),
+        VaultError::Io(ref e) =>
+            format!("Your vault file could not be read or written: {}.", e),
+        VaultError::WrongPassphrase =>
+            "Your vault could not be unlocked. Please check that your passphrase is correct. If it is, the vault file was modified.".to_owned(),
+        VaultError::Corrupted(_) =>
+            "Your vault file is corrupted. Please restore it from a backup.".to_owned(),
+        VaultError::Serialization(ref e) =>
+            format!("Your vault could not be prepared for writing: {}. The vault file was left as it was.", e),
+        VaultError::UnsupportedVersion(_) =>
+            "Your vault file was written by a newer version of Repassync. Please update Repassync to open it.".to_owned(),
+        VaultError::Crypto(ref e) =>
+            format!("An encryption error occurred: {}.", e)
+    }
 }
@@ -10,19 +10,176 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+
+use gtk::prelude::*;
 use gtk::*;
 
+use secstr::SecStr;
+
+use uuid::Uuid;
+
 use model::Entry;
 
-pub fn create_password_widget(e: &Entry) -> Frame {
+use util::OtpKind;
+
+use ui::App;
+use ui::edit::create_edit_entry_ui;
+use ui::save::save_vault;
+use ui::clipboard::{copy_secret, copy_text};
+
+pub fn create_password_widget(app: Rc<RefCell<App>>, e: &Entry) -> Frame {
 
     let builder = Builder::new_from_resource("/org/gnieh/Repassync/ui/PasswordWidget.glade");
 
     let frame: Frame = builder.get_object("password-widget-frame").unwrap();
     let name: Label = builder.get_object("password-widget-name").unwrap();
+    let edit: EventBox = builder.get_object("password-edit-events").unwrap();
+    let delete: EventBox = builder.get_object("password-delete-events").unwrap();
+    let copy: EventBox = builder.get_object("password-copy-events").unwrap();
+    let copy_user: EventBox = builder.get_object("password-copy-user-events").unwrap();
+    let copy_otp: EventBox = builder.get_object("password-copy-otp-events").unwrap();
+    let otp_label: Label = builder.get_object("password-widget-otp").unwrap();
 
     name.set_text(e.name.as_str());
 
+    {
+        let id = e.id().clone();
+        let app_bis = app.clone();
+        edit.connect_button_press_event(move |events, _| {
+            if let Some(popover) = create_edit_entry_ui(app_bis.clone(), &id) {
+                popover.set_relative_to(Some(events));
+                popover.popup();
+            }
+            Inhibit(false)
+        });
+    }
+
+    {
+        let id = e.id().clone();
+        let app_bis = app.clone();
+        copy.connect_button_press_event(move |_, _| {
+            let password = app_bis.borrow().reveal_password(&id);
+            if let Some(password) = password {
+                let timeout = app_bis.borrow().settings().clipboard_timeout;
+                copy_secret(&password, timeout);
+                app_bis.borrow_mut().notify("Password copied", None);
+            }
+            Inhibit(false)
+        });
+    }
+
+    match e.user {
+        Some(ref user) => {
+            let user = user.clone();
+            let app_bis = app.clone();
+            copy_user.connect_button_press_event(move |_, _| {
+                copy_text(user.as_str());
+                app_bis.borrow_mut().notify("Username copied", None);
+                Inhibit(false)
+            });
+        },
+        None => {
+            copy_user.set_no_show_all(true);
+            copy_user.hide();
+        }
+    }
+
+    if e.has_otp() {
+        let id = e.id().clone();
+        copy_otp.show();
+        let time_based = show_otp(&app, &id, &otp_label);
+        if time_based {
+            // refresh the code until the card is destroyed
+            let alive = Rc::new(Cell::new(true));
+            {
+                let alive_bis = alive.clone();
+                frame.connect_destroy(move |_| alive_bis.set(false));
+            }
+            let app_bis = app.clone();
+            timeout_add_seconds(1, move || {
+                Continue(alive.get() && show_otp(&app_bis, &id, &otp_label))
+            });
+        }
+    }
+
+    {
+        let id = e.id().clone();
+        let app_bis = app.clone();
+        copy_otp.connect_button_press_event(move |_, _| {
+            let otp = app_bis.borrow().otp(&id);
+            if let Some(mut otp) = otp {
+                match otp.current() {
+                    Ok((code, _)) => {
+                        let timeout = app_bis.borrow().settings().clipboard_timeout;
+                        copy_secret(&SecStr::new(code.into_bytes()), timeout);
+                        if let OtpKind::Hotp { .. } = otp.kind {
+                            // a counter based code is used once copied
+                            otp.increment();
+                            app_bis.borrow_mut().modify_vault(|vault| {
+                                if let Err(e) = vault.set_otp(&id, Some(otp.to_uri())) {
+                                    error!("Unable to update one-time password counter: {}", e);
+                                }
+                            });
+                            save_vault(app_bis.clone());
+                        }
+                        app_bis.borrow_mut().notify("One-time code copied", None);
+                    },
+                    Err(e) => {
+                        error!("Unable to generate one-time code: {}", e);
+                    }
+                }
+            }
+            Inhibit(false)
+        });
+    }
+
+    {
+        let id = e.id().clone();
+        let message = format!("“{}” moved to trash", e.name);
+        let app_bis = app.clone();
+        delete.connect_button_press_event(move |_, _| {
+            app_bis.borrow_mut().modify_vault(|vault| { vault.trash_entry(&id); });
+            save_vault(app_bis.clone());
+            let id_bis = id.clone();
+            app_bis.borrow_mut().notify(message.as_str(), Some(("Undo", ::std::boxed::Box::new(move |app| {
+                app.borrow_mut().modify_vault(|vault| { vault.restore_entry(&id_bis); });
+                save_vault(app);
+            }))));
+            Inhibit(false)
+        });
+    }
+
     frame
 
 }
+
+/// Shows the current one-time code of the entry, returns whether it is time based
+/// and needs to be refreshed.
+fn show_otp(app: &Rc<RefCell<App>>, id: &Uuid, label: &Label) -> bool {
+    let otp = app.borrow().otp(id);
+    match otp.map(|otp| otp.current()) {
+        Some(Ok((code, remaining))) => {
+            // group digits by three for readability
+            let (head, tail) = code.split_at(code.len() - 3);
+            match remaining {
+                Some(remaining) => {
+                    label.set_text(format!("{} {} · {}s", head, tail, remaining).as_str());
+                    true
+                },
+                None => {
+                    label.set_text(format!("{} {}", head, tail).as_str());
+                    false
+                }
+            }
+        },
+        Some(Err(e)) => {
+            error!("Unable to generate one-time code: {}", e);
+            false
+        },
+        None => {
+            false
+        }
+    }
+}